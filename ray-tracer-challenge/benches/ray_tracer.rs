@@ -8,7 +8,7 @@ use ray_tracer_challenge::{
     math::{point::Point3d, vector::Vec3d},
     scene::{
         camera::Camera,
-        light::PointLight,
+        light::{Falloff, PointLight},
         material::{Material, Surface},
         object::{plane::Plane, sphere::Sphere, transformed::Transformed},
         pattern::checker3d::Checker3d,
@@ -47,32 +47,33 @@ where
 
 fn test_mirror_scene() -> Scene {
     let floor = Plane {
-        material: Material {
+        material: Some(Material {
             surface: Surface::Pattern(Box::new(Checker3d {
                 a: color::white(),
                 b: color::black(),
                 transform: transformation::translation(0.0, -0.01, 0.0)
                     .try_into()
                     .unwrap(),
+                ..Default::default()
             })),
             specular: 0.0,
             reflectivity: 0.02,
             ..Default::default()
-        },
+        }),
     };
 
     let left_wall = Transformed::new(
         Plane {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(color::white()),
                 specular: 1.0,
                 reflectivity: 0.9,
                 shininess: 400.0,
                 diffuse: 0.0,
                 ..Default::default()
-            },
+            }),
         },
-        transformation::sequence(&vec![
+        transformation::sequence(&[
             transformation::rotation_x(consts::FRAC_PI_2),
             transformation::rotation_y(-consts::FRAC_PI_3),
             transformation::translation(-8.0, 0.0, 0.0),
@@ -83,16 +84,16 @@ fn test_mirror_scene() -> Scene {
 
     let right_wall = Transformed::new(
         Plane {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(color::white()),
                 specular: 1.0,
                 reflectivity: 1.0,
                 shininess: 400.0,
                 diffuse: 0.0,
                 ..Default::default()
-            },
+            }),
         },
-        transformation::sequence(&vec![
+        transformation::sequence(&[
             transformation::rotation_x(consts::FRAC_PI_2),
             transformation::rotation_y(consts::FRAC_PI_4),
             transformation::translation(10.0, 0.0, 0.0),
@@ -103,14 +104,14 @@ fn test_mirror_scene() -> Scene {
 
     let middle_wall = Transformed::new(
         Plane {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(Color::new(0.945, 0.788, 0.647)),
                 specular: 0.1,
                 shininess: 50.0,
                 ..Default::default()
-            },
+            }),
         },
-        transformation::sequence(&vec![
+        transformation::sequence(&[
             transformation::rotation_x(consts::FRAC_PI_2),
             transformation::translation(0.0, 0.0, 7.0),
         ])
@@ -120,7 +121,7 @@ fn test_mirror_scene() -> Scene {
 
     let ball = Transformed::new(
         Sphere {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(Color::new(0.059, 0.322, 0.729)),
                 diffuse: 0.3,
                 specular: 1.0,
@@ -128,7 +129,7 @@ fn test_mirror_scene() -> Scene {
                 transparency: 0.75,
                 refractive_index: 1.52,
                 ..Default::default()
-            },
+            }),
         },
         transformation::translation(0.0, 2.0, 0.0)
             .try_into()
@@ -137,7 +138,7 @@ fn test_mirror_scene() -> Scene {
 
     let inner_air_pocket = Transformed::new(
         Sphere {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(color::white()),
                 ambient: 0.0,
                 diffuse: 0.0,
@@ -146,9 +147,9 @@ fn test_mirror_scene() -> Scene {
                 refractive_index: 1.0,
                 reflectivity: 1.0,
                 ..Default::default()
-            },
+            }),
         },
-        transformation::sequence(&vec![
+        transformation::sequence(&[
             transformation::scaling(0.5, 0.5, 0.5),
             transformation::translation(0.0, 2.0, 0.0),
         ])
@@ -158,14 +159,14 @@ fn test_mirror_scene() -> Scene {
 
     let behind_wall = Transformed::new(
         Plane {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(Color::new(0.678, 0.847, 0.902)),
                 specular: 0.1,
                 shininess: 50.0,
                 ..Default::default()
-            },
+            }),
         },
-        transformation::sequence(&vec![
+        transformation::sequence(&[
             transformation::rotation_x(consts::FRAC_PI_2),
             transformation::translation(0.0, 0.0, -100.0),
         ])
@@ -176,6 +177,7 @@ fn test_mirror_scene() -> Scene {
     let light_source = PointLight {
         position: Point3d::new(-10.0, 10.0, -10.0),
         intensity: color::white(),
+        falloff: Falloff::None,
     };
 
     let world = World {
@@ -1,14 +1,16 @@
-#![allow(dead_code)]
-
-use std::{f64::consts, fs::File, io::BufReader};
+use std::{env, f64::consts, fs::File, io::BufReader, process, sync::Arc};
 
 use ray_tracer_challenge::{
-    draw::color::{self, Color},
+    draw::{
+        color::{self, Color},
+        post::{PostProcess, ToneMapping},
+    },
     io::wavefront_obj::WavefrontObj,
     math::{matrix::InvertibleMatrix, point::Point3d, vector::Vec3d},
     scene::{
+        animation::{AnimatedTransform, Easing, Frames, Keyframe, Track},
         camera::{Camera, RenderOpts},
-        light::PointLight,
+        light::{Falloff, PointLight},
         material::{Material, Surface},
         object::{
             bounded::Bounded,
@@ -16,6 +18,7 @@ use ray_tracer_challenge::{
             cube::Cube,
             cylinder::Cylinder,
             group::Group,
+            instance::Instance,
             plane::Plane,
             sphere::Sphere,
             transformed::Transformed,
@@ -32,27 +35,54 @@ use ray_tracer_challenge::{
 const RES_X: usize = 600;
 const RES_Y: usize = 400;
 
+/// Renders the scene named by the first argument, the OBJ model by default, or the model's
+/// turntable animation for "turntable"
 fn main() {
     use std::time::Instant;
     let now = Instant::now();
 
-    let scene = test_obj_world();
+    let scene = match env::args().nth(1).as_deref() {
+        None | Some("obj") => test_obj_world(),
+        Some("hexagon") => test_hexagon_world(),
+        Some("mirror") => test_mirror_world(),
+        Some("csg") => test_csg_world(),
+        Some("turntable") => {
+            render_obj_turntable();
+            return;
+        }
+        Some(other) => {
+            eprintln!(
+                "Unknown scene {}; expected obj, hexagon, mirror, csg or turntable",
+                other
+            );
+            process::exit(1);
+        }
+    };
 
     println!("Rendering scene...");
     let canvas = scene.render(&RenderOpts {
         anti_aliasing_samples: 2,
+        ..Default::default()
     });
     println!("Scene rendered.");
 
     let elapsed = now.elapsed();
     println!("Elapsed: {:.2?}", elapsed);
 
-    util::write_to_file(&canvas, "output/scene");
+    // The HDR file keeps the raw radiance; the PPM gets a filmic curve instead of hard clipping,
+    // encoded for display on an sRGB monitor
+    let post = PostProcess {
+        tone_mapping: ToneMapping::Filmic,
+        srgb: true,
+        ..Default::default()
+    };
+    util::write_to_file(&post.apply(&canvas), "output/scene");
+    util::write_hdr_to_file(&canvas, "output/scene");
 }
 
 fn hexagon_corner() -> impl Object {
     let corner = Sphere::unit();
-    let transform = transformation::sequence(&vec![
+    let transform = transformation::sequence(&[
         transformation::scaling(0.25, 0.25, 0.25),
         transformation::translation(0.0, 0.0, -1.0),
     ]);
@@ -65,7 +95,7 @@ fn hexagon_edge() -> impl Object {
         maximum: Some(1.0),
         ..Default::default()
     };
-    let transform = transformation::sequence(&vec![
+    let transform = transformation::sequence(&[
         transformation::scaling(0.25, 1.0, 0.25),
         transformation::rotation_z(-std::f64::consts::FRAC_PI_2),
         transformation::rotation_y(-std::f64::consts::FRAC_PI_6),
@@ -100,11 +130,12 @@ fn test_hexagon_world() -> Scene {
     let light_source = PointLight {
         position: Point3d::new(-10.0, 10.0, -10.0),
         intensity: color::white(),
+        falloff: Falloff::None,
     };
 
     let hexagon = Transformed::new(
         hexagon(),
-        InvertibleMatrix::try_from(transformation::sequence(&vec![
+        InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::scaling(2.0, 2.0, 2.0),
             transformation::rotation_x(-std::f64::consts::FRAC_PI_4),
             transformation::translation(0.0, 2.0, 0.0),
@@ -142,7 +173,7 @@ fn test_mirror_world() -> Scene {
     );
     let sphere_group = Bounded::new(Transformed::new(
         Group::new(vec![Box::new(gs1), Box::new(gs2)]),
-        InvertibleMatrix::try_from(transformation::sequence(&vec![
+        InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::rotation_z(std::f64::consts::FRAC_PI_2),
             transformation::translation(-2.0, 2.0, 0.0),
         ]))
@@ -150,31 +181,32 @@ fn test_mirror_world() -> Scene {
     ));
 
     let floor = Plane {
-        material: Material {
+        material: Some(Material {
             surface: Surface::Pattern(Box::new(Checker3d {
                 a: color::white(),
                 b: color::black(),
                 transform: InvertibleMatrix::try_from(transformation::translation(0.0, -0.01, 0.0))
                     .unwrap(),
+                ..Default::default()
             })),
             specular: 0.0,
             reflectivity: 0.02,
             ..Default::default()
-        },
+        }),
     };
 
     let left_wall = Transformed::new(
         Plane {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(color::white()),
                 specular: 1.0,
                 reflectivity: 0.9,
                 shininess: 400.0,
                 diffuse: 0.0,
                 ..Default::default()
-            },
+            }),
         },
-        InvertibleMatrix::try_from(transformation::sequence(&vec![
+        InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::rotation_x(consts::FRAC_PI_2),
             transformation::rotation_y(-consts::FRAC_PI_3),
             transformation::translation(-8.0, 0.0, 0.0),
@@ -184,16 +216,16 @@ fn test_mirror_world() -> Scene {
 
     let right_wall = Transformed::new(
         Plane {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(color::white()),
                 specular: 1.0,
                 reflectivity: 1.0,
                 shininess: 400.0,
                 diffuse: 0.0,
                 ..Default::default()
-            },
+            }),
         },
-        InvertibleMatrix::try_from(transformation::sequence(&vec![
+        InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::rotation_x(consts::FRAC_PI_2),
             transformation::rotation_y(consts::FRAC_PI_4),
             transformation::translation(10.0, 0.0, 0.0),
@@ -203,14 +235,14 @@ fn test_mirror_world() -> Scene {
 
     let middle_wall = Transformed::new(
         Plane {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(Color::new(0.945, 0.788, 0.647)),
                 specular: 0.1,
                 shininess: 50.0,
                 ..Default::default()
-            },
+            }),
         },
-        InvertibleMatrix::try_from(transformation::sequence(&vec![
+        InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::rotation_x(consts::FRAC_PI_2),
             transformation::translation(0.0, 0.0, 7.0),
         ]))
@@ -219,7 +251,7 @@ fn test_mirror_world() -> Scene {
 
     let ball = Transformed::new(
         Sphere {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(Color::new(0.059, 0.322, 0.729)),
                 diffuse: 0.3,
                 specular: 1.0,
@@ -227,14 +259,14 @@ fn test_mirror_world() -> Scene {
                 transparency: 0.75,
                 refractive_index: 1.52,
                 ..Default::default()
-            },
+            }),
         },
         InvertibleMatrix::try_from(transformation::translation(0.0, 2.0, 0.0)).unwrap(),
     );
 
     let inner_air_pocket = Transformed::new(
         Sphere {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(color::white()),
                 ambient: 0.0,
                 diffuse: 0.0,
@@ -243,9 +275,9 @@ fn test_mirror_world() -> Scene {
                 refractive_index: 1.0,
                 reflectivity: 1.0,
                 ..Default::default()
-            },
+            }),
         },
-        InvertibleMatrix::try_from(transformation::sequence(&vec![
+        InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::scaling(0.5, 0.5, 0.5),
             transformation::translation(0.0, 2.0, 0.0),
         ]))
@@ -254,29 +286,30 @@ fn test_mirror_world() -> Scene {
 
     let behind_cube = Transformed::new(
         Cube {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Pattern(Box::new(Stripe {
                     a: Color::new(0.545, 0.0, 0.0),
                     b: Color::new(0.0, 0.392, 0.0),
                     transform: InvertibleMatrix::try_from(transformation::scaling(0.2, 1.0, 1.0))
                         .unwrap(),
+                    ..Default::default()
                 })),
                 ..Default::default()
-            },
+            }),
         },
         InvertibleMatrix::try_from(transformation::translation(3.0, 0.0, -10.0)).unwrap(),
     );
 
     let behind_wall = Transformed::new(
         Plane {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(Color::new(0.678, 0.847, 0.902)),
                 specular: 0.1,
                 shininess: 50.0,
                 ..Default::default()
-            },
+            }),
         },
-        InvertibleMatrix::try_from(transformation::sequence(&vec![
+        InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::rotation_x(consts::FRAC_PI_2),
             transformation::translation(0.0, 0.0, -100.0),
         ]))
@@ -286,6 +319,7 @@ fn test_mirror_world() -> Scene {
     let light_source = PointLight {
         position: Point3d::new(-10.0, 10.0, -10.0),
         intensity: color::white(),
+        falloff: Falloff::None,
     };
 
     let world = World {
@@ -318,22 +352,33 @@ fn test_mirror_world() -> Scene {
 }
 
 fn test_obj_world() -> Scene {
-    let obj_file = File::open("objs/spot_triangulated.obj").unwrap();
-    let reader = BufReader::new(obj_file);
-
-    let obj = Transformed::new(
-        WavefrontObj::parse(reader).to_object(),
+    obj_world(
+        load_obj(),
         InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::rotation_y(std::f64::consts::FRAC_PI_4),
             transformation::scaling(10.0, 10.0, 10.0),
             transformation::translation(0.0, 5.0, 0.0),
         ]))
         .unwrap(),
-    );
+    )
+}
+
+fn load_obj() -> Arc<dyn Object> {
+    let obj_file = File::open("objs/spot_triangulated.obj").unwrap();
+    let reader = BufReader::new(obj_file);
+
+    Arc::new(WavefrontObj::parse(reader).to_object())
+}
+
+/// Places the model, which is shared rather than copied so that it can be reused from scene to
+/// scene
+fn obj_world(model: Arc<dyn Object>, transform: InvertibleMatrix<4>) -> Scene {
+    let obj = Transformed::new(Instance::new(model), transform);
 
     let light_source = PointLight {
         position: Point3d::new(-2.0, 20.0, -30.0),
         intensity: color::white(),
+        falloff: Falloff::None,
     };
 
     let world = World {
@@ -355,18 +400,57 @@ fn test_obj_world() -> Scene {
     Scene { camera, world }
 }
 
+/// Renders the model spinning once around, one frame for every 10 degrees
+fn render_obj_turntable() {
+    let spin = AnimatedTransform {
+        scale: Track::constant(Vec3d::new(10.0, 10.0, 10.0)),
+        rotation: Track::new(vec![
+            Keyframe {
+                time: 0.0,
+                value: Vec3d::new(0.0, 0.0, 0.0),
+                easing: Easing::Linear,
+            },
+            Keyframe {
+                time: 1.0,
+                value: Vec3d::new(0.0, 2.0 * consts::PI, 0.0),
+                easing: Easing::Linear,
+            },
+        ])
+        .unwrap(),
+        translation: Track::constant(Vec3d::new(0.0, 5.0, 0.0)),
+    };
+    let frames = Frames {
+        start: 0.0,
+        end: 1.0,
+        rate: 36.0,
+    };
+
+    let model = load_obj();
+    let scene_at = |time: f64| {
+        obj_world(
+            Arc::clone(&model),
+            InvertibleMatrix::try_from(spin.at(time)).unwrap(),
+        )
+    };
+    frames.render(&Default::default(), scene_at, |n, canvas| {
+        println!("Rendered frame {}.", n);
+        util::write_frame_to_file(canvas, "output/turntable", n);
+    });
+}
+
 fn test_csg_world() -> Scene {
     let room = Transformed::new(
         Cube {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Pattern(Box::new(Checker3d {
                     a: Color::new(0.6, 0.6, 0.6),
                     b: Color::new(0.7, 0.7, 0.7),
-                    transform: InvertibleMatrix::try_from(transformation::sequence(&vec![
+                    transform: InvertibleMatrix::try_from(transformation::sequence(&[
                         transformation::translation(0.01, 0.01, 0.01),
                         transformation::scaling(0.02, 0.02, 0.02),
                     ]))
                     .unwrap(),
+                    ..Default::default()
                 })),
                 reflectivity: 0.0,
                 ambient: 0.5,
@@ -374,45 +458,47 @@ fn test_csg_world() -> Scene {
                 diffuse: 0.3,
                 specular: 0.3,
                 ..Default::default()
-            },
+            }),
         },
         InvertibleMatrix::try_from(transformation::scaling(50.0, 50.0, 50.0)).unwrap(),
     );
 
-    let hollow_circle = Csg::<Box<dyn Object>> {
+    let hollow_circle = Csg {
         left: Box::new(Sphere {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(color::green()),
                 ..Default::default()
-            },
+            }),
         }),
         right: Box::new(Transformed::new(
             Sphere {
-                material: Material {
+                material: Some(Material {
                     surface: Surface::Color(color::blue()),
                     ..Default::default()
-                },
+                }),
             },
             InvertibleMatrix::try_from(transformation::scaling(0.7, 0.7, 0.7)).unwrap(),
         )),
         operation: CsgOperation::Difference,
+        material: None,
     };
-    let object = Csg::<Box<dyn Object>> {
+    let object = Csg {
         left: Box::new(hollow_circle),
         right: Box::new(Transformed::new(
             Cube {
-                material: Material {
+                material: Some(Material {
                     surface: Surface::Color(color::red()),
                     ..Default::default()
-                },
+                }),
             },
             InvertibleMatrix::try_from(transformation::translation(1.0, 0.0, 0.0)).unwrap(),
         )),
         operation: CsgOperation::Difference,
+        material: None,
     };
     let object_transformed = Transformed::new(
         object,
-        InvertibleMatrix::try_from(transformation::sequence(&vec![
+        InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::rotation_y(std::f64::consts::FRAC_PI_6),
             transformation::scaling(7.0, 7.0, 7.0),
         ]))
@@ -422,11 +508,13 @@ fn test_csg_world() -> Scene {
     let light_source_1 = PointLight {
         position: Point3d::new(-2.0, 20.0, -30.0),
         intensity: Color::new(0.5, 0.5, 0.5),
+        falloff: Falloff::None,
     };
 
     let light_source_2 = PointLight {
         position: Point3d::new(10.0, 20.0, -30.0),
         intensity: Color::new(0.5, 0.5, 0.5),
+        falloff: Falloff::None,
     };
 
     let world = World {
@@ -39,6 +39,33 @@ impl Canvas {
         idx.and_then(|i| self.data.get(i))
     }
 
+    /// Build a new canvas of the same size by transforming every pixel of this one
+    pub fn map<F: Fn(&Color) -> Color>(&self, f: F) -> Canvas {
+        Canvas {
+            width: self.width,
+            height: self.height,
+            data: self.data.iter().map(f).collect(),
+        }
+    }
+
+    /// Copy `other` into this canvas with its top-left corner at `origin`, scaling each of its
+    /// pixels up to a `scale` x `scale` block. Only the first `size` pixels across and down are
+    /// written, and pixels falling outside this canvas are discarded.
+    pub fn composite(
+        &mut self,
+        other: &Canvas,
+        origin: (usize, usize),
+        scale: usize,
+        size: (usize, usize),
+    ) {
+        for y in 0..(other.height * scale).min(size.1) {
+            for x in 0..(other.width * scale).min(size.0) {
+                let color = &other.data[other.width * (y / scale) + x / scale];
+                self.write((origin.0 + x, origin.1 + y), color.clone());
+            }
+        }
+    }
+
     fn to_idx(&self, x: usize, y: usize) -> Option<usize> {
         if x >= self.width || y >= self.height {
             None
@@ -95,6 +122,83 @@ impl Canvas {
 
         s
     }
+
+    /// Encode the canvas as a little-endian Portable Float Map, preserving values outside [0, 1]
+    pub fn pfm(&self) -> Vec<u8> {
+        let mut data = format!("PF\n{} {}\n-1.0\n", self.width, self.height).into_bytes();
+
+        // PFM stores rows from bottom to top
+        for row in self.data.chunks(self.width).rev() {
+            for color in row {
+                for c in [color.r(), color.g(), color.b()] {
+                    data.extend_from_slice(&(c as f32).to_le_bytes());
+                }
+            }
+        }
+
+        data
+    }
+
+    /// Encode the canvas as a Radiance RGBE (.hdr) image, preserving values above 1
+    pub fn hdr(&self) -> Vec<u8> {
+        let mut data = format!(
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            self.height, self.width
+        )
+        .into_bytes();
+
+        let use_rle = (8..0x8000).contains(&self.width);
+        for row in self.data.chunks(self.width) {
+            let pixels = row.iter().map(to_rgbe).collect::<Vec<_>>();
+            if use_rle {
+                let [hi, lo] = (self.width as u16).to_be_bytes();
+                data.extend_from_slice(&[2, 2, hi, lo]);
+                // Each component is stored separately, as runs of up to 128 literal values
+                for component in 0..4 {
+                    let values = pixels.iter().map(|p| p[component]).collect::<Vec<_>>();
+                    for chunk in values.chunks(128) {
+                        data.push(chunk.len() as u8);
+                        data.extend_from_slice(chunk);
+                    }
+                }
+            } else {
+                pixels.iter().for_each(|p| data.extend_from_slice(p));
+            }
+        }
+
+        data
+    }
+}
+
+/// The largest value RGBE can hold: a full mantissa with the largest exponent
+const RGBE_MAX: f64 = 255.0 / 256.0 * 1.7014118346046923e38;
+
+/// Encode a color as RGBE: three 8-bit mantissas sharing an 8-bit exponent. Values too large to
+/// hold, including infinity, are stored as the largest one, and NaN is stored as 0.
+fn to_rgbe(color: &Color) -> [u8; 4] {
+    let clamp = |c: f64| {
+        if c.is_nan() {
+            0.0
+        } else {
+            c.clamp(0.0, RGBE_MAX)
+        }
+    };
+    let (r, g, b) = (clamp(color.r()), clamp(color.g()), clamp(color.b()));
+    let v = r.max(g).max(b);
+
+    if v < 1e-32 {
+        [0, 0, 0, 0]
+    } else {
+        // v = m * 2^e with m in [0.5, 1)
+        let e = v.log2().floor() as i32 + 1;
+        let scale = 256.0 / 2f64.powi(e);
+        [
+            (r * scale).min(255.0) as u8,
+            (g * scale).min(255.0) as u8,
+            (b * scale).min(255.0) as u8,
+            (e + 128) as u8,
+        ]
+    }
 }
 
 #[cfg(test)]
@@ -125,6 +229,44 @@ mod tests {
         assert_eq!(c.at(2, 3).expect("pixel is defined"), &r);
     }
 
+    #[test]
+    fn compositing_a_canvas_into_another() {
+        let mut c = Canvas::new(10, 20);
+        let mut other = Canvas::new_with_color(2, 1, &Color::new(0.0, 1.0, 0.0));
+        other.write((1, 0), Color::new(0.0, 0.0, 1.0));
+
+        c.composite(&other, (3, 4), 1, (2, 1));
+
+        assert_eq!(c.at(2, 4), Some(&Color::new(0.0, 0.0, 0.0)));
+        assert_eq!(c.at(3, 4), Some(&Color::new(0.0, 1.0, 0.0)));
+        assert_eq!(c.at(4, 4), Some(&Color::new(0.0, 0.0, 1.0)));
+        assert_eq!(c.at(5, 4), Some(&Color::new(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn compositing_a_scaled_canvas_clips_to_the_destination() {
+        let mut c = Canvas::new(4, 4);
+        let other = Canvas::new_with_color(2, 2, &Color::new(1.0, 1.0, 1.0));
+
+        c.composite(&other, (1, 1), 2, (4, 4));
+
+        assert_eq!(c.at(0, 0), Some(&Color::new(0.0, 0.0, 0.0)));
+        assert_eq!(c.at(1, 1), Some(&Color::new(1.0, 1.0, 1.0)));
+        assert_eq!(c.at(3, 3), Some(&Color::new(1.0, 1.0, 1.0)));
+    }
+
+    #[test]
+    fn compositing_writes_no_more_than_the_given_size() {
+        let mut c = Canvas::new(6, 6);
+        let other = Canvas::new_with_color(2, 2, &Color::new(1.0, 1.0, 1.0));
+
+        c.composite(&other, (1, 1), 2, (3, 3));
+
+        assert_eq!(c.at(3, 3), Some(&Color::new(1.0, 1.0, 1.0)));
+        assert_eq!(c.at(4, 3), Some(&Color::new(0.0, 0.0, 0.0)));
+        assert_eq!(c.at(3, 4), Some(&Color::new(0.0, 0.0, 0.0)));
+    }
+
     mod ppm {
         use super::*;
 
@@ -190,4 +332,93 @@ mod tests {
             assert!(ppm.ends_with('\n'));
         }
     }
+
+    #[test]
+    fn mapping_a_canvas() {
+        let mut c = Canvas::new(2, 1);
+        c.write((1, 0), Color::new(0.5, 1.0, 2.0));
+
+        let mapped = c.map(|color| color * 2.0);
+
+        assert_eq!(mapped.at(0, 0), Some(&Color::new(0.0, 0.0, 0.0)));
+        assert_eq!(mapped.at(1, 0), Some(&Color::new(1.0, 2.0, 4.0)));
+    }
+
+    mod pfm {
+        use super::*;
+
+        #[test]
+        fn pfm_stores_unclamped_floats_bottom_row_first() {
+            let mut c = Canvas::new(1, 2);
+            c.write((0, 0), Color::new(1.5, -0.5, 0.25));
+
+            let pfm = c.pfm();
+            let header = b"PF\n1 2\n-1.0\n";
+
+            assert_eq!(&pfm[..header.len()], header);
+            let floats = pfm[header.len()..]
+                .chunks(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+                .collect::<Vec<_>>();
+            assert_eq!(floats, vec![0.0, 0.0, 0.0, 1.5, -0.5, 0.25]);
+        }
+    }
+
+    mod hdr {
+        use super::*;
+
+        const HEADER: &[u8] = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n";
+
+        #[test]
+        fn encoding_colors_as_rgbe() {
+            assert_eq!(to_rgbe(&Color::new(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+            assert_eq!(to_rgbe(&Color::new(1.0, 0.5, 0.25)), [128, 64, 32, 129]);
+            assert_eq!(to_rgbe(&Color::new(8.0, 0.0, -1.0)), [128, 0, 0, 132]);
+        }
+
+        #[test]
+        fn encoding_values_out_of_range_as_rgbe() {
+            assert_eq!(to_rgbe(&Color::new(1e300, 0.0, 0.0)), [255, 0, 0, 255]);
+            assert_eq!(
+                to_rgbe(&Color::new(f64::INFINITY, f64::INFINITY, 1.0)),
+                [255, 255, 0, 255]
+            );
+            assert_eq!(to_rgbe(&Color::new(f64::NAN, 0.5, 0.25)), [0, 128, 64, 128]);
+            assert_eq!(
+                to_rgbe(&Color::new(f64::NAN, f64::NAN, f64::NAN)),
+                [0, 0, 0, 0]
+            );
+        }
+
+        #[test]
+        fn narrow_images_use_flat_scanlines() {
+            let c = Canvas::new_with_color(2, 1, &Color::new(1.0, 0.5, 0.25));
+
+            let hdr = c.hdr();
+            let header = [HEADER, b"-Y 1 +X 2\n"].concat();
+
+            assert_eq!(&hdr[..header.len()], &header[..]);
+            assert_eq!(&hdr[header.len()..], &[128, 64, 32, 129, 128, 64, 32, 129]);
+        }
+
+        #[test]
+        fn wide_images_use_run_length_encoded_scanlines() {
+            let c = Canvas::new_with_color(8, 1, &Color::new(1.0, 0.5, 0.25));
+
+            let hdr = c.hdr();
+            let header = [HEADER, b"-Y 1 +X 8\n"].concat();
+            let scanline = &hdr[header.len()..];
+
+            assert_eq!(&scanline[..4], &[2, 2, 0, 8]);
+            assert_eq!(scanline.len(), 4 + 4 * 9);
+            assert_eq!(
+                &scanline[4..13],
+                &[8, 128, 128, 128, 128, 128, 128, 128, 128]
+            );
+            assert_eq!(
+                &scanline[31..40],
+                &[8, 129, 129, 129, 129, 129, 129, 129, 129]
+            );
+        }
+    }
 }
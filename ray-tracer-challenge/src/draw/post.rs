@@ -0,0 +1,172 @@
+use super::{canvas::Canvas, color::Color};
+
+/// Curve used to compress an HDR color into the displayable [0, 1] range
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToneMapping {
+    /// Clip each channel to [0, 1]
+    Clamp,
+    /// c / (1 + c)
+    Reinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve
+    Aces,
+    /// John Hable's Uncharted 2 filmic curve
+    Filmic,
+}
+
+impl ToneMapping {
+    fn map(&self, c: f64) -> f64 {
+        let c = c.max(0.0);
+        match self {
+            ToneMapping::Clamp => c,
+            ToneMapping::Reinhard => c / (1.0 + c),
+            ToneMapping::Aces => (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14),
+            ToneMapping::Filmic => {
+                const EXPOSURE_BIAS: f64 = 2.0;
+                const WHITE_POINT: f64 = 11.2;
+                hable(c * EXPOSURE_BIAS) / hable(WHITE_POINT)
+            }
+        }
+        .clamp(0.0, 1.0)
+    }
+}
+
+fn hable(x: f64) -> f64 {
+    const A: f64 = 0.15;
+    const B: f64 = 0.50;
+    const C: f64 = 0.10;
+    const D: f64 = 0.20;
+    const E: f64 = 0.02;
+    const F: f64 = 0.30;
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+/// Encode a linear value with the sRGB transfer function
+pub fn srgb_encode(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Steps that turn a rendered, linear HDR canvas into a displayable image
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostProcess {
+    /// Exposure adjustment in stops: each stop doubles the brightness
+    pub exposure: f64,
+    pub tone_mapping: ToneMapping,
+    /// Whether to gamma encode the result for display on an sRGB monitor
+    pub srgb: bool,
+}
+
+impl PostProcess {
+    pub fn apply(&self, canvas: &Canvas) -> Canvas {
+        let scale = 2f64.powf(self.exposure);
+        canvas.map(|color| {
+            let channel = |c: f64| {
+                let mapped = self.tone_mapping.map(c * scale);
+                if self.srgb {
+                    srgb_encode(mapped)
+                } else {
+                    mapped
+                }
+            };
+            Color::new(channel(color.r()), channel(color.g()), channel(color.b()))
+        })
+    }
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            tone_mapping: ToneMapping::Clamp,
+            srgb: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{draw::color, math::util::test_utils::are_within_tolerance};
+
+    use super::*;
+
+    #[test]
+    fn the_default_post_process_only_clamps() {
+        let mut c = Canvas::new(2, 1);
+        c.write((0, 0), Color::new(0.25, 1.5, -0.5));
+
+        let result = PostProcess::default().apply(&c);
+
+        assert_eq!(result.at(0, 0), Some(&Color::new(0.25, 1.0, 0.0)));
+        assert_eq!(result.at(1, 0), Some(&color::black()));
+    }
+
+    #[test]
+    fn exposure_is_measured_in_stops() {
+        let c = Canvas::new_with_color(1, 1, &Color::new(0.1, 0.2, 0.4));
+        let post = PostProcess {
+            exposure: 1.0,
+            ..Default::default()
+        };
+
+        let result = post.apply(&c);
+
+        assert_eq!(result.at(0, 0), Some(&Color::new(0.2, 0.4, 0.8)));
+    }
+
+    #[test]
+    fn reinhard_tone_mapping() {
+        assert_eq!(ToneMapping::Reinhard.map(0.0), 0.0);
+        assert_eq!(ToneMapping::Reinhard.map(1.0), 0.5);
+        assert_eq!(ToneMapping::Reinhard.map(3.0), 0.75);
+    }
+
+    #[test]
+    fn aces_tone_mapping_saturates_bright_values() {
+        assert_eq!(ToneMapping::Aces.map(0.0), 0.0);
+        assert!(are_within_tolerance(
+            ToneMapping::Aces.map(0.18),
+            0.26689,
+            1e-5
+        ));
+        assert_eq!(ToneMapping::Aces.map(100.0), 1.0);
+    }
+
+    #[test]
+    fn filmic_tone_mapping_maps_the_white_point_to_one() {
+        assert!(are_within_tolerance(
+            ToneMapping::Filmic.map(0.0),
+            0.0,
+            1e-9
+        ));
+        assert!(are_within_tolerance(
+            ToneMapping::Filmic.map(5.6),
+            1.0,
+            1e-9
+        ));
+    }
+
+    #[test]
+    fn tone_mapping_is_monotonic() {
+        for mapping in [
+            ToneMapping::Reinhard,
+            ToneMapping::Aces,
+            ToneMapping::Filmic,
+        ] {
+            let values = (0..100)
+                .map(|i| mapping.map(i as f64 * 0.1))
+                .collect::<Vec<_>>();
+            assert!(values.windows(2).all(|w| w[0] <= w[1]));
+        }
+    }
+
+    #[test]
+    fn srgb_encoding() {
+        assert_eq!(srgb_encode(0.0), 0.0);
+        assert!(are_within_tolerance(srgb_encode(1.0), 1.0, 1e-9));
+        assert!(are_within_tolerance(srgb_encode(0.002), 0.02584, 1e-9));
+        assert!(are_within_tolerance(srgb_encode(0.5), 0.73536, 1e-5));
+    }
+}
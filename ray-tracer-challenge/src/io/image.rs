@@ -0,0 +1,357 @@
+use std::io::{BufRead, Read};
+
+use crate::draw::{
+    canvas::Canvas,
+    color::{self, Color},
+};
+
+/// Read a Portable Float Map, in either color ("PF") or grayscale ("Pf") format
+pub fn read_pfm(mut reader: impl BufRead) -> Result<Canvas, String> {
+    let channels = match read_line(&mut reader)?.as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        other => return Err(format!("Unrecognized PFM type: {}", other)),
+    };
+    let (width, height) = parse_dimensions(&read_line(&mut reader)?)?;
+    let scale = read_line(&mut reader)?
+        .parse::<f64>()
+        .map_err(|e| format!("Invalid PFM scale: {}", e))?;
+    let little_endian = scale < 0.0;
+
+    let size = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(channels * 4))
+        .ok_or_else(|| format!("PFM dimensions are too large: {}x{}", width, height))?;
+    let data = read_remaining(&mut reader)?;
+    if data.len() < size {
+        return Err(format!(
+            "PFM pixel data is truncated: expected {} bytes, found {}",
+            size,
+            data.len()
+        ));
+    }
+
+    let values = data[..size]
+        .chunks(4)
+        .map(|b| {
+            let bytes = b.try_into().unwrap();
+            if little_endian {
+                f32::from_le_bytes(bytes)
+            } else {
+                f32::from_be_bytes(bytes)
+            }
+        })
+        .map(f64::from)
+        .collect::<Vec<_>>();
+
+    let mut canvas = Canvas::new(width, height);
+    for (i, pixel) in values.chunks(channels).enumerate() {
+        // PFM stores rows from bottom to top
+        let (x, y) = (i % width, height - 1 - i / width);
+        let color = match pixel {
+            [r, g, b] => Color::new(*r, *g, *b),
+            [v] => Color::new(*v, *v, *v),
+            _ => unreachable!(),
+        };
+        canvas.write((x, y), color);
+    }
+
+    Ok(canvas)
+}
+
+/// Read a Radiance RGBE (.hdr) image with the standard "-Y height +X width" orientation
+pub fn read_hdr(mut reader: impl BufRead) -> Result<Canvas, String> {
+    if !read_line(&mut reader)?.starts_with("#?") {
+        return Err("Missing Radiance signature".to_string());
+    }
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        } else if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(format!("Unsupported Radiance format: {}", format));
+            }
+        }
+    }
+
+    let resolution = read_line(&mut reader)?;
+    let (width, height) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", h, "+X", w] => parse_dimensions(&format!("{} {}", w, h))?,
+        _ => return Err(format!("Unsupported resolution line: {}", resolution)),
+    };
+
+    // Even a fully run-length encoded scanline takes a header plus two bytes per run of each
+    // component, so reject headers claiming more scanlines than the data could hold
+    let min_scanline = if (8..0x8000).contains(&width) {
+        Some(4 + 4 * 2 * width.div_ceil(127))
+    } else {
+        width.checked_mul(4)
+    };
+    let data = read_remaining(&mut reader)?;
+    match min_scanline.and_then(|n| n.checked_mul(height)) {
+        Some(n) if n <= data.len() => (),
+        _ => {
+            return Err(format!(
+                "Radiance pixel data is too short for {}x{}",
+                width, height
+            ))
+        }
+    }
+
+    let mut data = &data[..];
+    let mut canvas = Canvas::new(width, height);
+    for y in 0..height {
+        let scanline = read_scanline(&mut data, width)?;
+        for (x, rgbe) in scanline.iter().enumerate() {
+            canvas.write((x, y), from_rgbe(rgbe));
+        }
+    }
+
+    Ok(canvas)
+}
+
+fn read_scanline(reader: &mut impl BufRead, width: usize) -> Result<Vec<[u8; 4]>, String> {
+    let mut first = [0u8; 4];
+    read_bytes(reader, &mut first)?;
+
+    let is_rle = first[0] == 2
+        && first[1] == 2
+        && first[2] & 0x80 == 0
+        && (8..0x8000).contains(&width)
+        && u16::from_be_bytes([first[2], first[3]]) as usize == width;
+
+    let mut pixels = vec![[0u8; 4]; width];
+    if is_rle {
+        // Each component is stored separately as a series of runs and literal chunks
+        for component in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let mut count = [0u8; 1];
+                read_bytes(reader, &mut count)?;
+                let (is_run, count) = if count[0] > 128 {
+                    (true, count[0] as usize - 128)
+                } else {
+                    (false, count[0] as usize)
+                };
+                if count == 0 || x + count > width {
+                    return Err("Invalid run length in scanline".to_string());
+                }
+
+                if is_run {
+                    let mut value = [0u8; 1];
+                    read_bytes(reader, &mut value)?;
+                    pixels[x..x + count]
+                        .iter_mut()
+                        .for_each(|p| p[component] = value[0]);
+                } else {
+                    let mut values = vec![0u8; count];
+                    read_bytes(reader, &mut values)?;
+                    pixels[x..x + count]
+                        .iter_mut()
+                        .zip(values)
+                        .for_each(|(p, v)| p[component] = v);
+                }
+                x += count;
+            }
+        }
+    } else {
+        pixels[0] = first;
+        for pixel in pixels.iter_mut().skip(1) {
+            read_bytes(reader, pixel)?;
+        }
+    }
+
+    Ok(pixels)
+}
+
+fn from_rgbe(rgbe: &[u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        color::black()
+    } else {
+        let f = 2f64.powi(rgbe[3] as i32 - (128 + 8));
+        Color::new(
+            (rgbe[0] as f64 + 0.5) * f,
+            (rgbe[1] as f64 + 0.5) * f,
+            (rgbe[2] as f64 + 0.5) * f,
+        )
+    }
+}
+
+fn read_line(reader: &mut impl BufRead) -> Result<String, String> {
+    let mut bytes = Vec::new();
+    reader
+        .read_until(b'\n', &mut bytes)
+        .map_err(|e| format!("Unable to read header: {}", e))?;
+    if bytes.is_empty() {
+        return Err("Unexpected end of file".to_string());
+    }
+    String::from_utf8(bytes)
+        .map(|s| s.trim().to_string())
+        .map_err(|_| "Header is not valid text".to_string())
+}
+
+fn read_remaining(reader: &mut impl Read) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    reader
+        .read_to_end(&mut data)
+        .map_err(|e| format!("Unable to read pixel data: {}", e))?;
+    Ok(data)
+}
+
+fn read_bytes(reader: &mut impl Read, buf: &mut [u8]) -> Result<(), String> {
+    reader
+        .read_exact(buf)
+        .map_err(|e| format!("Unable to read pixel data: {}", e))
+}
+
+fn parse_dimensions(line: &str) -> Result<(usize, usize), String> {
+    let dims = line
+        .split_whitespace()
+        .map(|s| s.parse::<usize>().ok())
+        .collect::<Option<Vec<_>>>();
+
+    match dims.as_deref() {
+        Some([w, h]) if *w > 0 && *h > 0 => Ok((*w, *h)),
+        _ => Err(format!("Invalid image dimensions: {}", line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::draw::color;
+
+    use super::*;
+
+    fn test_canvas(width: usize) -> Canvas {
+        let mut c = Canvas::new(width, 2);
+        c.write((0, 0), Color::new(1.0, 0.5, 0.25));
+        c.write((width - 1, 1), Color::new(8.0, 0.0, 3.0));
+        c
+    }
+
+    mod pfm {
+        use super::*;
+
+        #[test]
+        fn reading_a_written_pfm_round_trips() {
+            let c = test_canvas(3);
+
+            let read = read_pfm(&c.pfm()[..]).unwrap();
+
+            assert_eq!(read.width(), 3);
+            assert_eq!(read.height(), 2);
+            assert_eq!(read.at(0, 0), c.at(0, 0));
+            assert_eq!(read.at(2, 1), c.at(2, 1));
+            assert_eq!(read.at(1, 1), Some(&color::black()));
+        }
+
+        #[test]
+        fn reading_a_big_endian_grayscale_pfm() {
+            let mut data = b"Pf\n2 1\n1.0\n".to_vec();
+            data.extend_from_slice(&0.5f32.to_be_bytes());
+            data.extend_from_slice(&2.0f32.to_be_bytes());
+
+            let read = read_pfm(&data[..]).unwrap();
+
+            assert_eq!(read.at(0, 0), Some(&Color::new(0.5, 0.5, 0.5)));
+            assert_eq!(read.at(1, 0), Some(&Color::new(2.0, 2.0, 2.0)));
+        }
+
+        #[test]
+        fn reading_a_truncated_pfm_fails() {
+            let data = b"PF\n2 1\n-1.0\n\0\0\0\0";
+
+            assert!(read_pfm(&data[..]).is_err());
+        }
+
+        #[test]
+        fn reading_a_pfm_with_overflowing_dimensions_fails() {
+            let data = b"PF\n18446744073709551615 18446744073709551615\n-1.0\n\0\0\0\0";
+
+            assert!(read_pfm(&data[..]).is_err());
+        }
+
+        #[test]
+        fn reading_a_pfm_larger_than_its_data_fails() {
+            let data = b"PF\n100000 100000\n-1.0\n\0\0\0\0";
+
+            assert!(read_pfm(&data[..]).is_err());
+        }
+    }
+
+    mod hdr {
+        use super::*;
+
+        fn assert_round_trips(width: usize) {
+            let c = test_canvas(width);
+
+            let read = read_hdr(&c.hdr()[..]).unwrap();
+
+            assert_eq!(read.width(), width);
+            assert_eq!(read.height(), 2);
+            // Channels share an exponent, so precision is relative to the brightest channel
+            let tolerance = |a: &Color, b: &Color| {
+                let max = b.r().max(b.g()).max(b.b());
+                [(a.r(), b.r()), (a.g(), b.g()), (a.b(), b.b())]
+                    .iter()
+                    .all(|(x, y)| (x - y).abs() <= max / 128.0)
+            };
+            assert!(tolerance(read.at(0, 0).unwrap(), c.at(0, 0).unwrap()));
+            assert!(tolerance(
+                read.at(width - 1, 1).unwrap(),
+                c.at(width - 1, 1).unwrap()
+            ));
+            assert_eq!(read.at(1, 1), Some(&color::black()));
+        }
+
+        #[test]
+        fn reading_a_written_flat_hdr_round_trips() {
+            assert_round_trips(3);
+        }
+
+        #[test]
+        fn reading_a_written_run_length_encoded_hdr_round_trips() {
+            assert_round_trips(10);
+        }
+
+        #[test]
+        fn reading_runs_of_repeated_values() {
+            let mut data = b"#?RADIANCE\n\n-Y 1 +X 8\n".to_vec();
+            data.extend_from_slice(&[2, 2, 0, 8]);
+            for value in [128, 64, 0, 129] {
+                data.extend_from_slice(&[128 + 8, value]);
+            }
+
+            let read = read_hdr(&data[..]).unwrap();
+
+            for x in 0..8 {
+                assert_eq!(
+                    read.at(x, 0),
+                    Some(&Color::new(128.5 / 128.0, 64.5 / 128.0, 0.5 / 128.0))
+                );
+            }
+        }
+
+        #[test]
+        fn reading_an_unsupported_orientation_fails() {
+            let data = b"#?RADIANCE\n\n+Y 1 +X 1\n\0\0\0\0";
+
+            assert!(read_hdr(&data[..]).is_err());
+        }
+
+        #[test]
+        fn reading_an_hdr_with_overflowing_dimensions_fails() {
+            let data = b"#?RADIANCE\n\n-Y 18446744073709551615 +X 18446744073709551615\n\0\0\0\0";
+
+            assert!(read_hdr(&data[..]).is_err());
+        }
+
+        #[test]
+        fn reading_an_hdr_larger_than_its_data_fails() {
+            let data = b"#?RADIANCE\n\n-Y 100000 +X 100000\n\x02\x02\x00\x08";
+
+            assert!(read_hdr(&data[..]).is_err());
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use super::point::Point3d;
+
+/// Points in space, each carrying a value, arranged so that the ones near a given point can be
+/// found without checking every one
+pub struct KdTree<T> {
+    /// A balanced tree laid out in place: each slice's middle element splits the rest of the slice
+    /// along the axis for its depth, x then y then z
+    nodes: Vec<(Point3d, T)>,
+}
+
+impl<T> KdTree<T> {
+    pub fn new(mut items: Vec<(Point3d, T)>) -> Self {
+        build(&mut items, 0);
+        KdTree { nodes: items }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Calls `f` with every point no farther than `radius` from `center`, in no particular order
+    pub fn within(&self, center: &Point3d, radius: f64, mut f: impl FnMut(&Point3d, &T)) {
+        visit(&self.nodes, 0, center, radius * radius, &mut f);
+    }
+}
+
+fn coordinate(p: &Point3d, axis: usize) -> f64 {
+    match axis {
+        0 => p.x(),
+        1 => p.y(),
+        _ => p.z(),
+    }
+}
+
+fn build<T>(nodes: &mut [(Point3d, T)], depth: usize) {
+    if nodes.len() <= 1 {
+        return;
+    }
+
+    let axis = depth % 3;
+    let mid = nodes.len() / 2;
+    nodes.select_nth_unstable_by(mid, |(a, _), (b, _)| {
+        coordinate(a, axis).total_cmp(&coordinate(b, axis))
+    });
+
+    let (before, after) = nodes.split_at_mut(mid);
+    build(before, depth + 1);
+    build(&mut after[1..], depth + 1);
+}
+
+fn visit<T>(
+    nodes: &[(Point3d, T)],
+    depth: usize,
+    center: &Point3d,
+    radius_squared: f64,
+    f: &mut impl FnMut(&Point3d, &T),
+) {
+    if nodes.is_empty() {
+        return;
+    }
+
+    let mid = nodes.len() / 2;
+    let (point, value) = &nodes[mid];
+    let offset = center - point;
+    if offset.dot(&offset) <= radius_squared {
+        f(point, value);
+    }
+
+    let axis = depth % 3;
+    let split = coordinate(center, axis) - coordinate(point, axis);
+    let (near, far) = if split < 0.0 {
+        (&nodes[..mid], &nodes[mid + 1..])
+    } else {
+        (&nodes[mid + 1..], &nodes[..mid])
+    };
+    visit(near, depth + 1, center, radius_squared, f);
+    // The far side can only hold points in range if the sphere reaches across the split
+    if split * split <= radius_squared {
+        visit(far, depth + 1, center, radius_squared, f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    use super::*;
+
+    fn random_points(n: usize) -> Vec<(Point3d, usize)> {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+        (0..n)
+            .map(|i| {
+                let p = Point3d::new(
+                    rng.gen_range(-5.0..5.0),
+                    rng.gen_range(-5.0..5.0),
+                    rng.gen_range(-5.0..5.0),
+                );
+                (p, i)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn an_empty_tree_finds_nothing() {
+        let tree: KdTree<()> = KdTree::new(Vec::new());
+        let mut found = 0;
+
+        tree.within(&Point3d::new(0.0, 0.0, 0.0), 10.0, |_, _| found += 1);
+
+        assert!(tree.is_empty());
+        assert_eq!(found, 0);
+    }
+
+    #[test]
+    fn finds_exactly_the_points_within_the_radius() {
+        let points = random_points(500);
+        let tree = KdTree::new(points.clone());
+        let center = Point3d::new(1.0, -0.5, 2.0);
+
+        let mut found = Vec::new();
+        tree.within(&center, 2.0, |_, &i| found.push(i));
+        found.sort();
+
+        let expected: Vec<usize> = points
+            .iter()
+            .filter(|(p, _)| (&center - p).mag() <= 2.0)
+            .map(|&(_, i)| i)
+            .collect();
+        assert_eq!(tree.len(), 500);
+        assert!(!expected.is_empty());
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn points_on_the_edge_of_the_radius_are_included() {
+        let tree = KdTree::new(vec![
+            (Point3d::new(1.0, 0.0, 0.0), 'a'),
+            (Point3d::new(0.0, 2.0, 0.0), 'b'),
+        ]);
+        let mut found = Vec::new();
+
+        tree.within(&Point3d::new(0.0, 0.0, 0.0), 1.0, |_, &c| found.push(c));
+
+        assert_eq!(found, vec!['a']);
+    }
+}
@@ -101,7 +101,7 @@ fn determinant(data: &[&[f64]]) -> f64 {
 
 fn cofactor(data: &[&[f64]], n: usize, m: usize) -> f64 {
     let minor = minor(data, n, m);
-    if (n + m) % 2 == 0 {
+    if (n + m).is_multiple_of(2) {
         minor
     } else {
         -minor
@@ -603,7 +603,7 @@ mod tests {
                 ]);
 
                 assert_eq!(a.determinant(), -2120.0);
-                assert_eq!(a.is_invertible(), true);
+                assert!(a.is_invertible());
             }
 
             #[test]
@@ -616,7 +616,7 @@ mod tests {
                 ]);
 
                 assert_eq!(a.determinant(), 0.0);
-                assert_eq!(a.is_invertible(), false);
+                assert!(!a.is_invertible());
             }
 
             #[test]
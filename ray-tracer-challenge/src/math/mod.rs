@@ -1,5 +1,8 @@
+pub mod kd_tree;
 pub mod matrix;
 pub mod point;
+pub mod quaternion;
+pub mod sampling;
 pub mod vector;
 
 pub mod util;
@@ -0,0 +1,174 @@
+use super::matrix::{Matrix, SquareMatrix};
+
+/// A rotation, in a form that can be blended smoothly with another
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quaternion {
+    w: f64,
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+impl Quaternion {
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Quaternion { w, x, y, z }
+    }
+
+    pub fn identity() -> Self {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    /// The rotation done by the upper-left 3x3 part of `m`, which must be a pure rotation
+    pub fn from_rotation(m: &SquareMatrix<4>) -> Self {
+        let trace = m.at(0, 0) + m.at(1, 1) + m.at(2, 2);
+
+        // Divide by whichever component is largest, to keep the others accurate
+        if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion::new(
+                s / 4.0,
+                (m.at(2, 1) - m.at(1, 2)) / s,
+                (m.at(0, 2) - m.at(2, 0)) / s,
+                (m.at(1, 0) - m.at(0, 1)) / s,
+            )
+        } else if m.at(0, 0) > m.at(1, 1) && m.at(0, 0) > m.at(2, 2) {
+            let s = (1.0 + m.at(0, 0) - m.at(1, 1) - m.at(2, 2)).sqrt() * 2.0;
+            Quaternion::new(
+                (m.at(2, 1) - m.at(1, 2)) / s,
+                s / 4.0,
+                (m.at(0, 1) + m.at(1, 0)) / s,
+                (m.at(0, 2) + m.at(2, 0)) / s,
+            )
+        } else if m.at(1, 1) > m.at(2, 2) {
+            let s = (1.0 + m.at(1, 1) - m.at(0, 0) - m.at(2, 2)).sqrt() * 2.0;
+            Quaternion::new(
+                (m.at(0, 2) - m.at(2, 0)) / s,
+                (m.at(0, 1) + m.at(1, 0)) / s,
+                s / 4.0,
+                (m.at(1, 2) + m.at(2, 1)) / s,
+            )
+        } else {
+            let s = (1.0 + m.at(2, 2) - m.at(0, 0) - m.at(1, 1)).sqrt() * 2.0;
+            Quaternion::new(
+                (m.at(1, 0) - m.at(0, 1)) / s,
+                (m.at(0, 2) + m.at(2, 0)) / s,
+                (m.at(1, 2) + m.at(2, 1)) / s,
+                s / 4.0,
+            )
+        }
+    }
+
+    pub fn dot(&self, other: &Self) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    /// The rotation `t` of the way from this one to `other`, turning at a steady rate the shorter
+    /// way around
+    pub fn slerp(&self, other: &Self, t: f64) -> Self {
+        // A quaternion and its negation are the same rotation, but only one is the short way round
+        let (other, cos) = match self.dot(other) {
+            d if d < 0.0 => (Quaternion::new(-other.w, -other.x, -other.y, -other.z), -d),
+            d => (other.clone(), d),
+        };
+
+        let (a, b) = if cos > 0.9995 {
+            // Nearly the same rotation, where blending straight across is just as good
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+
+        Quaternion::new(
+            a * self.w + b * other.w,
+            a * self.x + b * other.x,
+            a * self.y + b * other.y,
+            a * self.z + b * other.z,
+        )
+        .normalize()
+    }
+
+    fn normalize(&self) -> Self {
+        let mag = self.dot(self).sqrt();
+        Quaternion::new(self.w / mag, self.x / mag, self.y / mag, self.z / mag)
+    }
+
+    /// The rotation as a transformation matrix
+    pub fn matrix(&self) -> SquareMatrix<4> {
+        let Quaternion { w, x, y, z } = self;
+        Matrix::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts;
+
+    use crate::{math::matrix::test_utils::assert_matrix_approx_equals, scene::transformation};
+
+    use super::*;
+
+    #[test]
+    fn the_identity_quaternion_does_not_rotate() {
+        assert_eq!(Quaternion::identity().matrix(), SquareMatrix::identity());
+    }
+
+    #[test]
+    fn converting_rotations_to_quaternions_and_back() {
+        let rotations = [
+            transformation::rotation_x(consts::FRAC_PI_3),
+            transformation::rotation_y(-consts::FRAC_PI_2),
+            transformation::rotation_z(consts::PI),
+            transformation::sequence(&[
+                transformation::rotation_x(2.0),
+                transformation::rotation_y(1.0),
+                transformation::rotation_z(-2.5),
+            ]),
+        ];
+
+        for r in rotations {
+            assert_matrix_approx_equals(&Quaternion::from_rotation(&r).matrix(), &r);
+        }
+    }
+
+    #[test]
+    fn slerp_turns_at_a_steady_rate() {
+        let a = Quaternion::identity();
+        let b = Quaternion::from_rotation(&transformation::rotation_y(consts::FRAC_PI_2));
+
+        assert_matrix_approx_equals(
+            &a.slerp(&b, 0.5).matrix(),
+            &transformation::rotation_y(consts::FRAC_PI_4),
+        );
+    }
+
+    #[test]
+    fn slerp_takes_the_shorter_way_around() {
+        let a = Quaternion::from_rotation(&transformation::rotation_z(0.1));
+        let b = Quaternion::from_rotation(&transformation::rotation_z(-0.1));
+        let flipped = Quaternion::new(-b.w, -b.x, -b.y, -b.z);
+
+        assert_matrix_approx_equals(&a.slerp(&flipped, 0.5).matrix(), &SquareMatrix::identity());
+    }
+}
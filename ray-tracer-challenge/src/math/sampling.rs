@@ -0,0 +1,136 @@
+use std::{
+    f64::consts,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use super::{point::Point3d, vector::Vec3d};
+
+/// A random number generator seeded from a point, so that renders are reproducible no matter
+/// which order (or which thread) points are shaded in
+pub fn rng_for_point(point: &Point3d) -> Xoshiro256PlusPlus {
+    let mut hasher = DefaultHasher::new();
+    [point.x(), point.y(), point.z()]
+        .iter()
+        .for_each(|c| c.to_bits().hash(&mut hasher));
+    Xoshiro256PlusPlus::seed_from_u64(hasher.finish())
+}
+
+/// Two unit vectors which, together with `n`, form an orthonormal basis
+pub fn orthonormal_basis(n: &Vec3d) -> (Vec3d, Vec3d) {
+    let helper = if n.x().abs() > 0.9 {
+        Vec3d::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3d::new(1.0, 0.0, 0.0)
+    };
+    let tangent = helper.cross(n).norm().unwrap();
+    let bitangent = n.cross(&tangent);
+    (tangent, bitangent)
+}
+
+/// A random direction in the hemisphere around the unit vector `n`, more likely the closer it is
+/// to `n` (with probability proportional to the cosine of the angle between them)
+pub fn cosine_weighted_hemisphere<R: Rng + ?Sized>(rng: &mut R, n: &Vec3d) -> Vec3d {
+    let (tangent, bitangent) = orthonormal_basis(n);
+    let r1: f64 = rng.gen();
+    let r2: f64 = rng.gen();
+
+    let phi = 2.0 * consts::PI * r1;
+    let r = r2.sqrt();
+    let (x, y, z) = (r * phi.cos(), r * phi.sin(), (1.0 - r2).sqrt());
+
+    &(&(&tangent * x) + &(&bitangent * y)) + &(n * z)
+}
+
+/// A random direction within `half_angle` radians of the unit vector `axis`, with every direction
+/// in the cone equally likely
+pub fn in_cone<R: Rng + ?Sized>(rng: &mut R, axis: &Vec3d, half_angle: f64) -> Vec3d {
+    let (tangent, bitangent) = orthonormal_basis(axis);
+    let r1: f64 = rng.gen();
+    let r2: f64 = rng.gen();
+
+    let cos_theta = 1.0 - r1 * (1.0 - half_angle.cos());
+    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+    let phi = 2.0 * consts::PI * r2;
+
+    &(&(&tangent * (sin_theta * phi.cos())) + &(&bitangent * (sin_theta * phi.sin())))
+        + &(axis * cos_theta)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::util::test_utils::are_within_tolerance;
+
+    use super::*;
+
+    #[test]
+    fn rngs_for_the_same_point_agree() {
+        let p = Point3d::new(1.0, 2.0, 3.0);
+
+        let a: u64 = rng_for_point(&p).gen();
+        let b: u64 = rng_for_point(&p).gen();
+        let c: u64 = rng_for_point(&Point3d::new(1.0, 2.0, 3.5)).gen();
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn orthonormal_basis_is_orthonormal() {
+        for n in [
+            Vec3d::new(0.0, 1.0, 0.0),
+            Vec3d::new(1.0, 0.0, 0.0),
+            Vec3d::new(1.0, 2.0, -3.0).norm().unwrap(),
+        ] {
+            let (t, b) = orthonormal_basis(&n);
+
+            assert!(are_within_tolerance(t.mag(), 1.0, 1e-9));
+            assert!(are_within_tolerance(b.mag(), 1.0, 1e-9));
+            assert!(are_within_tolerance(t.dot(&n), 0.0, 1e-9));
+            assert!(are_within_tolerance(b.dot(&n), 0.0, 1e-9));
+            assert!(are_within_tolerance(t.dot(&b), 0.0, 1e-9));
+        }
+    }
+
+    #[test]
+    fn cosine_weighted_directions_stay_in_the_hemisphere() {
+        let n = Vec3d::new(0.0, 0.0, -1.0);
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+
+        let mean_cos = (0..1000)
+            .map(|_| {
+                let d = cosine_weighted_hemisphere(&mut rng, &n);
+                assert!(are_within_tolerance(d.mag(), 1.0, 1e-9));
+                assert!(d.dot(&n) >= 0.0);
+                d.dot(&n)
+            })
+            .sum::<f64>()
+            / 1000.0;
+
+        // The expected cosine under a cosine-weighted distribution is 2/3
+        assert!(are_within_tolerance(mean_cos, 2.0 / 3.0, 0.02));
+    }
+
+    #[test]
+    fn cone_directions_stay_within_the_cone() {
+        let axis = Vec3d::new(1.0, 2.0, -3.0).norm().unwrap();
+        let half_angle = 0.3;
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+
+        for _ in 0..1000 {
+            let d = in_cone(&mut rng, &axis, half_angle);
+            assert!(are_within_tolerance(d.mag(), 1.0, 1e-9));
+            assert!(d.dot(&axis) >= half_angle.cos() - 1e-9);
+        }
+    }
+
+    #[test]
+    fn a_cone_with_no_angle_is_just_its_axis() {
+        let axis = Vec3d::new(0.0, 1.0, 0.0);
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+
+        assert_eq!(in_cone(&mut rng, &axis, 0.0), axis);
+    }
+}
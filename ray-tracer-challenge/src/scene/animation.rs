@@ -0,0 +1,463 @@
+use crate::{
+    draw::{canvas::Canvas, color::Color},
+    math::{matrix::SquareMatrix, point::Point3d, vector::Vec3d},
+};
+
+use super::{
+    camera::RenderOpts,
+    light::{Falloff, PointLight},
+    transformation, Scene,
+};
+
+/// Values that can be blended between keyframes
+pub trait Interpolate {
+    /// The value `t` of the way from `self` to `other`
+    fn interpolate(&self, other: &Self, t: f64) -> Self;
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for Vec3d {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        self + &(&(other - self) * t)
+    }
+}
+
+impl Interpolate for Point3d {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        self + &(&(other - self) * t)
+    }
+}
+
+impl Interpolate for Color {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        &(self * (1.0 - t)) + &(other * t)
+    }
+}
+
+/// How a value moves between one keyframe and the next
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Easing {
+    /// At a steady pace
+    #[default]
+    Linear,
+    /// Along a cubic Bezier timing curve from (0, 0) to (1, 1) with control points (x1, y1) and
+    /// (x2, y2), like CSS's `cubic-bezier`
+    Bezier { x1: f64, y1: f64, x2: f64, y2: f64 },
+}
+
+impl Easing {
+    /// Starts and stops gently
+    pub fn ease_in_out() -> Self {
+        Easing::Bezier {
+            x1: 0.42,
+            y1: 0.0,
+            x2: 0.58,
+            y2: 1.0,
+        }
+    }
+
+    /// How far along the value is once `t` of the time between the keyframes has passed
+    fn progress(&self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::Bezier { x1, y1, x2, y2 } => {
+                let bezier = |a: f64, b: f64, s: f64| {
+                    3.0 * (1.0 - s) * (1.0 - s) * s * a + 3.0 * (1.0 - s) * s * s * b + s * s * s
+                };
+                // Keeping the control points' x within the keyframes makes x grow steadily with
+                // the curve parameter, so it can be found by bisection
+                let (x1, x2) = (x1.clamp(0.0, 1.0), x2.clamp(0.0, 1.0));
+                let (mut low, mut high) = (0.0, 1.0);
+                for _ in 0..50 {
+                    let mid = (low + high) / 2.0;
+                    if bezier(x1, x2, mid) < t {
+                        low = mid;
+                    } else {
+                        high = mid;
+                    }
+                }
+                bezier(*y1, *y2, (low + high) / 2.0)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframe<T> {
+    pub time: f64,
+    pub value: T,
+    /// How the value moves from this keyframe to the next one
+    pub easing: Easing,
+}
+
+/// A value that changes over time, passing through each of its keyframes in turn
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track<T> {
+    keyframes: Vec<Keyframe<T>>,
+}
+
+impl<T: Interpolate + Clone> Track<T> {
+    /// Fails unless there's at least one keyframe and their times are in increasing order
+    pub fn new(keyframes: Vec<Keyframe<T>>) -> Result<Self, String> {
+        if keyframes.is_empty() {
+            return Err(String::from("A track needs at least one keyframe"));
+        }
+        if keyframes.windows(2).any(|w| w[0].time >= w[1].time) {
+            return Err(String::from(
+                "A track's keyframes must be in order of increasing time",
+            ));
+        }
+
+        Ok(Track { keyframes })
+    }
+
+    /// A value that never changes
+    pub fn constant(value: T) -> Self {
+        Track {
+            keyframes: vec![Keyframe {
+                time: 0.0,
+                value,
+                easing: Easing::Linear,
+            }],
+        }
+    }
+
+    /// The value at `time`, holding the first and last keyframes' values before and after them
+    pub fn at(&self, time: f64) -> T {
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return self.keyframes[0].value.clone();
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].value.clone();
+        }
+
+        let (from, to) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let t = (time - from.time) / (to.time - from.time);
+        from.value.interpolate(&to.value, from.easing.progress(t))
+    }
+}
+
+/// A transform built from a scaling, then rotations about x, y and z, then a translation, each of
+/// which can change over time
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimatedTransform {
+    pub scale: Track<Vec3d>,
+    /// Angles in radians about the x, y and z axes, applied in that order
+    pub rotation: Track<Vec3d>,
+    pub translation: Track<Vec3d>,
+}
+
+impl AnimatedTransform {
+    pub fn at(&self, time: f64) -> SquareMatrix<4> {
+        let s = self.scale.at(time);
+        let r = self.rotation.at(time);
+        let t = self.translation.at(time);
+        transformation::sequence(&[
+            transformation::scaling(s.x(), s.y(), s.z()),
+            transformation::rotation_x(r.x()),
+            transformation::rotation_y(r.y()),
+            transformation::rotation_z(r.z()),
+            transformation::translation(t.x(), t.y(), t.z()),
+        ])
+    }
+}
+
+impl Default for AnimatedTransform {
+    fn default() -> Self {
+        Self {
+            scale: Track::constant(Vec3d::new(1.0, 1.0, 1.0)),
+            rotation: Track::constant(Vec3d::new(0.0, 0.0, 0.0)),
+            translation: Track::constant(Vec3d::new(0.0, 0.0, 0.0)),
+        }
+    }
+}
+
+/// The parameters of a camera's `view_transform`, changing over time
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimatedView {
+    pub from: Track<Point3d>,
+    pub to: Track<Point3d>,
+    pub up: Track<Vec3d>,
+}
+
+impl AnimatedView {
+    pub fn at(&self, time: f64) -> SquareMatrix<4> {
+        transformation::view_transform(&self.from.at(time), &self.to.at(time), &self.up.at(time))
+    }
+}
+
+/// A point light that can move and change brightness over time
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimatedLight {
+    pub position: Track<Point3d>,
+    pub intensity: Track<Color>,
+    pub falloff: Falloff,
+}
+
+impl AnimatedLight {
+    pub fn at(&self, time: f64) -> PointLight {
+        PointLight {
+            position: self.position.at(time),
+            intensity: self.intensity.at(time),
+            falloff: self.falloff.clone(),
+        }
+    }
+}
+
+/// The times to render an animation's frames at: `rate` frames to each unit of time, from `start`
+/// up to but not including `end`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frames {
+    pub start: f64,
+    pub end: f64,
+    pub rate: f64,
+}
+
+impl Frames {
+    pub fn times(&self) -> impl Iterator<Item = f64> {
+        let count = ((self.end - self.start) * self.rate).ceil().max(0.0) as usize;
+        let (start, rate) = (self.start, self.rate);
+        (0..count).map(move |n| start + n as f64 / rate)
+    }
+
+    /// Renders the scene as `scene_at` builds it for each frame's time, handing the frames to
+    /// `write` along with their numbers, counting up from 0
+    pub fn render(
+        &self,
+        opts: &RenderOpts,
+        scene_at: impl Fn(f64) -> Scene,
+        mut write: impl FnMut(usize, &Canvas),
+    ) {
+        for (n, time) in self.times().enumerate() {
+            write(n, &scene_at(time).render(opts));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts;
+
+    use crate::{
+        draw::color,
+        math::matrix::{test_utils::assert_matrix_approx_equals, InvertibleMatrix},
+        scene::{
+            camera::Camera,
+            material::{Material, Surface},
+            object::sphere::Sphere,
+            world::World,
+        },
+    };
+
+    use super::*;
+
+    fn key<T>(time: f64, value: T) -> Keyframe<T> {
+        Keyframe {
+            time,
+            value,
+            easing: Easing::Linear,
+        }
+    }
+
+    mod track {
+        use super::*;
+
+        #[test]
+        fn a_track_needs_keyframes() {
+            assert!(Track::<f64>::new(Vec::new()).is_err());
+        }
+
+        #[test]
+        fn a_tracks_keyframes_must_be_in_order() {
+            assert!(Track::new(vec![key(1.0, 0.0), key(0.0, 1.0)]).is_err());
+            assert!(Track::new(vec![key(1.0, 0.0), key(1.0, 1.0)]).is_err());
+        }
+
+        #[test]
+        fn a_constant_track_has_the_same_value_at_any_time() {
+            let t = Track::constant(3.0);
+
+            assert_eq!(t.at(-10.0), 3.0);
+            assert_eq!(t.at(10.0), 3.0);
+        }
+
+        #[test]
+        fn values_hold_before_the_first_keyframe_and_after_the_last() {
+            let t = Track::new(vec![key(1.0, 2.0), key(2.0, 4.0)]).unwrap();
+
+            assert_eq!(t.at(0.0), 2.0);
+            assert_eq!(t.at(5.0), 4.0);
+        }
+
+        #[test]
+        fn linear_interpolation_between_keyframes() {
+            let t = Track::new(vec![key(0.0, 0.0), key(1.0, 2.0), key(3.0, 0.0)]).unwrap();
+
+            assert_eq!(t.at(0.25), 0.5);
+            assert_eq!(t.at(1.0), 2.0);
+            assert_eq!(t.at(2.0), 1.0);
+        }
+
+        #[test]
+        fn interpolating_points_and_colors() {
+            let points = Track::new(vec![
+                key(0.0, Point3d::new(0.0, 0.0, 0.0)),
+                key(1.0, Point3d::new(2.0, 4.0, -2.0)),
+            ])
+            .unwrap();
+            let colors =
+                Track::new(vec![key(0.0, color::black()), key(1.0, color::white())]).unwrap();
+
+            assert_eq!(points.at(0.5), Point3d::new(1.0, 2.0, -1.0));
+            assert_eq!(colors.at(0.5), Color::new(0.5, 0.5, 0.5));
+        }
+
+        #[test]
+        fn easing_in_and_out_lags_behind_then_catches_up() {
+            let t = Track::new(vec![
+                Keyframe {
+                    time: 0.0,
+                    value: 0.0,
+                    easing: Easing::ease_in_out(),
+                },
+                key(1.0, 1.0),
+            ])
+            .unwrap();
+
+            assert!(t.at(0.25) < 0.25);
+            assert!((t.at(0.5) - 0.5).abs() < 1e-9);
+            assert!(t.at(0.75) > 0.75);
+        }
+
+        #[test]
+        fn a_bezier_curve_with_linear_control_points_is_linear() {
+            let easing = Easing::Bezier {
+                x1: 1.0 / 3.0,
+                y1: 1.0 / 3.0,
+                x2: 2.0 / 3.0,
+                y2: 2.0 / 3.0,
+            };
+
+            assert!((easing.progress(0.3) - 0.3).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn an_animated_transform_at_a_moment_in_time() {
+        let transform = AnimatedTransform {
+            rotation: Track::new(vec![
+                key(0.0, Vec3d::new(0.0, 0.0, 0.0)),
+                key(2.0, Vec3d::new(0.0, consts::PI, 0.0)),
+            ])
+            .unwrap(),
+            translation: Track::constant(Vec3d::new(0.0, 1.0, 0.0)),
+            ..Default::default()
+        };
+
+        assert_matrix_approx_equals(
+            &transform.at(1.0),
+            &transformation::sequence(&[
+                transformation::rotation_y(consts::FRAC_PI_2),
+                transformation::translation(0.0, 1.0, 0.0),
+            ]),
+        );
+    }
+
+    #[test]
+    fn an_animated_view_moves_the_camera() {
+        let view = AnimatedView {
+            from: Track::new(vec![
+                key(0.0, Point3d::new(0.0, 0.0, -5.0)),
+                key(1.0, Point3d::new(0.0, 0.0, -15.0)),
+            ])
+            .unwrap(),
+            to: Track::constant(Point3d::new(0.0, 0.0, 0.0)),
+            up: Track::constant(Vec3d::new(0.0, 1.0, 0.0)),
+        };
+
+        assert_eq!(
+            view.at(0.5),
+            transformation::view_transform(
+                &Point3d::new(0.0, 0.0, -10.0),
+                &Point3d::new(0.0, 0.0, 0.0),
+                &Vec3d::new(0.0, 1.0, 0.0)
+            )
+        );
+    }
+
+    #[test]
+    fn an_animated_light_at_a_moment_in_time() {
+        let light = AnimatedLight {
+            position: Track::new(vec![
+                key(0.0, Point3d::new(0.0, 10.0, 0.0)),
+                key(1.0, Point3d::new(10.0, 10.0, 0.0)),
+            ])
+            .unwrap(),
+            intensity: Track::constant(color::white()),
+            falloff: Falloff::None,
+        };
+
+        assert_eq!(light.at(0.5).position, Point3d::new(5.0, 10.0, 0.0));
+    }
+
+    #[test]
+    fn frame_times_run_up_to_the_end() {
+        let frames = Frames {
+            start: 1.0,
+            end: 2.0,
+            rate: 4.0,
+        };
+
+        assert_eq!(frames.times().collect::<Vec<_>>(), [1.0, 1.25, 1.5, 1.75]);
+    }
+
+    #[test]
+    fn rendering_each_frame_of_an_animation() {
+        // A sphere in front of the camera that brightens from black to white
+        let brightness = Track::new(vec![key(0.0, 0.0), key(1.0, 1.0)]).unwrap();
+        let scene_at = |time: f64| {
+            let sphere = Sphere::new(Material {
+                surface: Surface::Color(color::black()),
+                emissive: &color::white() * brightness.at(time),
+                ..Default::default()
+            });
+            let world = World {
+                objects: vec![Box::new(sphere)],
+                ..Default::default()
+            };
+            let camera = Camera::new(
+                1,
+                1,
+                consts::FRAC_PI_2,
+                InvertibleMatrix::try_from(transformation::view_transform(
+                    &Point3d::new(0.0, 0.0, -5.0),
+                    &Point3d::new(0.0, 0.0, 0.0),
+                    &Vec3d::new(0.0, 1.0, 0.0),
+                ))
+                .unwrap(),
+            );
+            Scene { camera, world }
+        };
+        let frames = Frames {
+            start: 0.0,
+            end: 1.0,
+            rate: 2.0,
+        };
+
+        let mut rendered = Vec::new();
+        frames.render(&Default::default(), scene_at, |n, canvas| {
+            rendered.push((n, canvas.at(0, 0).unwrap().clone()))
+        });
+
+        assert_eq!(
+            rendered,
+            [(0, color::black()), (1, Color::new(0.5, 0.5, 0.5))]
+        );
+    }
+}
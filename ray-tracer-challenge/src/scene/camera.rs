@@ -1,10 +1,18 @@
 use crate::{
-    draw::canvas::Canvas,
-    math::{matrix::InvertibleMatrix, point::Point3d},
+    draw::{
+        canvas::Canvas,
+        color::{self, Color},
+    },
+    math::{matrix::InvertibleMatrix, point::Point3d, sampling},
 };
 
-use super::{ray::Ray, world::World};
+use super::{
+    intersect::Intersections,
+    ray::Ray,
+    world::{HitInfo, Sample, World},
+};
 
+use rand::{seq::SliceRandom, Rng};
 use rayon::prelude::*;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -13,20 +21,120 @@ pub struct Camera {
     pub vsize: usize,
     pub fov: f64,
     pub transform: InvertibleMatrix<4>,
+    pub shutter: Shutter,
     pixel_size: f64,
     half_width: f64,
     half_height: f64,
 }
 
+/// When the camera's shutter is open. Objects that move while it is open are blurred along their
+/// path.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Shutter {
+    pub open: f64,
+    pub close: f64,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct RenderOpts {
     pub anti_aliasing_samples: usize,
+    /// Only render this rectangle of the camera's image, or the whole image if `None`
+    pub region: Option<Region>,
+    /// Render one pixel for every `downscale` x `downscale` block of the image, for quick previews
+    pub downscale: usize,
+    /// How many rays to average for blurry reflections and refractions off rough materials
+    pub glossy_samples: usize,
+    pub integrator: Integrator,
+}
+
+/// How the world works out the color seen along each camera ray
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Integrator {
+    /// Direct lighting plus perfect reflection and refraction, as in the book
+    #[default]
+    Whitted,
+    /// Monte Carlo path tracing, which also picks up light bounced between diffuse surfaces
+    PathTracing(PathTracing),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathTracing {
+    /// How many paths to trace and average for each camera ray
+    pub samples_per_pixel: usize,
+    /// How many bounces every path makes before Russian roulette may end it early
+    pub min_bounces: usize,
+    pub max_bounces: usize,
+}
+
+impl Default for PathTracing {
+    fn default() -> Self {
+        Self {
+            samples_per_pixel: 16,
+            min_bounces: 3,
+            max_bounces: 16,
+        }
+    }
 }
 
 impl Default for RenderOpts {
     fn default() -> Self {
         Self {
             anti_aliasing_samples: 1,
+            region: None,
+            downscale: 1,
+            glossy_samples: 1,
+            integrator: Integrator::Whitted,
+        }
+    }
+}
+
+/// Auxiliary output buffers produced alongside a rendered image
+pub struct Aovs {
+    /// Distance along the camera ray to the first hit, or infinity where nothing was hit
+    pub depth: Canvas,
+    /// World-space normal at the first hit, with x, y and z stored as r, g and b
+    pub normal: Canvas,
+    /// A color unique to each top-level object in the world
+    pub object_id: Canvas,
+    /// Surface color at the first hit, before lighting
+    pub albedo: Canvas,
+    pub direct: Canvas,
+    pub reflected: Canvas,
+    pub refracted: Canvas,
+}
+
+type Pixels<T> = Vec<((usize, usize), T)>;
+
+struct AovPixel {
+    color: Color,
+    depth: f64,
+    normal: Color,
+    object_id: Color,
+    albedo: Color,
+    direct: Color,
+    reflected: Color,
+    refracted: Color,
+}
+
+/// A rectangle of pixels on the camera's image, with its origin at the top-left corner
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    /// Clip the region so that it lies within an image of the given size
+    fn clip(&self, hsize: usize, vsize: usize) -> Region {
+        let x = self.x.min(hsize);
+        let y = self.y.min(vsize);
+        Region {
+            x,
+            y,
+            width: self.width.min(hsize - x),
+            height: self.height.min(vsize - y),
         }
     }
 }
@@ -50,6 +158,7 @@ impl Camera {
             vsize,
             fov,
             transform,
+            shutter: Default::default(),
             pixel_size,
             half_width,
             half_height,
@@ -60,20 +169,34 @@ impl Camera {
         Camera::new(hsize, vsize, fov, InvertibleMatrix::identity())
     }
 
+    #[cfg(test)]
     fn rays_for_pixel(&self, px: usize, py: usize, samples: usize) -> impl Iterator<Item = Ray> {
+        self.rays_for_block(px, py, (1, 1), samples)
+    }
+
+    /// Rays through a block of `size` pixels across and down whose top-left pixel is (px, py)
+    fn rays_for_block(
+        &self,
+        px: usize,
+        py: usize,
+        size: (usize, usize),
+        samples: usize,
+    ) -> impl Iterator<Item = Ray> {
         let pixel_size = self.pixel_size;
         let half_width = self.half_width;
         let half_height = self.half_height;
         let inverse = self.transform.inverse().clone();
+        let times = self.shutter_times(px, py, samples * samples);
 
         let mut rays = Vec::with_capacity(samples);
 
         for nx in 0..samples {
             for ny in 0..samples {
                 // offset from the edge of the canvas to the pixel's center
-                let sample_offset = 1.0 / samples as f64;
-                let xoffset = (px as f64 + sample_offset * (nx as f64 + 0.5)) * pixel_size;
-                let yoffset = (py as f64 + sample_offset * (ny as f64 + 0.5)) * pixel_size;
+                let x_step = size.0 as f64 / samples as f64;
+                let y_step = size.1 as f64 / samples as f64;
+                let xoffset = (px as f64 + x_step * (nx as f64 + 0.5)) * pixel_size;
+                let yoffset = (py as f64 + y_step * (ny as f64 + 0.5)) * pixel_size;
 
                 // untransformed coordinates of the pixel in world space
                 let world_x = half_width - xoffset;
@@ -83,45 +206,178 @@ impl Camera {
                 let origin = &inverse * &Point3d::new(0.0, 0.0, 0.0);
                 let direction = (&pixel - &origin).norm().unwrap();
 
-                rays.push(Ray { origin, direction })
+                rays.push(Ray {
+                    origin,
+                    direction,
+                    time: times[nx * samples + ny],
+                })
             }
         }
 
         rays.into_iter()
     }
 
-    pub fn render(&self, world: &World, opts: &RenderOpts) -> Canvas {
-        let mut image = Canvas::new(self.hsize, self.vsize);
+    /// A time for each of `count` rays through the block at (px, py), spread evenly over the
+    /// shutter interval but in a random order, so that they don't line up with the rays' positions
+    fn shutter_times(&self, px: usize, py: usize, count: usize) -> Vec<f64> {
+        let Shutter { open, close } = self.shutter;
+        if open == close {
+            return vec![open; count];
+        }
 
-        let indices = (0..self.hsize)
-            .into_par_iter()
-            .flat_map_iter(|x| (0..self.vsize).map(move |y| (x, y)));
-
-        let colors = indices
-            .map(|(x, y)| {
-                (
-                    (x, y),
-                    self.rays_for_pixel(x, y, opts.anti_aliasing_samples),
-                )
-            })
-            .map(|(p, rays)| {
-                (
-                    p,
-                    &(rays
-                        .map(|r| world.color_at(&r))
-                        .reduce(|acc, c| &acc + &c)
-                        .unwrap())
-                        * (1.0 / (opts.anti_aliasing_samples.pow(2)) as f64),
-                )
-            })
-            .collect::<Vec<_>>();
+        let mut rng = sampling::rng_for_point(&Point3d::new(px as f64, py as f64, 0.0));
+        let mut times: Vec<f64> = (0..count)
+            .map(|k| open + (close - open) * (k as f64 + rng.gen::<f64>()) / count as f64)
+            .collect();
+        times.shuffle(&mut rng);
+        times
+    }
+
+    pub fn render(&self, world: &World, opts: &RenderOpts) -> Canvas {
+        let samples = opts.anti_aliasing_samples;
+        let (width, height, colors) = self.render_pixels(opts, |xs, rays| {
+            &(rays
+                .map(|r| world.color_at_into(&r, opts, xs))
+                .reduce(|acc, c| &acc + &c)
+                .unwrap())
+                * (1.0 / (samples.pow(2)) as f64)
+        });
 
+        let mut image = Canvas::new(width, height);
         colors.into_iter().for_each(|(p, c)| {
             image.write(p, c);
         });
 
         image
     }
+
+    /// Render the image along with its auxiliary output buffers. Color buffers are averaged over
+    /// all anti-aliasing samples, while depth, normal and object ID come from the nearest hit.
+    pub fn render_aovs(&self, world: &World, opts: &RenderOpts) -> (Canvas, Aovs) {
+        let weight = 1.0 / (opts.anti_aliasing_samples.pow(2)) as f64;
+        let (width, height, pixels) = self.render_pixels(opts, |_, rays| {
+            let samples = rays
+                .map(|r| world.sample_with(&r, opts))
+                .collect::<Vec<_>>();
+            let average = |f: &dyn Fn(&Sample) -> Color| {
+                &samples.iter().map(f).reduce(|acc, c| &acc + &c).unwrap() * weight
+            };
+            let on_hit = |f: fn(&HitInfo) -> Color| {
+                move |s: &Sample| s.hit.as_ref().map_or(color::black(), f)
+            };
+            let nearest = samples
+                .iter()
+                .filter_map(|s| s.hit.as_ref())
+                .min_by(|a, b| a.distance.total_cmp(&b.distance));
+
+            AovPixel {
+                color: average(&|s| s.color.clone()),
+                depth: nearest.map_or(f64::INFINITY, |h| h.distance),
+                normal: nearest.map_or(color::black(), |h| {
+                    Color::new(h.normal.x(), h.normal.y(), h.normal.z())
+                }),
+                object_id: nearest.map_or(color::black(), |h| id_color(h.object_id)),
+                albedo: average(&on_hit(|h| h.albedo.clone())),
+                direct: average(&on_hit(|h| h.contributions.direct.clone())),
+                reflected: average(&on_hit(|h| h.contributions.reflected.clone())),
+                refracted: average(&on_hit(|h| h.contributions.refracted.clone())),
+            }
+        });
+
+        let mut image = Canvas::new(width, height);
+        let mut aovs = Aovs {
+            depth: Canvas::new(width, height),
+            normal: Canvas::new(width, height),
+            object_id: Canvas::new(width, height),
+            albedo: Canvas::new(width, height),
+            direct: Canvas::new(width, height),
+            reflected: Canvas::new(width, height),
+            refracted: Canvas::new(width, height),
+        };
+        pixels.into_iter().for_each(|(p, px)| {
+            image.write(p, px.color);
+            aovs.depth
+                .write(p, Color::new(px.depth, px.depth, px.depth));
+            aovs.normal.write(p, px.normal);
+            aovs.object_id.write(p, px.object_id);
+            aovs.albedo.write(p, px.albedo);
+            aovs.direct.write(p, px.direct);
+            aovs.reflected.write(p, px.reflected);
+            aovs.refracted.write(p, px.refracted);
+        });
+
+        (image, aovs)
+    }
+
+    /// Compute a value for every pixel of the region selected by `opts` from the rays through it,
+    /// returning the dimensions of the resulting image along with each pixel's value. Each worker
+    /// thread gets its own intersection buffer, which `f` can reuse from pixel to pixel.
+    fn render_pixels<'w, T, F>(&self, opts: &RenderOpts, f: F) -> (usize, usize, Pixels<T>)
+    where
+        T: Send,
+        F: Fn(&mut Intersections<'w>, Box<dyn Iterator<Item = Ray>>) -> T + Sync + Send,
+    {
+        let region = self.region(opts);
+        let scale = opts.downscale.max(1);
+        let width = region.width.div_ceil(scale);
+        let height = region.height.div_ceil(scale);
+
+        let indices = (0..width)
+            .into_par_iter()
+            .flat_map_iter(|x| (0..height).map(move |y| (x, y)));
+
+        let values = indices
+            .map_init(Intersections::new, |xs, (x, y)| {
+                // The last blocks across and down are cut short by the edge of the region
+                let block = (
+                    scale.min(region.width - x * scale),
+                    scale.min(region.height - y * scale),
+                );
+                let rays = self.rays_for_block(
+                    region.x + x * scale,
+                    region.y + y * scale,
+                    block,
+                    opts.anti_aliasing_samples,
+                );
+                ((x, y), f(xs, Box::new(rays)))
+            })
+            .collect::<Vec<_>>();
+
+        (width, height, values)
+    }
+
+    /// Render the region selected by `opts` and composite it into `canvas`, which should be the
+    /// full size of the camera's image. Downscaled pixels are written to every pixel of their block
+    /// that lies within the region.
+    pub fn render_into(&self, world: &World, opts: &RenderOpts, canvas: &mut Canvas) {
+        let region = self.region(opts);
+        let rendered = self.render(world, opts);
+        canvas.composite(
+            &rendered,
+            (region.x, region.y),
+            opts.downscale.max(1),
+            (region.width, region.height),
+        );
+    }
+
+    fn region(&self, opts: &RenderOpts) -> Region {
+        opts.region
+            .as_ref()
+            .unwrap_or(&Region {
+                x: 0,
+                y: 0,
+                width: self.hsize,
+                height: self.vsize,
+            })
+            .clip(self.hsize, self.vsize)
+    }
+}
+
+/// Pick an arbitrary but stable color to represent an object ID
+fn id_color(id: usize) -> Color {
+    let hash = (id as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    let channel = |shift: u64| ((hash >> shift) & 0xff) as f64 / 255.0;
+    Color::new(channel(40), channel(48), channel(56))
 }
 
 #[cfg(test)]
@@ -131,7 +387,7 @@ mod tests {
     use crate::{
         draw::color::{self, Color},
         math::{util, vector::Vec3d},
-        scene::{transformation, world::World},
+        scene::{environment::Environment, transformation, world::World},
     };
 
     use super::*;
@@ -203,7 +459,7 @@ mod tests {
 
         #[test]
         fn constructing_ray_when_camera_is_transformed() {
-            let transform = transformation::sequence(&vec![
+            let transform = transformation::sequence(&[
                 transformation::translation(0.0, -2.0, 5.0),
                 transformation::rotation_y(consts::FRAC_PI_4),
             ]);
@@ -271,4 +527,258 @@ mod tests {
             &Color::new(0.38066, 0.47583, 0.2855),
         );
     }
+
+    mod motion_blur {
+        use crate::scene::{
+            material::Material,
+            object::{moving::Moving, sphere::Sphere},
+        };
+
+        use super::*;
+
+        #[test]
+        fn rays_are_cast_at_the_moment_the_shutter_opens_by_default() {
+            let c = Camera::default(1, 1, consts::FRAC_PI_2);
+
+            assert!(c.rays_for_pixel(0, 0, 3).all(|r| r.time == 0.0));
+        }
+
+        #[test]
+        fn rays_are_spread_over_the_time_the_shutter_is_open() {
+            let mut c = Camera::default(1, 1, consts::FRAC_PI_2);
+            c.shutter = Shutter {
+                open: 1.0,
+                close: 2.0,
+            };
+
+            let mut times: Vec<f64> = c.rays_for_pixel(0, 0, 3).map(|r| r.time).collect();
+            times.sort_by(f64::total_cmp);
+
+            // One ray in each ninth of the interval
+            for (k, t) in times.iter().enumerate() {
+                assert!(*t >= 1.0 + k as f64 / 9.0 && *t < 1.0 + (k + 1) as f64 / 9.0);
+            }
+        }
+
+        #[test]
+        fn an_object_moving_while_the_shutter_is_open_is_blurred() {
+            // A glowing ball that slides out of view of the pixel halfway through the exposure
+            let ball = Moving::new(
+                Sphere::new(Material {
+                    diffuse: 0.0,
+                    emissive: color::white(),
+                    ..Default::default()
+                }),
+                &InvertibleMatrix::identity(),
+                &InvertibleMatrix::try_from(transformation::translation(4.0, 0.0, 0.0)).unwrap(),
+            )
+            .unwrap();
+            let w = World {
+                objects: vec![Box::new(ball)],
+                ..Default::default()
+            };
+            let from = Point3d::new(0.0, 0.0, -5.0);
+            let to = Point3d::new(0.0, 0.0, 0.0);
+            let up = Vec3d::new(0.0, 1.0, 0.0);
+            let transform =
+                InvertibleMatrix::try_from(transformation::view_transform(&from, &to, &up))
+                    .unwrap();
+            let mut c = Camera::new(1, 1, 0.01, transform);
+            let opts = RenderOpts {
+                anti_aliasing_samples: 4,
+                ..Default::default()
+            };
+
+            let still = c.render(&w, &opts);
+            c.shutter = Shutter {
+                open: 0.0,
+                close: 1.0,
+            };
+            let blurred = c.render(&w, &opts);
+
+            assert_eq!(still.at(0, 0).unwrap(), &color::white());
+            assert!(util::are_equal(blurred.at(0, 0).unwrap().r(), 0.25));
+        }
+    }
+
+    mod aovs {
+        use super::*;
+
+        #[test]
+        fn rendering_auxiliary_buffers() {
+            let mut w = World::basic();
+            w.environment = Environment::constant(color::blue());
+            let from = Point3d::new(0.0, 0.0, -5.0);
+            let to = Point3d::new(0.0, 0.0, 0.0);
+            let up = Vec3d::new(0.0, 1.0, 0.0);
+            let transform =
+                InvertibleMatrix::try_from(transformation::view_transform(&from, &to, &up))
+                    .unwrap();
+            let c = Camera::new(11, 11, consts::FRAC_PI_2, transform);
+
+            let (image, aovs) = c.render_aovs(&w, &Default::default());
+
+            assert_eq!(image.at(5, 5), c.render(&w, &Default::default()).at(5, 5));
+            assert_eq!(aovs.depth.at(5, 5), Some(&Color::new(4.0, 4.0, 4.0)));
+            assert_eq!(aovs.normal.at(5, 5), Some(&Color::new(0.0, 0.0, -1.0)));
+            assert_eq!(aovs.object_id.at(5, 5), Some(&id_color(0)));
+            assert_eq!(aovs.albedo.at(5, 5), Some(&Color::new(0.8, 1.0, 0.6)));
+            assert_eq!(aovs.direct.at(5, 5), image.at(5, 5));
+            assert_eq!(aovs.reflected.at(5, 5), Some(&color::black()));
+
+            assert_eq!(image.at(0, 0), Some(&color::blue()));
+            let infinite = f64::INFINITY;
+            assert_eq!(
+                aovs.depth.at(0, 0),
+                Some(&Color::new(infinite, infinite, infinite))
+            );
+            assert_eq!(aovs.object_id.at(0, 0), Some(&color::black()));
+        }
+
+        #[test]
+        fn object_id_colors_are_distinct() {
+            assert_ne!(id_color(0), id_color(1));
+            assert_ne!(id_color(1), id_color(2));
+        }
+    }
+
+    mod region {
+        use super::*;
+
+        fn basic_camera() -> Camera {
+            let from = Point3d::new(0.0, 0.0, -5.0);
+            let to = Point3d::new(0.0, 0.0, 0.0);
+            let up = Vec3d::new(0.0, 1.0, 0.0);
+            let transform =
+                InvertibleMatrix::try_from(transformation::view_transform(&from, &to, &up))
+                    .unwrap();
+            Camera::new(11, 11, consts::FRAC_PI_2, transform)
+        }
+
+        #[test]
+        fn rendering_a_region_of_the_image() {
+            let w = World::basic();
+            let c = basic_camera();
+            let opts = RenderOpts {
+                region: Some(Region {
+                    x: 4,
+                    y: 3,
+                    width: 3,
+                    height: 4,
+                }),
+                ..Default::default()
+            };
+
+            let full = c.render(&w, &Default::default());
+            let image = c.render(&w, &opts);
+
+            assert_eq!(image.width(), 3);
+            assert_eq!(image.height(), 4);
+            assert_eq!(image.at(1, 2), full.at(5, 5));
+        }
+
+        #[test]
+        fn a_region_is_clipped_to_the_image() {
+            let c = basic_camera();
+            let opts = RenderOpts {
+                region: Some(Region {
+                    x: 8,
+                    y: 10,
+                    width: 5,
+                    height: 5,
+                }),
+                ..Default::default()
+            };
+
+            let image = c.render(&World::basic(), &opts);
+
+            assert_eq!(image.width(), 3);
+            assert_eq!(image.height(), 1);
+        }
+
+        #[test]
+        fn a_downscaled_render_samples_the_center_of_each_block() {
+            let w = World::basic();
+            let c = basic_camera();
+            let opts = RenderOpts {
+                region: Some(Region {
+                    x: 4,
+                    y: 4,
+                    width: 3,
+                    height: 3,
+                }),
+                downscale: 3,
+                ..Default::default()
+            };
+
+            let full = c.render(&w, &Default::default());
+            let image = c.render(&w, &opts);
+
+            assert_eq!(image.width(), 1);
+            assert_eq!(image.height(), 1);
+            color::test_utils::assert_colors_approx_equal(
+                image.at(0, 0).unwrap(),
+                full.at(5, 5).unwrap(),
+            );
+        }
+
+        #[test]
+        fn downscaled_blocks_stop_at_the_edge_of_an_odd_sized_region() {
+            let w = World::basic();
+            let c = basic_camera();
+            let opts = RenderOpts {
+                region: Some(Region {
+                    x: 4,
+                    y: 4,
+                    width: 3,
+                    height: 3,
+                }),
+                downscale: 2,
+                ..Default::default()
+            };
+            let mut canvas = Canvas::new_with_color(11, 11, &color::red());
+
+            let full = c.render(&w, &Default::default());
+            let image = c.render(&w, &opts);
+            c.render_into(&w, &opts, &mut canvas);
+
+            assert_eq!(image.width(), 2);
+            assert_eq!(image.height(), 2);
+            // The last block is a single pixel, so it is sampled at that pixel's center
+            color::test_utils::assert_colors_approx_equal(
+                image.at(1, 1).unwrap(),
+                full.at(6, 6).unwrap(),
+            );
+            assert_eq!(canvas.at(6, 6), image.at(1, 1));
+            assert_eq!(canvas.at(7, 6), Some(&color::red()));
+            assert_eq!(canvas.at(6, 7), Some(&color::red()));
+            assert_eq!(canvas.at(7, 7), Some(&color::red()));
+        }
+
+        #[test]
+        fn rendering_a_region_into_an_existing_canvas() {
+            let w = World::basic();
+            let c = basic_camera();
+            let opts = RenderOpts {
+                region: Some(Region {
+                    x: 4,
+                    y: 4,
+                    width: 4,
+                    height: 4,
+                }),
+                downscale: 2,
+                ..Default::default()
+            };
+            let mut canvas = Canvas::new_with_color(11, 11, &color::red());
+
+            c.render_into(&w, &opts, &mut canvas);
+            let rendered = c.render(&w, &opts);
+
+            assert_eq!(canvas.at(3, 3), Some(&color::red()));
+            assert_eq!(canvas.at(4, 4), rendered.at(0, 0));
+            assert_eq!(canvas.at(5, 5), rendered.at(0, 0));
+            assert_eq!(canvas.at(7, 6), rendered.at(1, 1));
+            assert_eq!(canvas.at(8, 8), Some(&color::red()));
+        }
+    }
 }
@@ -0,0 +1,156 @@
+use std::f64::consts;
+
+use crate::{
+    draw::{
+        canvas::Canvas,
+        color::{self, Color},
+    },
+    math::{matrix::InvertibleMatrix, vector::Vec3d},
+};
+
+/// What a ray sees when it escapes the scene without hitting anything
+pub enum Sky {
+    Constant(Color),
+    /// Blends from `bottom` for rays pointing straight down to `top` for rays pointing straight up
+    Gradient {
+        bottom: Color,
+        top: Color,
+    },
+    /// An equirectangular (latitude/longitude) image covering every direction
+    Image(Canvas),
+}
+
+pub struct Environment {
+    pub sky: Sky,
+    /// Orients the sky in the world, e.g. to rotate the sun in an image about the y-axis
+    pub transform: InvertibleMatrix<4>,
+}
+
+impl Environment {
+    pub fn new(sky: Sky) -> Self {
+        Environment {
+            sky,
+            transform: InvertibleMatrix::identity(),
+        }
+    }
+
+    pub fn constant(color: Color) -> Self {
+        Environment::new(Sky::Constant(color))
+    }
+
+    /// The color seen looking out along `direction`, which need not be normalized
+    pub fn color_at(&self, direction: &Vec3d) -> Color {
+        let local_direction = || (self.transform.inverse() * direction).norm();
+
+        match &self.sky {
+            Sky::Constant(c) => c.clone(),
+            Sky::Gradient { bottom, top } => local_direction().map_or(color::black(), |d| {
+                let t = 0.5 * (d.y() + 1.0);
+                &(bottom * (1.0 - t)) + &(top * t)
+            }),
+            Sky::Image(image) => {
+                local_direction().map_or(color::black(), |d| equirectangular_lookup(image, &d))
+            }
+        }
+    }
+}
+
+fn equirectangular_lookup(image: &Canvas, d: &Vec3d) -> Color {
+    // u runs once around the horizon starting from -z, v runs from straight up to straight down
+    let u = 0.5 + f64::atan2(d.x(), d.z()) / (2.0 * consts::PI);
+    let v = f64::acos(d.y().clamp(-1.0, 1.0)) / consts::PI;
+
+    let x = ((u * image.width() as f64) as usize).min(image.width().saturating_sub(1));
+    let y = ((v * image.height() as f64) as usize).min(image.height().saturating_sub(1));
+
+    image.at(x, y).cloned().unwrap_or(color::black())
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::constant(color::black())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{draw::color, scene::transformation};
+
+    use super::*;
+
+    #[test]
+    fn a_constant_environment_is_the_same_in_every_direction() {
+        let env = Environment::constant(color::blue());
+
+        assert_eq!(env.color_at(&Vec3d::new(0.0, 1.0, 0.0)), color::blue());
+        assert_eq!(env.color_at(&Vec3d::new(1.0, -1.0, 3.0)), color::blue());
+    }
+
+    #[test]
+    fn a_gradient_environment_blends_vertically() {
+        let env = Environment::new(Sky::Gradient {
+            bottom: color::black(),
+            top: color::white(),
+        });
+
+        assert_eq!(env.color_at(&Vec3d::new(0.0, -2.0, 0.0)), color::black());
+        assert_eq!(
+            env.color_at(&Vec3d::new(0.0, 0.0, 1.0)),
+            Color::new(0.5, 0.5, 0.5)
+        );
+        assert_eq!(env.color_at(&Vec3d::new(0.0, 3.0, 0.0)), color::white());
+    }
+
+    fn quadrant_image() -> Canvas {
+        // Four columns, one per direction around the horizon, over two rows for up and down
+        let mut image = Canvas::new(4, 2);
+        let columns = [color::red(), color::green(), color::blue(), color::white()];
+        for (x, c) in columns.iter().enumerate() {
+            image.write((x, 0), c.clone());
+            image.write((x, 1), c * 0.5);
+        }
+        image
+    }
+
+    #[test]
+    fn an_image_environment_maps_directions_to_pixels() {
+        let env = Environment::new(Sky::Image(quadrant_image()));
+
+        assert_eq!(env.color_at(&Vec3d::new(-1.0, 0.1, -0.1)), color::red());
+        assert_eq!(env.color_at(&Vec3d::new(-1.0, 0.1, 0.1)), color::green());
+        assert_eq!(env.color_at(&Vec3d::new(1.0, 0.1, 0.1)), color::blue());
+        assert_eq!(env.color_at(&Vec3d::new(1.0, 0.1, -0.1)), color::white());
+        assert_eq!(
+            env.color_at(&Vec3d::new(1.0, -0.1, -0.1)),
+            Color::new(0.5, 0.5, 0.5)
+        );
+    }
+
+    #[test]
+    fn the_environment_can_be_rotated() {
+        let env = Environment {
+            sky: Sky::Image(quadrant_image()),
+            transform: InvertibleMatrix::try_from(transformation::rotation_y(consts::PI)).unwrap(),
+        };
+
+        assert_eq!(env.color_at(&Vec3d::new(1.0, 0.1, 0.1)), color::red());
+        assert_eq!(env.color_at(&Vec3d::new(-1.0, 0.1, -0.1)), color::blue());
+    }
+
+    #[test]
+    fn rotating_a_gradient_tilts_it() {
+        let env = Environment {
+            sky: Sky::Gradient {
+                bottom: color::black(),
+                top: color::white(),
+            },
+            transform: InvertibleMatrix::try_from(transformation::rotation_z(consts::FRAC_PI_2))
+                .unwrap(),
+        };
+
+        color::test_utils::assert_colors_approx_equal(
+            &env.color_at(&Vec3d::new(-1.0, 0.0, 0.0)),
+            &color::white(),
+        );
+    }
+}
@@ -0,0 +1,443 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::math::{matrix::InvertibleMatrix, point::Point3d, vector::NormalizedVec3d};
+
+use super::{
+    material::Material,
+    object::{group::Group, instance::Instance, transformed::Transformed, Object},
+};
+
+/// A handle to a node in the [SceneGraph] it was added to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+struct Node {
+    name: String,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    transform: InvertibleMatrix<4>,
+    material: Option<Material>,
+    shape: Option<Box<dyn Object>>,
+}
+
+/// Named objects arranged in a tree, each placed relative to its parent, which can be looked up
+/// and edited until the graph is built into a world's objects
+#[derive(Default)]
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+    names: HashMap<String, NodeId>,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a node with no shape of its own, to arrange the nodes added under it
+    pub fn add_group(
+        &mut self,
+        name: &str,
+        parent: Option<NodeId>,
+        transform: InvertibleMatrix<4>,
+    ) -> Result<NodeId, String> {
+        self.add(name, parent, transform, None)
+    }
+
+    /// Adds a node holding a shape, which can't have nodes added under it
+    pub fn add_shape(
+        &mut self,
+        name: &str,
+        parent: Option<NodeId>,
+        transform: InvertibleMatrix<4>,
+        shape: impl Object + 'static,
+    ) -> Result<NodeId, String> {
+        self.add(name, parent, transform, Some(Box::new(shape)))
+    }
+
+    fn add(
+        &mut self,
+        name: &str,
+        parent: Option<NodeId>,
+        transform: InvertibleMatrix<4>,
+        shape: Option<Box<dyn Object>>,
+    ) -> Result<NodeId, String> {
+        if self.names.contains_key(name) {
+            return Err(format!("There is already a node named '{}'.", name));
+        }
+        if let Some(parent) = parent {
+            match self.nodes.get(parent.0) {
+                None => return Err(format!("The parent of '{}' is not in this graph.", name)),
+                Some(p) if p.shape.is_some() => {
+                    return Err(format!(
+                        "'{}' is a shape, so it can't hold '{}'.",
+                        p.name, name
+                    ))
+                }
+                _ => (),
+            }
+        }
+
+        let id = NodeId(self.nodes.len());
+        if let Some(parent) = parent {
+            self.nodes[parent.0].children.push(id);
+        }
+        self.nodes.push(Node {
+            name: name.to_string(),
+            parent,
+            children: Vec::new(),
+            transform,
+            material: None,
+            shape,
+        });
+        self.names.insert(name.to_string(), id);
+        Ok(id)
+    }
+
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.names.get(name).copied()
+    }
+
+    pub fn name(&self, id: NodeId) -> &str {
+        &self.nodes[id.0].name
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.nodes[id.0].children
+    }
+
+    /// The node's transformation relative to its parent
+    pub fn transform(&self, id: NodeId) -> &InvertibleMatrix<4> {
+        &self.nodes[id.0].transform
+    }
+
+    /// The node's transformation relative to the world, taking in those of all its ancestors
+    pub fn world_transform(&self, id: NodeId) -> InvertibleMatrix<4> {
+        let node = &self.nodes[id.0];
+        match node.parent {
+            Some(parent) => &self.world_transform(parent) * &node.transform,
+            None => node.transform.clone(),
+        }
+    }
+
+    /// A point given in world space, in the space of the node
+    pub fn world_to_object(&self, id: NodeId, point: &Point3d) -> Point3d {
+        self.world_transform(id).inverse() * point
+    }
+
+    /// A point given in the space of the node, in world space
+    pub fn object_to_world(&self, id: NodeId, point: &Point3d) -> Point3d {
+        &*self.world_transform(id) * point
+    }
+
+    /// A normal on the node's shape, in world space
+    pub fn normal_to_world(&self, id: NodeId, normal: &NormalizedVec3d) -> NormalizedVec3d {
+        let inverse_transpose = self.world_transform(id).inverse().transpose();
+        NormalizedVec3d::try_from(&inverse_transpose * &**normal)
+            .expect("an invertible transformation should not flatten a normal")
+    }
+
+    pub fn material(&self, id: NodeId) -> Option<&Material> {
+        self.nodes[id.0].material.as_ref()
+    }
+
+    pub fn set_transform(
+        &mut self,
+        name: &str,
+        transform: InvertibleMatrix<4>,
+    ) -> Result<(), String> {
+        self.node_mut(name)?.transform = transform;
+        Ok(())
+    }
+
+    /// Gives the node a material, which replaces that of a shape node's shape, or is inherited
+    /// by any shapes below a group node that have no material of their own
+    pub fn set_material(&mut self, name: &str, material: Material) -> Result<(), String> {
+        self.node_mut(name)?.material = Some(material);
+        Ok(())
+    }
+
+    fn node_mut(&mut self, name: &str) -> Result<&mut Node, String> {
+        let id = self
+            .find(name)
+            .ok_or_else(|| format!("There is no node named '{}'.", name))?;
+        Ok(&mut self.nodes[id.0])
+    }
+
+    /// The objects to render for each node without a parent, with their descendants nested inside
+    pub fn build(self) -> Vec<Box<dyn Object>> {
+        let mut built: Vec<Option<Box<dyn Object>>> = self.nodes.iter().map(|_| None).collect();
+        let mut roots = Vec::new();
+
+        // Nodes can only be added under ones that already exist, so every node's children are
+        // built by the time it is reached going backwards
+        for (i, node) in self.nodes.into_iter().enumerate().rev() {
+            let contents: Box<dyn Object> = match (node.shape, node.material) {
+                (Some(shape), Some(material)) => Box::new(Instance::<dyn Object>::with_material(
+                    Arc::from(shape),
+                    material,
+                )),
+                (Some(shape), None) => shape,
+                (None, material) => {
+                    let children = node
+                        .children
+                        .iter()
+                        .map(|c| built[c.0].take().expect("should have built the child"))
+                        .collect();
+                    let mut group: Group<Box<dyn Object>> = Group::new(children);
+                    group.material = material;
+                    Box::new(group)
+                }
+            };
+
+            let object = Box::new(Transformed::new(contents, node.transform));
+            match node.parent {
+                Some(_) => built[i] = Some(object),
+                None => roots.push(object as Box<dyn Object>),
+            }
+        }
+
+        roots.reverse();
+        roots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        math::{
+            matrix::test_utils::assert_matrix_approx_equals,
+            vector::{self, Vec3d},
+        },
+        scene::{object::sphere::Sphere, ray::Ray, transformation},
+    };
+
+    use super::*;
+
+    fn translation(x: f64, y: f64, z: f64) -> InvertibleMatrix<4> {
+        InvertibleMatrix::try_from(transformation::translation(x, y, z)).unwrap()
+    }
+
+    fn with_ambient(ambient: f64) -> Material {
+        Material {
+            ambient,
+            ..Default::default()
+        }
+    }
+
+    fn table() -> SceneGraph {
+        let mut graph = SceneGraph::new();
+        let table = graph
+            .add_group("table", None, translation(0.0, 1.0, 0.0))
+            .unwrap();
+        graph
+            .add_shape(
+                "ball",
+                Some(table),
+                translation(5.0, 0.0, 0.0),
+                Sphere::unit(),
+            )
+            .unwrap();
+        graph
+    }
+
+    fn first_hit_x(objects: &[Box<dyn Object>], y: f64) -> Option<f64> {
+        let r = Ray::new(Point3d::new(-10.0, y, 0.0), Vec3d::new(1.0, 0.0, 0.0));
+        objects
+            .iter()
+            .flat_map(|o| o.intersect(&r).iter().map(|i| i.t()).collect::<Vec<_>>())
+            .reduce(f64::min)
+            .map(|t| t - 10.0)
+    }
+
+    #[test]
+    fn nodes_are_found_by_name() {
+        let graph = table();
+        let table = graph.find("table").unwrap();
+        let ball = graph.find("ball").unwrap();
+
+        assert_eq!(graph.name(ball), "ball");
+        assert_eq!(graph.parent(ball), Some(table));
+        assert_eq!(graph.children(table), &[ball]);
+        assert_eq!(graph.find("chair"), None);
+    }
+
+    #[test]
+    fn node_names_must_be_unique() {
+        let mut graph = table();
+
+        assert!(graph
+            .add_group("ball", None, InvertibleMatrix::identity())
+            .is_err());
+    }
+
+    #[test]
+    fn a_node_must_be_added_under_a_node_in_the_same_graph() {
+        let mut graph = SceneGraph::new();
+
+        assert!(graph
+            .add_group("orphan", Some(NodeId(3)), InvertibleMatrix::identity())
+            .is_err());
+    }
+
+    #[test]
+    fn the_world_transform_of_a_node_includes_its_ancestors() {
+        let mut graph = table();
+        let ball = graph.find("ball").unwrap();
+        graph
+            .set_transform(
+                "table",
+                InvertibleMatrix::try_from(transformation::sequence(&[
+                    transformation::scaling(2.0, 2.0, 2.0),
+                    transformation::translation(0.0, 1.0, 0.0),
+                ]))
+                .unwrap(),
+            )
+            .unwrap();
+
+        assert_matrix_approx_equals(
+            &graph.world_transform(ball),
+            &transformation::sequence(&[
+                transformation::translation(5.0, 0.0, 0.0),
+                transformation::scaling(2.0, 2.0, 2.0),
+                transformation::translation(0.0, 1.0, 0.0),
+            ]),
+        );
+    }
+
+    #[test]
+    fn points_and_normals_convert_through_the_parent_chain() {
+        let mut graph = SceneGraph::new();
+        let g1 = graph
+            .add_group(
+                "g1",
+                None,
+                InvertibleMatrix::try_from(transformation::rotation_y(std::f64::consts::FRAC_PI_2))
+                    .unwrap(),
+            )
+            .unwrap();
+        let g2 = graph
+            .add_group(
+                "g2",
+                Some(g1),
+                InvertibleMatrix::try_from(transformation::scaling(1.0, 2.0, 3.0)).unwrap(),
+            )
+            .unwrap();
+        let s = graph
+            .add_shape("s", Some(g2), translation(5.0, 0.0, 0.0), Sphere::unit())
+            .unwrap();
+        let t = 3f64.sqrt() / 3.0;
+
+        let origin = Point3d::new(0.0, 0.0, 0.0);
+        let center = graph.object_to_world(s, &origin);
+        vector::test_utils::assert_vec_approx_equals(
+            &(&center - &origin),
+            &Vec3d::new(0.0, 0.0, -5.0),
+        );
+        vector::test_utils::assert_vec_approx_equals(
+            &(&graph.world_to_object(s, &center) - &origin),
+            &Vec3d::new(0.0, 0.0, 0.0),
+        );
+        vector::test_utils::assert_vec_approx_equals(
+            &graph.normal_to_world(s, &NormalizedVec3d::new(t, t, t).unwrap()),
+            &Vec3d::new(0.28571, 0.42857, -0.85714),
+        );
+    }
+
+    #[test]
+    fn editing_a_transform_by_name_moves_the_built_object() {
+        let mut graph = table();
+        graph
+            .set_transform("ball", translation(-2.0, 0.0, 0.0))
+            .unwrap();
+
+        assert_eq!(first_hit_x(&graph.build(), 1.0), Some(-3.0));
+    }
+
+    #[test]
+    fn editing_an_unknown_node_fails() {
+        let mut graph = table();
+
+        assert!(graph
+            .set_transform("chair", InvertibleMatrix::identity())
+            .is_err());
+        assert!(graph.set_material("chair", Material::default()).is_err());
+    }
+
+    #[test]
+    fn a_nodes_material_replaces_that_of_its_shape() {
+        let mut graph = SceneGraph::new();
+        graph
+            .add_shape(
+                "ball",
+                None,
+                InvertibleMatrix::identity(),
+                Sphere::new(with_ambient(0.2)),
+            )
+            .unwrap();
+        graph.set_material("ball", with_ambient(0.4)).unwrap();
+
+        let objects = graph.build();
+        let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+        let xs = objects[0].intersect(&r);
+
+        assert_eq!(xs.material(&xs[0]).ambient, 0.4);
+    }
+
+    #[test]
+    fn shapes_inherit_the_material_of_the_nearest_group_above_them_with_one() {
+        let mut graph = table();
+        let table = graph.find("table").unwrap();
+        let shelf = graph
+            .add_group("shelf", Some(table), translation(0.0, 3.0, 0.0))
+            .unwrap();
+        graph
+            .add_shape(
+                "marble",
+                Some(shelf),
+                InvertibleMatrix::identity(),
+                Sphere::unit(),
+            )
+            .unwrap();
+        graph.set_material("table", with_ambient(0.2)).unwrap();
+        graph.set_material("shelf", with_ambient(0.3)).unwrap();
+
+        let objects = graph.build();
+        let r = Ray::new(Point3d::new(0.0, 4.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+        let xs = objects[0].intersect(&r);
+
+        assert_eq!(xs.material(&xs[0]).ambient, 0.3);
+    }
+
+    #[test]
+    fn nothing_can_be_added_under_a_shape() {
+        let mut graph = table();
+        let ball = graph.find("ball").unwrap();
+
+        assert!(graph
+            .add_shape(
+                "marble",
+                Some(ball),
+                InvertibleMatrix::identity(),
+                Sphere::unit()
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn every_node_without_a_parent_becomes_an_object() {
+        let mut graph = table();
+        graph
+            .add_shape("moon", None, translation(0.0, 10.0, 0.0), Sphere::unit())
+            .unwrap();
+
+        let objects = graph.build();
+
+        assert_eq!(objects.len(), 2);
+        assert_eq!(first_hit_x(&objects, 1.0), Some(4.0));
+        assert_eq!(first_hit_x(&objects, 10.0), Some(-1.0));
+    }
+}
@@ -1,27 +1,46 @@
+use std::ops::{Deref, DerefMut};
+
 use crate::{
     draw::color::Color,
-    math::{point::Point3d, util, vector::NormalizedVec3d},
+    math::{
+        matrix::{InvertibleMatrix, SquareMatrix},
+        point::Point3d,
+        util,
+        vector::{NormalizedVec3d, Vec3d},
+    },
 };
 
-use super::{object::Object, ray::Ray};
+use super::{
+    material::{self, Material},
+    object::{Object, PhysicalObject},
+    ray::Ray,
+};
 
 const POINT_OFFSET_BIAS: f64 = 1e-5;
 
-#[derive(Debug, Clone)]
-pub struct Intersection<T, C, N> {
+/// Where a ray hit an object. Only enough is recorded to work out the rest on demand, since most
+/// intersections are never shaded.
+#[derive(Debug, Clone, Copy)]
+pub struct Intersection<T> {
     t: f64,
     object: T,
-    pub color: C,
-    pub normal: N,
+    /// Where on the object's surface the hit landed, for shapes whose normal depends on more than
+    /// the point (the barycentric coordinates of a triangle hit)
+    pub uv: (f64, f64),
+    frame: Option<usize>,
 }
 
-impl<T, C, N> Intersection<T, C, N> {
-    pub fn new(t: f64, object: T, color: C, normal: N) -> Self {
+impl<T> Intersection<T> {
+    pub fn new(t: f64, object: T) -> Self {
+        Intersection::with_uv(t, object, (0.0, 0.0))
+    }
+
+    pub fn with_uv(t: f64, object: T, uv: (f64, f64)) -> Self {
         Intersection {
             t,
             object,
-            color,
-            normal,
+            uv,
+            frame: None,
         }
     }
 
@@ -34,25 +53,403 @@ impl<T, C, N> Intersection<T, C, N> {
     }
 }
 
-impl<'a, T: Object + ?Sized> Intersection<&'a T, Color, NormalizedVec3d> {
+/// What an enclosing object imposes on the objects nested in it
+#[derive(Clone)]
+enum Frame<'a> {
+    /// The transformation from the space of the enclosing object into the nested one
+    Transform {
+        transform: &'a InvertibleMatrix<4>,
+        inverse_transpose: &'a SquareMatrix<4>,
+    },
+    /// A transformation worked out for just this ray, like that of a moving object at the ray's
+    /// time, along with its inverse transpose
+    OwnedTransform(Box<(InvertibleMatrix<4>, SquareMatrix<4>)>),
+    /// The material taken on by nested objects without one of their own
+    Material(&'a Material),
+    /// The material taken on by all nested objects, whatever their own
+    Override(&'a Material),
+    /// An object whose parts make up a single solid, like a CSG object, so that refraction
+    /// treats the ray as entering and leaving the whole rather than each part
+    Solid(&'a dyn Object),
+}
+
+impl Frame<'_> {
+    /// The frame's transformation and its inverse transpose, unless it only sets a material
+    fn transform(&self) -> Option<(&InvertibleMatrix<4>, &SquareMatrix<4>)> {
+        match self {
+            Frame::Transform {
+                transform,
+                inverse_transpose,
+            } => Some((transform, inverse_transpose)),
+            Frame::OwnedTransform(owned) => Some((&owned.0, &owned.1)),
+            Frame::Material(_) | Frame::Override(_) | Frame::Solid(_) => None,
+        }
+    }
+}
+
+/// A reusable buffer of intersections with objects borrowed for `'a`, along with the
+/// transformations and materials of the objects they are nested in
+#[derive(Default)]
+pub struct Intersections<'a> {
+    hits: Vec<Intersection<&'a dyn PhysicalObject>>,
+    frames: Vec<(Frame<'a>, Option<usize>)>,
+    current_frame: Option<usize>,
+    /// Whether any frame overrides materials, so that lookups can skip checking when none do
+    overridden: bool,
+}
+
+impl<'a> Intersections<'a> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Records an intersection found in the space of the current frame
+    pub fn push(&mut self, i: Intersection<&'a dyn PhysicalObject>) {
+        let i = self.in_current_frame(i);
+        self.hits.push(i);
+    }
+
+    /// Tags an intersection as found in the space of the current frame, without recording it
+    pub fn in_current_frame(
+        &self,
+        i: Intersection<&'a dyn PhysicalObject>,
+    ) -> Intersection<&'a dyn PhysicalObject> {
+        Intersection {
+            frame: self.current_frame,
+            ..i
+        }
+    }
+
+    /// Moves into the space of a nested object, returning the frame to go back to with
+    /// [Intersections::exit_frame]
+    pub fn enter_frame(
+        &mut self,
+        transform: &'a InvertibleMatrix<4>,
+        inverse_transpose: &'a SquareMatrix<4>,
+    ) -> Option<usize> {
+        self.push_frame(Frame::Transform {
+            transform,
+            inverse_transpose,
+        })
+    }
+
+    /// Like [Intersections::enter_frame], for a transformation that isn't borrowed from an object
+    pub fn enter_owned_frame(
+        &mut self,
+        transform: InvertibleMatrix<4>,
+        inverse_transpose: SquareMatrix<4>,
+    ) -> Option<usize> {
+        self.push_frame(Frame::OwnedTransform(Box::new((
+            transform,
+            inverse_transpose,
+        ))))
+    }
+
+    /// Moves into an object whose material is inherited by everything nested in it without a
+    /// material of its own, returning the frame to go back to with [Intersections::exit_frame]
+    pub fn enter_material(&mut self, material: &'a Material) -> Option<usize> {
+        self.push_frame(Frame::Material(material))
+    }
+
+    /// Moves into an object whose material replaces those of everything nested in it, returning
+    /// the frame to go back to with [Intersections::exit_frame]
+    pub fn enter_override(&mut self, material: &'a Material) -> Option<usize> {
+        self.overridden = true;
+        self.push_frame(Frame::Override(material))
+    }
+
+    /// Moves into an object made up of parts that together form a single solid, returning the
+    /// frame to go back to with [Intersections::exit_frame]
+    pub fn enter_solid(&mut self, object: &'a dyn Object) -> Option<usize> {
+        self.push_frame(Frame::Solid(object))
+    }
+
+    /// Runs `f` within [Intersections::enter_material] for the given material, if there is one
+    pub fn with_material<R>(
+        &mut self,
+        material: Option<&'a Material>,
+        f: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        match material {
+            Some(material) => {
+                let previous = self.enter_material(material);
+                let result = f(self);
+                self.exit_frame(previous);
+                result
+            }
+            None => f(self),
+        }
+    }
+
+    fn push_frame(&mut self, frame: Frame<'a>) -> Option<usize> {
+        let parent = self.current_frame;
+        self.frames.push((frame, parent));
+        self.current_frame = Some(self.frames.len() - 1);
+        parent
+    }
+
+    pub fn exit_frame(&mut self, previous: Option<usize>) {
+        self.current_frame = previous;
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.hits.truncate(len);
+    }
+
+    pub fn clear(&mut self) {
+        self.hits.clear();
+        self.frames.clear();
+        self.current_frame = None;
+        self.overridden = false;
+    }
+
+    /// The ray as seen from within a frame, given the ray these intersections were found along
+    fn local_ray(&self, frame: Option<usize>, ray: &Ray) -> Ray {
+        match frame.map(|index| &self.frames[index]) {
+            None => ray.clone(),
+            Some((frame, parent)) => match frame.transform() {
+                Some((transform, _)) => self.local_ray(*parent, ray).transform(transform.inverse()),
+                None => self.local_ray(*parent, ray),
+            },
+        }
+    }
+
+    /// A point in the space of the ray as seen from within a frame
+    fn local_point(&self, frame: Option<usize>, point: &Point3d) -> Point3d {
+        match frame.map(|index| &self.frames[index]) {
+            None => point.clone(),
+            Some((frame, parent)) => match frame.transform() {
+                Some((transform, _)) => transform.inverse() * &self.local_point(*parent, point),
+                None => self.local_point(*parent, point),
+            },
+        }
+    }
+
+    /// Takes a point from the space of a frame out to the space of the ray
+    fn point_to_outside(&self, frame: Option<usize>, point: Point3d) -> Point3d {
+        match frame.map(|index| &self.frames[index]) {
+            None => point,
+            Some((frame, parent)) => match frame.transform() {
+                Some((transform, _)) => self.point_to_outside(*parent, &**transform * &point),
+                None => self.point_to_outside(*parent, point),
+            },
+        }
+    }
+
+    /// Takes a normal from the space of the ray into the space of a frame
+    fn normal_to_inside(&self, frame: Option<usize>, normal: &Vec3d) -> Vec3d {
+        match frame.map(|index| &self.frames[index]) {
+            None => normal.clone(),
+            Some((frame, parent)) => match frame.transform() {
+                Some((transform, _)) => {
+                    &transform.transpose() * &self.normal_to_inside(*parent, normal)
+                }
+                None => self.normal_to_inside(*parent, normal),
+            },
+        }
+    }
+
+    /// A point given in the space of the ray, which is world space for intersections with a
+    /// world, as seen by the intersected object through all the objects it is nested in
+    pub fn world_to_object(
+        &self,
+        i: &Intersection<&'a dyn PhysicalObject>,
+        point: &Point3d,
+    ) -> Point3d {
+        self.local_point(i.frame, point)
+    }
+
+    /// A point given in the space of the intersected object, in the space of the ray
+    pub fn object_to_world(
+        &self,
+        i: &Intersection<&'a dyn PhysicalObject>,
+        point: &Point3d,
+    ) -> Point3d {
+        self.point_to_outside(i.frame, point.clone())
+    }
+
+    /// A normal on the intersected object, in the space of the ray
+    pub fn normal_to_world(
+        &self,
+        i: &Intersection<&'a dyn PhysicalObject>,
+        normal: NormalizedVec3d,
+    ) -> NormalizedVec3d {
+        self.normal_to_outside(i.frame, normal)
+    }
+
+    /// A normal given in the space of the ray, as seen by the intersected object
+    pub fn normal_to_object(
+        &self,
+        i: &Intersection<&'a dyn PhysicalObject>,
+        normal: &NormalizedVec3d,
+    ) -> NormalizedVec3d {
+        NormalizedVec3d::try_from(self.normal_to_inside(i.frame, normal))
+            .expect("an invertible transformation should not flatten a normal")
+    }
+
+    /// Whether the solid the intersected object is part of contains a point given in the space
+    /// of the ray, at the ray's time, if it encloses a region at all. That is the outermost
+    /// solid the object is nested in, if any, or else the object itself.
+    pub fn object_contains(
+        &self,
+        i: &Intersection<&'a dyn PhysicalObject>,
+        point: &Point3d,
+        time: f64,
+    ) -> Option<bool> {
+        match self.outermost_solid(i.frame) {
+            Some((solid, frame)) => solid.contains(&self.local_point(Some(frame), point), time),
+            None => i.object.contains(&self.local_point(i.frame, point), time),
+        }
+    }
+
+    /// Whether two intersections are with the same solid, in the sense of
+    /// [Intersections::object_contains]
+    pub fn same_solid(
+        &self,
+        a: &Intersection<&'a dyn PhysicalObject>,
+        b: &Intersection<&'a dyn PhysicalObject>,
+    ) -> bool {
+        match (self.outermost_solid(a.frame), self.outermost_solid(b.frame)) {
+            (Some((a, _)), Some((b, _))) => std::ptr::addr_eq(a, b),
+            (None, None) => std::ptr::addr_eq(a.object, b.object),
+            _ => false,
+        }
+    }
+
+    /// The outermost solid enclosing a frame, along with the frame entered for it
+    fn outermost_solid(&self, frame: Option<usize>) -> Option<(&'a dyn Object, usize)> {
+        let index = frame?;
+        let (frame, parent) = &self.frames[index];
+        self.outermost_solid(*parent).or(match frame {
+            Frame::Solid(solid) => Some((*solid, index)),
+            _ => None,
+        })
+    }
+
+    /// Takes a normal from the space of a frame out to the space of the ray
+    fn normal_to_outside(&self, frame: Option<usize>, normal: NormalizedVec3d) -> NormalizedVec3d {
+        match frame.map(|index| &self.frames[index]) {
+            None => normal,
+            Some((frame, parent)) => match frame.transform() {
+                Some((_, inverse_transpose)) => {
+                    let outer = inverse_transpose * &*normal;
+                    let outer = NormalizedVec3d::try_from(outer).unwrap();
+                    self.normal_to_outside(*parent, outer)
+                }
+                None => self.normal_to_outside(*parent, normal),
+            },
+        }
+    }
+
+    /// The material of the intersected object, or else the one it inherits from the objects it
+    /// is nested in
+    pub fn material(&self, i: &Intersection<&'a dyn PhysicalObject>) -> &'a Material {
+        if let Some(material) = self
+            .overridden
+            .then(|| self.override_material(i.frame))
+            .flatten()
+        {
+            return material;
+        }
+        i.object
+            .material()
+            .unwrap_or_else(|| self.inherited_material(i.frame))
+    }
+
+    /// The material of the outermost object overriding the materials nested in it, if any
+    fn override_material(&self, frame: Option<usize>) -> Option<&'a Material> {
+        match frame.map(|index| &self.frames[index]) {
+            None => None,
+            Some((Frame::Override(material), parent)) => {
+                self.override_material(*parent).or(Some(material))
+            }
+            Some((_, parent)) => self.override_material(*parent),
+        }
+    }
+
+    fn inherited_material(&self, frame: Option<usize>) -> &'a Material {
+        match frame.map(|index| &self.frames[index]) {
+            None => material::default_material(),
+            Some((Frame::Material(material), _)) => material,
+            Some((_, parent)) => self.inherited_material(*parent),
+        }
+    }
+
+    /// The surface normal and color at an intersection, given the ray it was found along
+    pub fn shading(
+        &self,
+        i: &Intersection<&'a dyn PhysicalObject>,
+        ray: &Ray,
+    ) -> (NormalizedVec3d, Color) {
+        let local_point = self.local_ray(i.frame, ray).position(i.t);
+        let local_normal = i.object.hit_normal_at(&local_point, i.uv);
+        let color = self
+            .material(i)
+            .surface
+            .color_at_hit(&local_point, &ray.position(i.t));
+        (self.normal_to_outside(i.frame, local_normal), color)
+    }
+
+    pub fn normal_at(
+        &self,
+        i: &Intersection<&'a dyn PhysicalObject>,
+        ray: &Ray,
+    ) -> NormalizedVec3d {
+        self.shading(i, ray).0
+    }
+
+    pub fn color_at(&self, i: &Intersection<&'a dyn PhysicalObject>, ray: &Ray) -> Color {
+        self.shading(i, ray).1
+    }
+}
+
+impl<'a> Deref for Intersections<'a> {
+    type Target = [Intersection<&'a dyn PhysicalObject>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.hits
+    }
+}
+
+impl DerefMut for Intersections<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.hits
+    }
+}
+
+impl<'a> Intersection<&'a dyn PhysicalObject> {
+    /// Works out everything needed to shade this intersection, which must be one found along the
+    /// ray in `xs`. The other intersections in `xs` determine the refractive indices on either
+    /// side.
     pub fn prepare_computations(
         &self,
         ray: &Ray,
-        xs: &[Intersection<&T, Color, NormalizedVec3d>],
-    ) -> Precomputation<&T> {
-        prepare_computations_helper(self, ray, self.normal.clone(), self.color.clone(), xs)
+        xs: &Intersections<'a>,
+    ) -> Precomputation<'a, &'a dyn PhysicalObject> {
+        prepare_computations_helper(
+            self,
+            ray,
+            xs.shading(self, ray),
+            xs,
+            |i| xs.material(i),
+            |i, point| xs.object_contains(i, point, ray.time),
+            |a, b| xs.same_solid(a, b),
+        )
     }
 }
 
-fn prepare_computations_helper<'a, T: Object + ?Sized, C1, N1, C2, N2>(
-    intersection: &'a Intersection<&T, C1, N1>,
+fn prepare_computations_helper<'a, T: Object + ?Sized>(
+    intersection: &Intersection<&'a T>,
     ray: &Ray,
-    normal: NormalizedVec3d,
-    color: Color,
-    xs: &[Intersection<&T, C2, N2>],
-) -> Precomputation<&'a T> {
+    (normal, color): (NormalizedVec3d, Color),
+    xs: &[Intersection<&'a T>],
+    material_of: impl Fn(&Intersection<&'a T>) -> &'a Material,
+    contains: impl Fn(&Intersection<&'a T>, &Point3d) -> Option<bool>,
+    same_container: impl Fn(&Intersection<&'a T>, &Intersection<&'a T>) -> bool,
+) -> Precomputation<'a, &'a T> {
     let t = intersection.t();
-    let object = intersection.object();
+    let object = *intersection.object();
+    let material = material_of(intersection);
     let point = ray.position(t);
     let eye_v = NormalizedVec3d::try_from(-&ray.direction).unwrap();
     let normal_v = normal;
@@ -69,29 +466,33 @@ fn prepare_computations_helper<'a, T: Object + ?Sized, C1, N1, C2, N2>(
 
     let reflect_v = NormalizedVec3d::try_from(ray.direction.reflect(&adjusted_normal_v)).unwrap();
 
-    let mut containers = Vec::<&T>::with_capacity(xs.len());
+    // Containers are tracked by the intersection where the ray entered them
+    let mut containers = Vec::<&Intersection<&'a T>>::with_capacity(xs.len());
     let mut n1: f64 = 1.0;
     let mut n2: f64 = 1.0;
     for i in xs {
         if i == intersection {
             n1 = match containers.last() {
-                Some(o) => o.material().refractive_index,
+                Some(c) => material_of(c).refractive_index,
                 None => 1.0,
             };
         }
 
-        if let Some(index) = containers
-            .iter()
-            .position(|&obj| std::ptr::eq(obj, *i.object()))
-        {
-            containers.remove(index);
-        } else {
-            containers.push(i.object());
+        // Objects that can tell say whether the ray goes into them here, which is robust to
+        // missing or repeated hits; for the rest, every other hit is an entry
+        let index = containers.iter().position(|c| same_container(c, i));
+        let past_hit = ray.position(i.t() + POINT_OFFSET_BIAS);
+        match (contains(i, &past_hit).unwrap_or(index.is_none()), index) {
+            (true, None) => containers.push(i),
+            (false, Some(index)) => {
+                containers.remove(index);
+            }
+            _ => (),
         }
 
         if i == intersection {
             n2 = match containers.last() {
-                Some(o) => o.material().refractive_index,
+                Some(c) => material_of(c).refractive_index,
                 None => 1.0,
             };
             break;
@@ -101,6 +502,7 @@ fn prepare_computations_helper<'a, T: Object + ?Sized, C1, N1, C2, N2>(
     Precomputation {
         t,
         object,
+        material,
         point,
         eye_v,
         normal_v: adjusted_normal_v,
@@ -111,19 +513,22 @@ fn prepare_computations_helper<'a, T: Object + ?Sized, C1, N1, C2, N2>(
         refraction_exiting: n1,
         refraction_entering: n2,
         object_color: color,
+        time: ray.time,
     }
 }
 
-impl<T: ?Sized, CL, NL, CR, NR> PartialEq<Intersection<&T, CR, NR>> for Intersection<&T, CL, NL> {
-    fn eq(&self, other: &Intersection<&T, CR, NR>) -> bool {
+impl<T: ?Sized> PartialEq for Intersection<&T> {
+    fn eq(&self, other: &Self) -> bool {
         util::are_equal(self.t, other.t) && std::ptr::eq(self.object, other.object)
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct Precomputation<T> {
+#[derive(Clone, PartialEq)]
+pub struct Precomputation<'a, T> {
     pub t: f64,
     pub object: T,
+    /// The material the object is shaded with, which may be inherited from a parent
+    pub material: &'a Material,
     pub point: Point3d,
     pub eye_v: NormalizedVec3d,
     pub normal_v: NormalizedVec3d,
@@ -134,9 +539,11 @@ pub struct Precomputation<T> {
     pub refraction_exiting: f64,
     pub refraction_entering: f64,
     pub object_color: Color,
+    /// The time of the ray that made the hit, for the rays cast onward from it
+    pub time: f64,
 }
 
-impl<T> Precomputation<T> {
+impl<T> Precomputation<'_, T> {
     pub fn schlick(&self) -> f64 {
         let cos = self.eye_v.dot(&self.normal_v);
         let n = self.refraction_exiting / self.refraction_entering;
@@ -156,7 +563,7 @@ impl<T> Precomputation<T> {
     }
 }
 
-pub fn hit<T, C, N>(intersections: &[Intersection<T, C, N>]) -> Option<&Intersection<T, C, N>> {
+pub fn hit<T>(intersections: &[Intersection<T>]) -> Option<&Intersection<T>> {
     intersections.iter().fold(None, |acc, i| {
         if i.t() >= 0.0 {
             acc.map(|lowest| if lowest.t() < i.t() { lowest } else { i })
@@ -167,7 +574,26 @@ pub fn hit<T, C, N>(intersections: &[Intersection<T, C, N>]) -> Option<&Intersec
     })
 }
 
-pub fn sort<T, C, N>(xs: &mut [Intersection<T, C, N>]) {
+/// The intersection [hit] would pick from `xs` in sorted order, only considering those with
+/// `t <= t_max`. Ties go to whichever comes last.
+pub fn closest<T>(
+    xs: impl IntoIterator<Item = Intersection<T>>,
+    t_max: f64,
+) -> Option<Intersection<T>> {
+    xs.into_iter()
+        .filter(|i| i.t() >= 0.0 && i.t() <= t_max)
+        .fold(None, |acc, i| match acc {
+            Some(lowest) if lowest.t() < i.t() => Some(lowest),
+            _ => Some(i),
+        })
+}
+
+/// Like [closest], but for bare values of `t`
+pub fn closest_t(ts: impl IntoIterator<Item = f64>, t_max: f64) -> Option<f64> {
+    closest(ts.into_iter().map(|t| Intersection::new(t, ())), t_max).map(|i| i.t())
+}
+
+pub fn sort<T>(xs: &mut [Intersection<T>]) {
     xs.sort_by(|a, b| a.t().partial_cmp(&b.t()).unwrap())
 }
 
@@ -175,8 +601,8 @@ pub fn sort<T, C, N>(xs: &mut [Intersection<T, C, N>]) {
 pub mod test_utils {
     use super::*;
 
-    pub fn to_ts<T, C, N>(ts: &[Intersection<T, C, N>]) -> Vec<f64> {
-        ts.into_iter().map(|i| i.t()).collect()
+    pub fn to_ts<T>(ts: &[Intersection<T>]) -> Vec<f64> {
+        ts.iter().map(|i| i.t()).collect()
     }
 }
 
@@ -202,7 +628,7 @@ mod test {
     fn an_intersection_encapsulates_t_and_object() {
         let s = Sphere::unit();
 
-        let i = Intersection::new(3.5, &s, default_color(), default_normal());
+        let i = Intersection::new(3.5, &s);
 
         assert_eq!(i.t(), 3.5);
         assert!(std::ptr::eq(*i.object(), &s));
@@ -214,8 +640,8 @@ mod test {
         #[test]
         fn hit_when_all_intersections_have_positive_t() {
             let s = Sphere::unit();
-            let i1 = Intersection::new(1.0, &s, default_color(), default_normal());
-            let i2 = Intersection::new(2.0, &s, default_color(), default_normal());
+            let i1 = Intersection::new(1.0, &s);
+            let i2 = Intersection::new(2.0, &s);
             let xs = vec![i2, i1];
 
             let i = hit(&xs);
@@ -226,8 +652,8 @@ mod test {
         #[test]
         fn hit_when_some_intersections_have_negative_t() {
             let s = Sphere::unit();
-            let i1 = Intersection::new(-1.0, &s, default_color(), default_normal());
-            let i2 = Intersection::new(1.0, &s, default_color(), default_normal());
+            let i1 = Intersection::new(-1.0, &s);
+            let i2 = Intersection::new(1.0, &s);
             let xs = vec![i2, i1];
 
             let i = hit(&xs);
@@ -238,8 +664,8 @@ mod test {
         #[test]
         fn hit_when_all_intersections_have_negative_t() {
             let s = Sphere::unit();
-            let i1 = Intersection::new(-2.0, &s, default_color(), default_normal());
-            let i2 = Intersection::new(-1.0, &s, default_color(), default_normal());
+            let i1 = Intersection::new(-2.0, &s);
+            let i2 = Intersection::new(-1.0, &s);
             let xs = vec![i2, i1];
 
             let i = hit(&xs);
@@ -250,16 +676,134 @@ mod test {
         #[test]
         fn hit_is_always_lowest_nonnegative_intersection() {
             let s = Sphere::unit();
-            let i1 = Intersection::new(5.0, &s, default_color(), default_normal());
-            let i2 = Intersection::new(7.0, &s, default_color(), default_normal());
-            let i3 = Intersection::new(-3.0, &s, default_color(), default_normal());
-            let i4 = Intersection::new(2.0, &s, default_color(), default_normal());
+            let i1 = Intersection::new(5.0, &s);
+            let i2 = Intersection::new(7.0, &s);
+            let i3 = Intersection::new(-3.0, &s);
+            let i4 = Intersection::new(2.0, &s);
             let xs = vec![i1, i2, i3, i4];
 
             let i = hit(&xs);
 
             assert!(std::ptr::eq(i.unwrap(), &xs[3]));
         }
+
+        #[test]
+        fn closest_ignores_intersections_beyond_t_max() {
+            let xs = [-1.0, 5.0, 2.0, 7.0].map(|t| Intersection::new(t, ()));
+
+            assert_eq!(closest(xs, f64::INFINITY).map(|i| i.t()), Some(2.0));
+            assert_eq!(closest(xs, 2.0).map(|i| i.t()), Some(2.0));
+            assert_eq!(closest(xs, 1.5).map(|i| i.t()), None);
+        }
+
+        #[test]
+        fn closest_agrees_with_hit_on_ties() {
+            let xs = [(3.0, 0), (1.0, 1), (1.0, 2)].map(|(t, id)| Intersection::new(t, id));
+
+            assert_eq!(
+                closest(xs, f64::INFINITY).map(|i| *i.object()),
+                hit(&xs).map(|i| *i.object())
+            );
+        }
+    }
+
+    mod space {
+        use crate::{
+            math::{matrix::InvertibleMatrix, vector},
+            scene::{object::transformed::Transformed, transformation},
+        };
+
+        use super::*;
+
+        fn transformed<T: Object>(child: T, transform: SquareMatrix<4>) -> Transformed<T> {
+            Transformed::new(child, InvertibleMatrix::try_from(transform).unwrap())
+        }
+
+        /// A sphere nested three deep, as in the book's groups of groups
+        fn nested_sphere(scale: (f64, f64, f64)) -> impl Object {
+            transformed(
+                transformed(
+                    transformed(Sphere::unit(), transformation::translation(5.0, 0.0, 0.0)),
+                    transformation::scaling(scale.0, scale.1, scale.2),
+                ),
+                transformation::rotation_y(std::f64::consts::FRAC_PI_2),
+            )
+        }
+
+        fn ray() -> Ray {
+            Ray::new(Point3d::new(0.0, 0.0, -20.0), Vec3d::new(0.0, 0.0, 1.0))
+        }
+
+        #[test]
+        fn converting_a_point_from_world_to_object_space() {
+            let s = nested_sphere((2.0, 2.0, 2.0));
+            let xs = s.intersect(&ray());
+
+            assert_eq!(
+                xs.world_to_object(&xs[0], &Point3d::new(-2.0, 0.0, -10.0)),
+                Point3d::new(0.0, 0.0, -1.0)
+            );
+        }
+
+        #[test]
+        fn converting_a_point_from_object_to_world_space() {
+            let s = nested_sphere((2.0, 2.0, 2.0));
+            let xs = s.intersect(&ray());
+
+            let world = xs.object_to_world(&xs[0], &Point3d::new(0.0, 0.0, -1.0));
+            assert!(util::test_utils::are_within_tolerance(
+                world.x(),
+                -2.0,
+                1e-9
+            ));
+            assert!(util::test_utils::are_within_tolerance(world.y(), 0.0, 1e-9));
+            assert!(util::test_utils::are_within_tolerance(
+                world.z(),
+                -10.0,
+                1e-9
+            ));
+        }
+
+        #[test]
+        fn converting_a_normal_from_object_to_world_space() {
+            let s = nested_sphere((1.0, 2.0, 3.0));
+            let xs = s.intersect(&ray());
+            let t = 3f64.sqrt() / 3.0;
+
+            vector::test_utils::assert_vec_approx_equals(
+                &xs.normal_to_world(&xs[0], NormalizedVec3d::new(t, t, t).unwrap()),
+                &Vec3d::new(0.28571, 0.42857, -0.85714),
+            );
+        }
+
+        #[test]
+        fn converting_a_normal_to_object_space_and_back() {
+            let s = nested_sphere((2.0, 2.0, 2.0));
+            let xs = s.intersect(&ray());
+            let normal = NormalizedVec3d::new(1.0, -2.0, 3.0).unwrap();
+
+            let local = xs.normal_to_object(&xs[0], &normal);
+            vector::test_utils::assert_vec_approx_equals(
+                &xs.normal_to_world(&xs[0], local),
+                &normal,
+            );
+        }
+
+        #[test]
+        fn the_world_normal_at_a_hit_matches_the_shaded_normal() {
+            let s = nested_sphere((2.0, 2.0, 2.0));
+            let r = ray();
+            let xs = s.intersect(&r);
+
+            let local_point = xs.world_to_object(&xs[0], &r.position(xs[0].t()));
+            let local_normal =
+                NormalizedVec3d::try_from(&local_point - &Point3d::new(0.0, 0.0, 0.0)).unwrap();
+
+            vector::test_utils::assert_vec_approx_equals(
+                &xs.normal_to_world(&xs[0], local_normal),
+                &xs.normal_at(&xs[0], &r),
+            );
+        }
     }
 
     mod prepare_computations {
@@ -284,7 +828,7 @@ mod test {
             let is = s.intersect(&r);
             let i = &is[0];
 
-            let comps = i.prepare_computations(&r, &vec![]);
+            let comps = i.prepare_computations(&r, &is);
 
             assert_eq!(comps.t, i.t());
             assert!(std::ptr::eq(comps.object, *i.object()));
@@ -306,7 +850,7 @@ mod test {
             let r = Ray::new(Point3d::new(0.0, 1.0, -1.0), Vec3d::new(0.0, -t, t));
             let is = shape.intersect(&r);
 
-            let comps = is[0].prepare_computations(&r, &vec![]);
+            let comps = is[0].prepare_computations(&r, &is);
 
             assert_eq!(*comps.reflect_v, Vec3d::new(0.0, t, t));
         }
@@ -317,9 +861,9 @@ mod test {
             let s: Sphere = Default::default();
             let is = s.intersect(&r);
 
-            let comps = is[0].prepare_computations(&r, &vec![]);
+            let comps = is[0].prepare_computations(&r, &is);
 
-            assert_eq!(comps.inside, false);
+            assert!(!comps.inside);
         }
 
         #[test]
@@ -328,14 +872,14 @@ mod test {
             let s: Sphere = Default::default();
             let is = s.intersect(&r);
 
-            let comps = is[1].prepare_computations(&r, &vec![]);
+            let comps = is[1].prepare_computations(&r, &is);
 
             assert_eq!(comps.point, Point3d::new(0.0, 0.0, 1.0));
             assert_eq!(
                 comps.eye_v,
                 NormalizedVec3d::try_from(Vec3d::new(0.0, 0.0, -1.0)).unwrap()
             );
-            assert_eq!(comps.inside, true);
+            assert!(comps.inside);
             assert_eq!(
                 comps.normal_v,
                 NormalizedVec3d::try_from(Vec3d::new(0.0, 0.0, -1.0)).unwrap()
@@ -344,10 +888,7 @@ mod test {
 
         #[test]
         fn the_hit_should_offset_the_over_point() {
-            let r = Ray {
-                origin: Point3d::new(0.0, 0.0, -5.0),
-                direction: Vec3d::new(0.0, 0.0, 1.0),
-            };
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
             let shape = Transformed::new(
                 Sphere::unit(),
                 InvertibleMatrix::try_from(transformation::translation(0.0, 0.0, 1.0)).unwrap(),
@@ -355,7 +896,7 @@ mod test {
             let is = shape.intersect(&r);
             let i = &is[0];
 
-            let comps = i.prepare_computations(&r, &vec![]);
+            let comps = i.prepare_computations(&r, &is);
 
             assert!(comps.over_point.z() < -POINT_OFFSET_BIAS / 2.0);
             assert!(comps.point.z() > comps.over_point.z());
@@ -385,14 +926,14 @@ mod test {
                 Transformed<Sphere>,
             ) {
                 let mut a_s = sphere::glass_sphere();
-                a_s.material.refractive_index = 1.5;
+                a_s.material.as_mut().unwrap().refractive_index = 1.5;
                 let a = Transformed::new(
                     a_s,
                     InvertibleMatrix::try_from(transformation::scaling(2.0, 2.0, 2.0)).unwrap(),
                 );
 
                 let mut b_s = sphere::glass_sphere();
-                b_s.material.refractive_index = 2.0;
+                b_s.material.as_mut().unwrap().refractive_index = 2.0;
                 let b = Transformed::new(
                     b_s,
                     InvertibleMatrix::try_from(transformation::translation(0.0, 0.0, -0.25))
@@ -400,7 +941,7 @@ mod test {
                 );
 
                 let mut c_s = sphere::glass_sphere();
-                c_s.material.refractive_index = 2.5;
+                c_s.material.as_mut().unwrap().refractive_index = 2.5;
                 let c = Transformed::new(
                     c_s,
                     InvertibleMatrix::try_from(transformation::translation(0.0, 0.0, 0.25))
@@ -414,7 +955,7 @@ mod test {
                 a: &'a Transformed<Sphere>,
                 b: &'a Transformed<Sphere>,
                 c: &'a Transformed<Sphere>,
-            ) -> Vec<Intersection<&'a Transformed<Sphere>, Color, NormalizedVec3d>> {
+            ) -> Vec<Intersection<&'a Transformed<Sphere>>> {
                 vec![
                     (2.0, a),
                     (2.75, b),
@@ -424,7 +965,7 @@ mod test {
                     (6.0, a),
                 ]
                 .into_iter()
-                .map(|(t, o)| Intersection::new(t, o, default_color(), default_normal()))
+                .map(|(t, o)| Intersection::new(t, o))
                 .collect()
             }
 
@@ -437,7 +978,15 @@ mod test {
                             let (a, b, c) = get_objects();
                             let xs = get_xs(&a, &b, &c);
                             let r = Ray::new(Point3d::new(0.0, 0.0, -4.0), Vec3d::new(0.0, 0.0, 1.0));
-                            let comps = xs[index].prepare_computations(&r, &xs);
+                            let comps = prepare_computations_helper(
+                                &xs[index],
+                                &r,
+                                (default_normal(), default_color()),
+                                &xs,
+                                |i| i.object().material().unwrap(),
+                                |i, p| i.object().contains(p, 0.0),
+                                |a, b| std::ptr::eq(*a.object(), *b.object()),
+                            );
 
                             assert_eq!(comps.refraction_exiting, n1);
                             assert_eq!(comps.refraction_entering, n2);
@@ -454,6 +1003,27 @@ mod test {
                 refractive_index_4: (4, 2.5, 1.5),
                 refractive_index_5: (5, 1.5, 1.0),
             }
+
+            #[test]
+            fn a_repeated_hit_does_not_leave_an_object_that_contains_the_ray() {
+                let (a, b, c) = get_objects();
+                let mut xs = get_xs(&a, &b, &c);
+                xs.insert(0, Intersection::new(2.0, &a));
+                let r = Ray::new(Point3d::new(0.0, 0.0, -4.0), Vec3d::new(0.0, 0.0, 1.0));
+
+                let comps = prepare_computations_helper(
+                    &xs[2],
+                    &r,
+                    (default_normal(), default_color()),
+                    &xs,
+                    |i| i.object().material().unwrap(),
+                    |i, p| i.object().contains(p, 0.0),
+                    |a, b| std::ptr::eq(*a.object(), *b.object()),
+                );
+
+                assert_eq!(comps.refraction_exiting, 1.5);
+                assert_eq!(comps.refraction_entering, 2.0);
+            }
         }
 
         mod schlick {
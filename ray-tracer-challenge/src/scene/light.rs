@@ -1,14 +1,244 @@
-use crate::{draw::color::Color, math::point::Point3d};
+use std::f64::consts;
+
+use rand::Rng;
+
+use crate::{
+    draw::color::{self, Color},
+    math::{matrix::InvertibleMatrix, point::Point3d, sampling, vector::Vec3d},
+};
+
+use super::{
+    material::{Material, Surface},
+    object::{group::Group, sphere::Sphere, transformed::Transformed, triangle::Triangle, Object},
+    ray::Ray,
+    transformation,
+};
+
+/// How far off the surface of an area light its sample points sit, so that shadow rays cast
+/// toward them aren't blocked by the light's own glowing object
+const SURFACE_OFFSET: f64 = 1e-4;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct PointLight {
     pub position: Point3d,
+    /// The brightness of the light, as seen from within its falloff's reference radius
+    pub intensity: Color,
+    pub falloff: Falloff,
+}
+
+/// How a light dims the farther it shines
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Falloff {
+    /// The light is equally bright at any distance
+    #[default]
+    None,
+    /// Past `radius`, the light dims in proportion to the distance
+    Linear { radius: f64 },
+    /// Past `radius`, the light dims with the square of the distance, as real lights do
+    InverseSquare { radius: f64 },
+}
+
+impl Falloff {
+    /// The fraction of a light's intensity that reaches `distance` away from it
+    pub fn factor(&self, distance: f64) -> f64 {
+        match self {
+            Falloff::None => 1.0,
+            Falloff::Linear { radius } => (radius / distance).min(1.0),
+            Falloff::InverseSquare { radius } => (radius / distance).powi(2).min(1.0),
+        }
+    }
+}
+
+impl PointLight {
+    /// The light's intensity once it has dimmed on the way to `point`
+    pub fn intensity_at(&self, point: &Point3d) -> Color {
+        match self.falloff {
+            Falloff::None => self.intensity.clone(),
+            _ => &self.intensity * self.falloff.factor((&self.position - point).mag()),
+        }
+    }
+}
+
+/// A glowing sphere or rectangle that lights the scene from all over its surface, casting soft
+/// shadows
+#[derive(Debug, Clone, PartialEq)]
+pub struct AreaLight {
+    pub shape: LightShape,
+    /// The brightness of the light as a whole, like that of a point light
     pub intensity: Color,
+    /// How many points on the light to sample for each shaded point
+    pub samples: usize,
+    /// How the light dims with distance from each point on its surface
+    pub falloff: Falloff,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LightShape {
+    Sphere {
+        center: Point3d,
+        radius: f64,
+    },
+    /// The parallelogram spanned by `u` and `v` from `corner`
+    Rectangle {
+        corner: Point3d,
+        u: Vec3d,
+        v: Vec3d,
+    },
+}
+
+impl AreaLight {
+    /// Point lights at random points on the part of the light seen from `from`, which together
+    /// stand in for the whole light. The points are always the same for the same `from`.
+    pub fn point_lights(&self, from: &Point3d) -> Vec<PointLight> {
+        let mut rng = sampling::rng_for_point(from);
+        (0..self.samples.max(1))
+            .map(|_| {
+                let on_surface = self.shape.sample(&mut rng, from);
+                let position = match (from - &on_surface).norm() {
+                    Some(towards) => &on_surface + &(&towards * SURFACE_OFFSET),
+                    None => on_surface,
+                };
+                PointLight {
+                    position,
+                    intensity: self.intensity.clone(),
+                    falloff: self.falloff.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// The light's glowing surface, to add to the world's objects so that the light can be seen
+    pub fn object(&self) -> Result<Box<dyn Object>, String> {
+        let material = Material {
+            surface: Surface::Color(color::black()),
+            ambient: 0.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            emissive: self.intensity.clone(),
+            ..Default::default()
+        };
+
+        Ok(match &self.shape {
+            LightShape::Sphere { center, radius } => {
+                let transform = transformation::sequence(&[
+                    transformation::scaling(*radius, *radius, *radius),
+                    transformation::translation(center.x(), center.y(), center.z()),
+                ]);
+                Box::new(Transformed::new(
+                    Sphere::new(material),
+                    InvertibleMatrix::try_from(transform)?,
+                ))
+            }
+            LightShape::Rectangle { corner, u, v } => {
+                let opposite = &(corner + u) + v;
+                Box::new(Group::with_material(
+                    vec![
+                        Triangle::flat([corner.clone(), corner + u, opposite.clone()], None),
+                        Triangle::flat([corner.clone(), opposite, corner + v], None),
+                    ],
+                    material,
+                ))
+            }
+        })
+    }
+}
+
+impl LightShape {
+    /// A random point anywhere on the shape, with every part of it equally likely
+    fn random_point<R: Rng + ?Sized>(&self, rng: &mut R) -> Point3d {
+        match self {
+            LightShape::Sphere { center, radius } => {
+                let d = sampling::in_cone(rng, &Vec3d::new(0.0, 1.0, 0.0), consts::PI);
+                center + &(&d * *radius)
+            }
+            LightShape::Rectangle { corner, u, v } => {
+                let (a, b): (f64, f64) = (rng.gen(), rng.gen());
+                &(corner + &(u * a)) + &(v * b)
+            }
+        }
+    }
+
+    /// A ray leaving a random point on the shape, in a direction more likely the closer it is to
+    /// straight out of the surface, as light leaves a matte glowing surface. A rectangle glows
+    /// from both of its faces.
+    pub(crate) fn random_emission<R: Rng + ?Sized>(&self, rng: &mut R) -> Ray {
+        let (point, normal) = match self {
+            LightShape::Sphere { center, radius } => {
+                let d = sampling::in_cone(rng, &Vec3d::new(0.0, 1.0, 0.0), consts::PI);
+                (center + &(&d * *radius), d)
+            }
+            LightShape::Rectangle { u, v, .. } => {
+                let normal = u
+                    .cross(v)
+                    .norm()
+                    .unwrap_or_else(|| Vec3d::new(0.0, 1.0, 0.0));
+                let side = if rng.gen::<bool>() { 1.0 } else { -1.0 };
+                (self.random_point(rng), &normal * side)
+            }
+        };
+
+        // Start just off the surface, so the ray doesn't hit the light's own glowing object
+        Ray::new(
+            &point + &(&normal * SURFACE_OFFSET),
+            sampling::cosine_weighted_hemisphere(rng, &normal),
+        )
+    }
+
+    /// Whether `point` lies on the shape's surface, as a hit on the light's object would
+    pub(crate) fn is_on_surface(&self, point: &Point3d) -> bool {
+        match self {
+            LightShape::Sphere { center, radius } => {
+                ((point - center).mag() - radius).abs() < SURFACE_OFFSET
+            }
+            LightShape::Rectangle { corner, u, v } => {
+                let Some(normal) = u.cross(v).norm() else {
+                    return false;
+                };
+                let w = point - corner;
+                if w.dot(&normal).abs() >= SURFACE_OFFSET {
+                    return false;
+                }
+
+                // Solve for how far along `u` and `v` the point is
+                let (uu, uv, vv) = (u.dot(u), u.dot(v), v.dot(v));
+                let (wu, wv) = (w.dot(u), w.dot(v));
+                let det = uu * vv - uv * uv;
+                let a = (vv * wu - uv * wv) / det;
+                let b = (uu * wv - uv * wu) / det;
+                let within = |x: f64| (-SURFACE_OFFSET..=1.0 + SURFACE_OFFSET).contains(&x);
+                within(a) && within(b)
+            }
+        }
+    }
+
+    /// A random point on the part of the shape that can be seen from `from`
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R, from: &Point3d) -> Point3d {
+        match self {
+            LightShape::Sphere { center, radius } => {
+                let to_center = center - from;
+                let distance = to_center.mag();
+                if distance <= *radius {
+                    return self.random_point(rng);
+                }
+
+                // Pick a direction toward the sphere and take the first point it hits
+                let axis = &to_center / distance;
+                let d = sampling::in_cone(rng, &axis, f64::asin(radius / distance));
+                let along = d.dot(&to_center);
+                let discriminant = (along * along - distance * distance + radius * radius).max(0.0);
+                from + &(&d * (along - discriminant.sqrt()))
+            }
+            LightShape::Rectangle { .. } => self.random_point(rng),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::draw::color::Color;
+    use crate::{
+        draw::color::Color, math::util::test_utils::are_within_tolerance,
+        scene::intersect::Intersections,
+    };
 
     use super::*;
 
@@ -19,9 +249,162 @@ mod tests {
         let light = PointLight {
             position: position.clone(),
             intensity: intensity.clone(),
+            falloff: Falloff::None,
         };
 
         assert_eq!(light.position, position);
         assert_eq!(light.intensity, intensity);
     }
+
+    #[test]
+    fn falloff_leaves_the_light_at_full_intensity_within_its_radius() {
+        let linear = Falloff::Linear { radius: 2.0 };
+        let inverse_square = Falloff::InverseSquare { radius: 2.0 };
+
+        assert_eq!(Falloff::None.factor(100.0), 1.0);
+        assert_eq!(linear.factor(1.0), 1.0);
+        assert_eq!(linear.factor(8.0), 0.25);
+        assert_eq!(inverse_square.factor(1.0), 1.0);
+        assert_eq!(inverse_square.factor(8.0), 0.0625);
+    }
+
+    #[test]
+    fn a_point_light_dims_with_distance() {
+        let light = PointLight {
+            position: Point3d::new(0.0, 10.0, 0.0),
+            intensity: color::tungsten_100w(),
+            falloff: Falloff::InverseSquare { radius: 5.0 },
+        };
+
+        assert_eq!(
+            light.intensity_at(&Point3d::new(0.0, 0.0, 0.0)),
+            &color::tungsten_100w() * 0.25
+        );
+        assert_eq!(
+            light.intensity_at(&Point3d::new(0.0, 8.0, 0.0)),
+            color::tungsten_100w()
+        );
+    }
+
+    #[test]
+    fn area_light_samples_share_its_falloff() {
+        let light = AreaLight {
+            falloff: Falloff::Linear { radius: 1.0 },
+            ..sphere_light()
+        };
+
+        for l in light.point_lights(&Point3d::new(0.0, 0.0, 0.0)) {
+            assert_eq!(l.falloff, Falloff::Linear { radius: 1.0 });
+        }
+    }
+
+    fn sphere_light() -> AreaLight {
+        AreaLight {
+            shape: LightShape::Sphere {
+                center: Point3d::new(0.0, 10.0, 0.0),
+                radius: 2.0,
+            },
+            intensity: color::white(),
+            samples: 16,
+            falloff: Falloff::None,
+        }
+    }
+
+    #[test]
+    fn sphere_light_samples_lie_on_the_side_facing_the_point() {
+        let light = sphere_light();
+        let from = Point3d::new(0.0, 0.0, 0.0);
+
+        let lights = light.point_lights(&from);
+
+        assert_eq!(lights.len(), 16);
+        for l in lights {
+            let off_center = &l.position - &Point3d::new(0.0, 10.0, 0.0);
+            assert!(are_within_tolerance(off_center.mag(), 2.0, 1e-3));
+            assert!(l.position.y() < 10.0);
+            assert_eq!(l.intensity, color::white());
+        }
+    }
+
+    #[test]
+    fn rectangle_light_samples_lie_within_the_rectangle() {
+        let light = AreaLight {
+            shape: LightShape::Rectangle {
+                corner: Point3d::new(-1.0, 5.0, -1.0),
+                u: Vec3d::new(2.0, 0.0, 0.0),
+                v: Vec3d::new(0.0, 0.0, 2.0),
+            },
+            intensity: color::white(),
+            samples: 16,
+            falloff: Falloff::None,
+        };
+
+        for l in light.point_lights(&Point3d::new(0.0, 0.0, 0.0)) {
+            assert!(l.position.x().abs() <= 1.0 && l.position.z().abs() <= 1.0);
+            assert!(are_within_tolerance(l.position.y(), 5.0, 1e-3));
+        }
+    }
+
+    #[test]
+    fn samples_are_the_same_for_the_same_point() {
+        let light = sphere_light();
+        let from = Point3d::new(1.0, 0.0, 0.0);
+
+        assert_eq!(light.point_lights(&from), light.point_lights(&from));
+    }
+
+    #[test]
+    fn samples_sit_just_outside_the_light_object() {
+        let light = sphere_light();
+        let object = light.object().unwrap();
+        let from = Point3d::new(3.0, 0.0, 0.0);
+
+        for l in light.point_lights(&from) {
+            let to_light = &l.position - &from;
+            let r = Ray::new(from.clone(), to_light.norm().unwrap());
+            assert!(!object.occluded(&r, to_light.mag(), &mut Intersections::new()));
+        }
+    }
+
+    #[test]
+    fn light_leaves_the_surface_outward() {
+        let light = sphere_light();
+        let object = light.object().unwrap();
+        let mut rng = sampling::rng_for_point(&Point3d::new(0.0, 0.0, 0.0));
+
+        for _ in 0..100 {
+            let r = light.shape.random_emission(&mut rng);
+            let normal = (&r.origin - &Point3d::new(0.0, 10.0, 0.0)).norm().unwrap();
+            assert!(r.direction.dot(&normal) >= 0.0);
+            assert!(!object.occluded(&r, f64::INFINITY, &mut Intersections::new()));
+        }
+    }
+
+    #[test]
+    fn points_on_a_light_lie_on_its_surface() {
+        let sphere = sphere_light().shape;
+        let rectangle = LightShape::Rectangle {
+            corner: Point3d::new(-1.0, 5.0, -1.0),
+            u: Vec3d::new(2.0, 0.0, 0.0),
+            v: Vec3d::new(0.0, 0.0, 2.0),
+        };
+
+        assert!(sphere.is_on_surface(&Point3d::new(0.0, 8.0, 0.0)));
+        assert!(!sphere.is_on_surface(&Point3d::new(0.0, 9.0, 0.0)));
+        assert!(rectangle.is_on_surface(&Point3d::new(0.5, 5.0, 1.0)));
+        assert!(!rectangle.is_on_surface(&Point3d::new(0.5, 5.1, 0.0)));
+        assert!(!rectangle.is_on_surface(&Point3d::new(1.5, 5.0, 0.0)));
+    }
+
+    #[test]
+    fn the_light_object_glows_with_the_light_intensity() {
+        let light = sphere_light();
+        let object = light.object().unwrap();
+        let r = Ray::new(Point3d::new(0.0, 0.0, 0.0), Vec3d::new(0.0, 1.0, 0.0));
+
+        let xs = object.intersect(&r);
+
+        assert_eq!(xs[0].t(), 8.0);
+        assert_eq!(xs.material(&xs[0]).emissive, color::white());
+    }
 }
@@ -1,9 +1,17 @@
+use std::{f64::consts, sync::LazyLock};
+
 use crate::{
     draw::color::{self, Color},
-    math::{point::Point3d, vector::NormalizedVec3d},
+    math::{
+        point::Point3d,
+        vector::{NormalizedVec3d, Vec3d},
+    },
 };
 
-use super::{light::PointLight, pattern::Pattern};
+use super::{
+    light::PointLight,
+    pattern::{Pattern, Space},
+};
 
 pub enum Surface {
     Color(Color),
@@ -17,10 +25,23 @@ impl Surface {
             Surface::Pattern(p) => p.at(point),
         }
     }
+
+    /// The color at a hit, looked up at whichever of its points is in the pattern's space
+    pub fn color_at_hit(&self, object_point: &Point3d, world_point: &Point3d) -> Color {
+        match self {
+            Surface::Color(c) => c.clone(),
+            Surface::Pattern(p) => p.at(match p.space() {
+                Space::Object => object_point,
+                Space::World => world_point,
+            }),
+        }
+    }
 }
 
 pub struct Material {
+    /// The object's color, or the base color of a [ShadingModel::Microfacet] material
     pub surface: Surface,
+    pub model: ShadingModel,
     pub ambient: f64,
     pub diffuse: f64,
     pub specular: f64,
@@ -28,6 +49,104 @@ pub struct Material {
     pub reflectivity: f64,
     pub transparency: f64,
     pub refractive_index: f64,
+    /// How blurry reflections and refractions are, from 0 for perfectly sharp to 1 for scattering
+    /// rays across the whole hemisphere. A [ShadingModel::Microfacet] material's highlights spread
+    /// out with it too, from a pinpoint to completely matte.
+    pub roughness: f64,
+    /// Light given off by the surface itself, seen whether or not anything lights it
+    pub emissive: Color,
+    /// Tints light traveling through a transparent material by how far it travels
+    pub absorption: Option<Absorption>,
+}
+
+/// How a material reflects the light arriving straight from a light source
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ShadingModel {
+    /// The book's model, using the material's `diffuse`, `specular` and `shininess`
+    #[default]
+    Phong,
+    Microfacet(Microfacet),
+}
+
+/// A physically based Cook–Torrance model with a GGX distribution of microfacets
+#[derive(Debug, Clone, PartialEq)]
+pub struct Microfacet {
+    /// From 0 for a dielectric like plastic to 1 for a bare metal, which has no diffuse color
+    pub metalness: f64,
+    /// How much a dielectric's highlight takes on the base color instead of staying white
+    pub specular_tint: f64,
+}
+
+impl Microfacet {
+    /// The diffuse and specular light reflected toward the eye, scaled like the Phong model's so
+    /// that a white matte surface lit head-on reflects about the light's full intensity. `incoming`
+    /// is the light's intensity after shadowing.
+    fn direct(
+        &self,
+        roughness: f64,
+        base_color: &Color,
+        incoming: &Color,
+        lightv: &Vec3d,
+        eyev: &NormalizedVec3d,
+        normalv: &NormalizedVec3d,
+    ) -> (Color, Color) {
+        let n_dot_l = lightv.dot(normalv);
+        // A light exactly edge-on adds nothing, and would otherwise divide zero by zero below
+        if n_dot_l <= 0.0 {
+            return (color::black(), color::black());
+        }
+        // Seen exactly edge-on the highlight would blow up, so treat it as nearly edge-on instead
+        let n_dot_v = eyev.dot(normalv).max(1e-4);
+        let halfv = (lightv + eyev)
+            .norm()
+            .unwrap_or_else(|| (**normalv).clone());
+        let n_dot_h = halfv.dot(normalv).max(0.0);
+        let v_dot_h = halfv.dot(eyev).max(0.0);
+
+        let roughness = roughness.clamp(1e-3, 1.0);
+        let alpha2 = roughness.powi(4);
+        let distribution =
+            alpha2 / (consts::PI * (n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0).powi(2));
+
+        let k = (roughness + 1.0).powi(2) / 8.0;
+        let geometry = |x: f64| x / (x * (1.0 - k) + k);
+        let visibility = geometry(n_dot_l) * geometry(n_dot_v) / (4.0 * n_dot_l * n_dot_v);
+
+        // Schlick's approximation, starting from 4% reflectance for dielectrics
+        let tint =
+            &(&color::white() * (1.0 - self.specular_tint)) + &(base_color * self.specular_tint);
+        let f0 = &(&tint * (0.04 * (1.0 - self.metalness))) + &(base_color * self.metalness);
+        let fresnel = &f0 + &(&(&color::white() - &f0) * (1.0 - v_dot_h).powi(5));
+
+        // Whatever isn't reflected off the surface is scattered diffusely, unless it's a metal
+        let diffuse_weight = &(&color::white() - &fresnel) * (1.0 - self.metalness);
+
+        let radiance = incoming * n_dot_l;
+        (
+            &(&diffuse_weight * base_color) * &radiance,
+            &(&fresnel * (consts::PI * distribution * visibility)) * &radiance,
+        )
+    }
+}
+
+/// Beer–Lambert absorption, so that thick glass is more deeply tinted than a thin pane
+#[derive(Debug, Clone, PartialEq)]
+pub struct Absorption {
+    /// The color white light takes on after traveling `1 / density` through the material
+    pub color: Color,
+    pub density: f64,
+}
+
+impl Absorption {
+    /// The fraction of each channel that makes it through `distance` of the material
+    pub fn transmittance(&self, distance: f64) -> Color {
+        let d = self.density * distance;
+        Color::new(
+            self.color.r().powf(d),
+            self.color.g().powf(d),
+            self.color.b().powf(d),
+        )
+    }
 }
 
 impl PartialEq for Material {
@@ -40,6 +159,7 @@ impl Default for Material {
     fn default() -> Self {
         Self {
             surface: Surface::Color(Color::new(1.0, 1.0, 1.0)),
+            model: ShadingModel::Phong,
             ambient: 0.1,
             diffuse: 0.9,
             specular: 0.9,
@@ -47,10 +167,19 @@ impl Default for Material {
             reflectivity: 0.0,
             transparency: 0.0,
             refractive_index: 1.0,
+            roughness: 0.0,
+            emissive: color::black(),
+            absorption: None,
         }
     }
 }
 
+/// The material of objects that neither have one of their own nor inherit one
+pub fn default_material() -> &'static Material {
+    static DEFAULT: LazyLock<Material> = LazyLock::new(Material::default);
+    &DEFAULT
+}
+
 pub fn lighting(
     material: &Material,
     point: &Point3d,
@@ -58,17 +187,27 @@ pub fn lighting(
     light: &PointLight,
     eyev: &NormalizedVec3d,
     normalv: &NormalizedVec3d,
-    shadow_attenuation: f64,
+    shadow_attenuation: &Color,
 ) -> Color {
-    let effective_color = object_color * &light.intensity;
+    let intensity = light.intensity_at(point);
+    let effective_color = object_color * &intensity;
     let lightv = (&light.position - point).norm().unwrap();
 
-    let ambient = &effective_color * material.ambient;
+    let ambient = ambient(material, point, object_color, light);
 
     let light_dot_normal = lightv.dot(normalv);
 
     let (diffuse, specular) = if light_dot_normal < 0.0 {
         (color::black(), color::black())
+    } else if let ShadingModel::Microfacet(m) = &material.model {
+        m.direct(
+            material.roughness,
+            object_color,
+            &(&intensity * shadow_attenuation),
+            &lightv,
+            eyev,
+            normalv,
+        )
     } else {
         let diff = &(&effective_color * material.diffuse) * light_dot_normal;
 
@@ -81,7 +220,7 @@ pub fn lighting(
                 color::black()
             } else {
                 let factor = reflect_dot_eye.powf(material.shininess);
-                &light.intensity * (material.specular * factor * shadow_attenuation)
+                &intensity * &(shadow_attenuation * (material.specular * factor))
             },
         )
     };
@@ -89,6 +228,16 @@ pub fn lighting(
     &(&ambient + &diffuse) + &specular
 }
 
+/// The ambient term of [lighting] on its own
+pub fn ambient(
+    material: &Material,
+    point: &Point3d,
+    object_color: &Color,
+    light: &PointLight,
+) -> Color {
+    &(object_color * &light.intensity_at(point)) * material.ambient
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -105,6 +254,9 @@ mod tests {
         assert_eq!(m.reflectivity, 0.0);
         assert_eq!(m.transparency, 0.0);
         assert_eq!(m.refractive_index, 1.0);
+        assert_eq!(m.roughness, 0.0);
+        assert_eq!(m.emissive, color::black());
+        assert_eq!(m.absorption, None);
     }
 
     #[test]
@@ -120,10 +272,46 @@ mod tests {
         assert!(m1 != m2);
     }
 
+    #[test]
+    fn absorption_deepens_with_distance() {
+        let a = Absorption {
+            color: Color::new(1.0, 0.5, 0.25),
+            density: 2.0,
+        };
+
+        assert_eq!(a.transmittance(0.0), color::white());
+        assert_eq!(a.transmittance(0.5), Color::new(1.0, 0.5, 0.25));
+        assert_eq!(a.transmittance(1.0), Color::new(1.0, 0.25, 0.0625));
+    }
+
+    #[test]
+    fn a_hit_is_colored_at_the_point_in_its_patterns_space() {
+        let object = Point3d::new(0.5, 0.0, 0.0);
+        let world = Point3d::new(1.5, 0.0, 0.0);
+        let surface = |space| {
+            Surface::Pattern(Box::new(crate::scene::pattern::stripe::Stripe {
+                space,
+                ..Default::default()
+            }))
+        };
+
+        assert_eq!(
+            surface(Space::Object).color_at_hit(&object, &world),
+            color::white()
+        );
+        assert_eq!(
+            surface(Space::World).color_at_hit(&object, &world),
+            color::black()
+        );
+    }
+
     mod lighting {
         use crate::{
             math::{matrix::InvertibleMatrix, vector::Vec3d},
-            scene::{light::PointLight, pattern::stripe::Stripe},
+            scene::{
+                light::{Falloff, PointLight},
+                pattern::stripe::Stripe,
+            },
         };
 
         use super::*;
@@ -140,6 +328,7 @@ mod tests {
             let light = PointLight {
                 position: Point3d::new(0.0, 0.0, -10.0),
                 intensity: Color::new(1.0, 1.0, 1.0),
+                falloff: Falloff::None,
             };
 
             let result = lighting(
@@ -149,11 +338,37 @@ mod tests {
                 &light,
                 &eyev,
                 &normalv,
-                1.0,
+                &color::white(),
             );
             assert_eq!(result, Color::new(1.9, 1.9, 1.9));
         }
 
+        #[test]
+        fn lighting_with_a_light_that_dims_with_distance() {
+            let (m, position) = setup();
+            let eyev = NormalizedVec3d::try_from(Vec3d::new(0.0, 0.0, -1.0)).unwrap();
+            let normalv = NormalizedVec3d::try_from(Vec3d::new(0.0, 0.0, -1.0)).unwrap();
+            let light = PointLight {
+                position: Point3d::new(0.0, 0.0, -10.0),
+                intensity: Color::new(1.0, 1.0, 1.0),
+                falloff: Falloff::InverseSquare { radius: 5.0 },
+            };
+
+            let result = lighting(
+                &m,
+                &position,
+                &m.surface.color_at(&position),
+                &light,
+                &eyev,
+                &normalv,
+                &color::white(),
+            );
+            color::test_utils::assert_colors_approx_equal(
+                &result,
+                &Color::new(0.475, 0.475, 0.475),
+            );
+        }
+
         #[test]
         fn lighting_with_eye_between_light_and_surface_eye_offset_45_degrees() {
             let (m, position) = setup();
@@ -163,6 +378,7 @@ mod tests {
             let light = PointLight {
                 position: Point3d::new(0.0, 0.0, -10.0),
                 intensity: Color::new(1.0, 1.0, 1.0),
+                falloff: Falloff::None,
             };
 
             let result = lighting(
@@ -172,7 +388,7 @@ mod tests {
                 &light,
                 &eyev,
                 &normalv,
-                1.0,
+                &color::white(),
             );
             assert_eq!(result, Color::new(1.0, 1.0, 1.0));
         }
@@ -185,6 +401,7 @@ mod tests {
             let light = PointLight {
                 position: Point3d::new(0.0, 10.0, -10.0),
                 intensity: Color::new(1.0, 1.0, 1.0),
+                falloff: Falloff::None,
             };
 
             let result = lighting(
@@ -194,7 +411,7 @@ mod tests {
                 &light,
                 &eyev,
                 &normalv,
-                1.0,
+                &color::white(),
             );
             color::test_utils::assert_colors_approx_equal(
                 &result,
@@ -211,6 +428,7 @@ mod tests {
             let light = PointLight {
                 position: Point3d::new(0.0, 10.0, -10.0),
                 intensity: Color::new(1.0, 1.0, 1.0),
+                falloff: Falloff::None,
             };
 
             let result = lighting(
@@ -220,7 +438,7 @@ mod tests {
                 &light,
                 &eyev,
                 &normalv,
-                1.0,
+                &color::white(),
             );
             color::test_utils::assert_colors_approx_equal(
                 &result,
@@ -236,6 +454,7 @@ mod tests {
             let light = PointLight {
                 position: Point3d::new(0.0, 0.0, 10.0),
                 intensity: Color::new(1.0, 1.0, 1.0),
+                falloff: Falloff::None,
             };
 
             let result = lighting(
@@ -245,7 +464,7 @@ mod tests {
                 &light,
                 &eyev,
                 &normalv,
-                1.0,
+                &color::white(),
             );
             assert_eq!(result, Color::new(0.1, 0.1, 0.1));
         }
@@ -258,6 +477,7 @@ mod tests {
             let light = PointLight {
                 position: Point3d::new(0.0, 0.0, -10.0),
                 intensity: Color::new(1.0, 1.0, 1.0),
+                falloff: Falloff::None,
             };
 
             let result = lighting(
@@ -267,7 +487,7 @@ mod tests {
                 &light,
                 &eyev,
                 &normalv,
-                0.0,
+                &color::black(),
             );
             assert_eq!(result, Color::new(0.1, 0.1, 0.1));
         }
@@ -279,6 +499,7 @@ mod tests {
                     a: color::white(),
                     b: color::black(),
                     transform: InvertibleMatrix::identity(),
+                    ..Default::default()
                 })),
                 ambient: 1.0,
                 diffuse: 0.0,
@@ -290,6 +511,7 @@ mod tests {
             let light = PointLight {
                 position: Point3d::new(0.0, 0.0, -10.0),
                 intensity: color::white(),
+                falloff: Falloff::None,
             };
 
             let p1 = Point3d::new(0.9, 0.0, 0.0);
@@ -300,7 +522,7 @@ mod tests {
                 &light,
                 &eyev,
                 &normalv,
-                1.0,
+                &color::white(),
             );
             let p2 = Point3d::new(1.1, 0.0, 0.0);
             let c2 = lighting(
@@ -310,11 +532,148 @@ mod tests {
                 &light,
                 &eyev,
                 &normalv,
-                1.0,
+                &color::white(),
             );
 
             assert_eq!(c1, color::white());
             assert_eq!(c2, color::black());
         }
     }
+
+    mod microfacet {
+        use crate::{math::vector::Vec3d, scene::light::Falloff};
+
+        use super::*;
+
+        fn material(base: Color, roughness: f64, metalness: f64) -> Material {
+            Material {
+                surface: Surface::Color(base),
+                model: ShadingModel::Microfacet(Microfacet {
+                    metalness,
+                    specular_tint: 0.0,
+                }),
+                roughness,
+                ..Default::default()
+            }
+        }
+
+        fn light_head_on(m: &Material, shadow_attenuation: &Color) -> Color {
+            let position = Point3d::new(0.0, 0.0, 0.0);
+            let v = NormalizedVec3d::try_from(Vec3d::new(0.0, 0.0, -1.0)).unwrap();
+            let light = PointLight {
+                position: Point3d::new(0.0, 0.0, -10.0),
+                intensity: color::white(),
+                falloff: Falloff::None,
+            };
+
+            lighting(
+                m,
+                &position,
+                &m.surface.color_at(&position),
+                &light,
+                &v,
+                &v,
+                shadow_attenuation,
+            )
+        }
+
+        #[test]
+        fn the_default_model_is_phong() {
+            assert_eq!(Material::default().model, ShadingModel::Phong);
+        }
+
+        #[test]
+        fn a_rough_dielectric_lit_head_on() {
+            let m = material(color::white(), 1.0, 0.0);
+
+            color::test_utils::assert_colors_approx_equal(
+                &light_head_on(&m, &color::white()),
+                &Color::new(1.07, 1.07, 1.07),
+            );
+        }
+
+        #[test]
+        fn a_metal_has_no_diffuse_color_and_a_tinted_highlight() {
+            let m = material(color::red(), 0.5, 1.0);
+            let result = light_head_on(&m, &color::white());
+
+            assert!(result.r() > 0.1);
+            assert_eq!(result.g(), 0.0);
+            assert_eq!(result.b(), 0.0);
+        }
+
+        #[test]
+        fn a_surface_in_shadow_only_has_its_ambient_term() {
+            let m = material(color::white(), 0.5, 0.0);
+
+            assert_eq!(
+                light_head_on(&m, &color::black()),
+                Color::new(0.1, 0.1, 0.1)
+            );
+        }
+
+        #[test]
+        fn smoother_surfaces_have_brighter_highlights_in_the_mirror_direction() {
+            let position = Point3d::new(0.0, 0.0, 0.0);
+            let t = std::f64::consts::SQRT_2 / 2.0;
+            let eyev = NormalizedVec3d::try_from(Vec3d::new(0.0, t, -t)).unwrap();
+            let normalv = NormalizedVec3d::try_from(Vec3d::new(0.0, 0.0, -1.0)).unwrap();
+            let light = PointLight {
+                position: Point3d::new(0.0, -10.0, -10.0),
+                intensity: color::white(),
+                falloff: Falloff::None,
+            };
+            let shade = |roughness| {
+                let m = material(color::white(), roughness, 0.0);
+                lighting(
+                    &m,
+                    &position,
+                    &color::white(),
+                    &light,
+                    &eyev,
+                    &normalv,
+                    &color::white(),
+                )
+            };
+
+            assert!(shade(0.2).r() > shade(0.8).r());
+        }
+
+        #[test]
+        fn a_light_exactly_edge_on_adds_nothing() {
+            let m = material(color::white(), 0.5, 0.0);
+            let position = Point3d::new(0.0, 0.0, 0.0);
+            let v = NormalizedVec3d::try_from(Vec3d::new(0.0, 1.0, 0.0)).unwrap();
+            let light = PointLight {
+                position: Point3d::new(10.0, 0.0, 0.0),
+                intensity: color::white(),
+                falloff: Falloff::None,
+            };
+
+            let result = lighting(
+                &m,
+                &position,
+                &color::white(),
+                &light,
+                &v,
+                &v,
+                &color::white(),
+            );
+
+            assert_eq!(result, Color::new(0.1, 0.1, 0.1));
+        }
+
+        #[test]
+        fn the_specular_tint_colors_a_dielectric_highlight() {
+            let mut m = material(color::red(), 0.2, 0.0);
+            let plain = light_head_on(&m, &color::white());
+            if let ShadingModel::Microfacet(f) = &mut m.model {
+                f.specular_tint = 1.0;
+            }
+            let tinted = light_head_on(&m, &color::white());
+
+            assert_eq!(tinted.r(), plain.r());
+            assert!(tinted.g() < plain.g());
+        }
+    }
 }
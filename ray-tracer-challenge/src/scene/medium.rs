@@ -0,0 +1,224 @@
+use std::iter;
+
+use crate::{draw::color::Color, math::point::Point3d};
+
+use super::{
+    intersect::{self, Intersections},
+    object::Object,
+    ray::Ray,
+};
+
+/// Haze filling the whole world that hides things more the farther away they are
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fog {
+    /// What the fog looks like where it hides everything behind it
+    pub color: Color,
+    pub density: f64,
+}
+
+impl Fog {
+    /// The fraction of light that makes it through `distance` of fog
+    pub fn transmittance(&self, distance: f64) -> f64 {
+        transmittance(self.density, distance)
+    }
+}
+
+/// A constant-density medium, like smoke or murky water, filling the inside of a closed object
+pub struct Volume {
+    boundary: Box<dyn Object>,
+    pub density: f64,
+    /// What the medium looks like where it hides everything behind it
+    pub color: Color,
+}
+
+impl Volume {
+    /// Fails if the boundary can't tell which points are inside it, e.g. an open cylinder
+    pub fn new(
+        boundary: impl Object + 'static,
+        density: f64,
+        color: Color,
+    ) -> Result<Self, String> {
+        if boundary
+            .contains(&Point3d::new(0.0, 0.0, 0.0), 0.0)
+            .is_none()
+        {
+            return Err(String::from(
+                "A volume's boundary must enclose a region of space",
+            ));
+        }
+
+        Ok(Volume {
+            boundary: Box::new(boundary),
+            density,
+            color,
+        })
+    }
+
+    /// How far the ray travels inside the volume before reaching `t_max`, using `xs` as scratch
+    /// space and leaving anything already in it untouched
+    pub fn distance_inside<'a>(&'a self, ray: &Ray, t_max: f64, xs: &mut Intersections<'a>) -> f64 {
+        let start = xs.len();
+        self.boundary.intersect_into(ray, xs);
+        intersect::sort(&mut xs[start..]);
+        let crossings = xs[start..].iter().map(|i| i.t());
+        let ts: Vec<f64> = iter::once(0.0)
+            .chain(crossings.filter(|&t| t > 0.0 && t < t_max))
+            .chain(iter::once(t_max))
+            .collect();
+        xs.truncate(start);
+
+        // Check each stretch between boundary crossings on its own rather than toggling at every
+        // crossing, so grazing hits and shared faces can't leave the count inside out
+        let inside: f64 = ts
+            .windows(2)
+            .filter(|w| {
+                let probe = if w[1].is_finite() {
+                    (w[0] + w[1]) / 2.0
+                } else {
+                    w[0] + 1.0
+                };
+                self.boundary.contains(&ray.position(probe), ray.time) == Some(true)
+            })
+            .map(|w| w[1] - w[0])
+            .sum();
+
+        inside * ray.direction.mag()
+    }
+
+    /// The fraction of light along the ray that makes it through the volume before `t_max`
+    pub fn transmittance<'a>(&'a self, ray: &Ray, t_max: f64, xs: &mut Intersections<'a>) -> f64 {
+        transmittance(self.density, self.distance_inside(ray, t_max, xs))
+    }
+}
+
+/// Beer–Lambert falloff, treating an empty medium as clear even over infinite distances
+fn transmittance(density: f64, distance: f64) -> f64 {
+    if density == 0.0 {
+        1.0
+    } else {
+        f64::exp(-density * distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        draw::color,
+        math::{matrix::InvertibleMatrix, vector::Vec3d},
+        scene::{
+            object::{cylinder::Cylinder, sphere::Sphere, transformed::Transformed},
+            transformation,
+        },
+    };
+
+    use super::*;
+
+    fn unit_volume() -> Volume {
+        Volume::new(Sphere::unit(), 1.0, color::white()).unwrap()
+    }
+
+    #[test]
+    fn fog_lets_everything_through_at_no_distance() {
+        let fog = Fog {
+            color: color::white(),
+            density: 0.5,
+        };
+
+        assert_eq!(fog.transmittance(0.0), 1.0);
+        assert_eq!(fog.transmittance(2.0), f64::exp(-1.0));
+        assert_eq!(fog.transmittance(f64::INFINITY), 0.0);
+    }
+
+    #[test]
+    fn empty_fog_is_clear_at_any_distance() {
+        let fog = Fog {
+            color: color::white(),
+            density: 0.0,
+        };
+
+        assert_eq!(fog.transmittance(f64::INFINITY), 1.0);
+    }
+
+    #[test]
+    fn a_volume_needs_a_closed_boundary() {
+        let tube = Cylinder {
+            minimum: Some(-1.0),
+            maximum: Some(1.0),
+            ..Default::default()
+        };
+        let open = Volume::new(tube, 1.0, color::white());
+
+        assert!(open.is_err());
+    }
+
+    #[test]
+    fn a_ray_passing_through_a_volume() {
+        let v = unit_volume();
+        let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+        assert_eq!(
+            v.distance_inside(&r, f64::INFINITY, &mut Intersections::new()),
+            2.0
+        );
+        assert_eq!(
+            v.transmittance(&r, f64::INFINITY, &mut Intersections::new()),
+            f64::exp(-2.0)
+        );
+    }
+
+    #[test]
+    fn a_ray_stopping_inside_a_volume() {
+        let v = unit_volume();
+        let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+        assert_eq!(v.distance_inside(&r, 4.5, &mut Intersections::new()), 0.5);
+    }
+
+    #[test]
+    fn a_ray_starting_inside_a_volume() {
+        let v = unit_volume();
+        let r = Ray::new(Point3d::new(0.0, 0.0, 0.0), Vec3d::new(0.0, 0.0, 1.0));
+
+        assert_eq!(
+            v.distance_inside(&r, f64::INFINITY, &mut Intersections::new()),
+            1.0
+        );
+    }
+
+    #[test]
+    fn a_ray_missing_a_volume() {
+        let v = unit_volume();
+        let r = Ray::new(Point3d::new(0.0, 2.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+        assert_eq!(
+            v.transmittance(&r, f64::INFINITY, &mut Intersections::new()),
+            1.0
+        );
+    }
+
+    #[test]
+    fn distance_inside_is_measured_in_world_units() {
+        let v = unit_volume();
+        let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 2.0));
+
+        assert_eq!(
+            v.distance_inside(&r, f64::INFINITY, &mut Intersections::new()),
+            2.0
+        );
+    }
+
+    #[test]
+    fn a_volume_inside_a_transformed_boundary() {
+        let boundary = Transformed::new(
+            Sphere::unit(),
+            InvertibleMatrix::try_from(transformation::scaling(2.0, 2.0, 2.0)).unwrap(),
+        );
+        let v = Volume::new(boundary, 1.0, color::white()).unwrap();
+        let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+        assert_eq!(
+            v.distance_inside(&r, f64::INFINITY, &mut Intersections::new()),
+            4.0
+        );
+    }
+}
@@ -5,12 +5,17 @@ use self::{
     world::World,
 };
 
+pub mod animation;
 pub mod camera;
+pub mod environment;
+pub mod graph;
 pub mod intersect;
 pub mod light;
 pub mod material;
+pub mod medium;
 pub mod object;
 pub mod pattern;
+pub mod photon_map;
 pub mod ray;
 pub mod transformation;
 pub mod world;
@@ -24,4 +29,8 @@ impl Scene {
     pub fn render(&self, opts: &RenderOpts) -> Canvas {
         self.camera.render(&self.world, opts)
     }
+
+    pub fn render_into(&self, opts: &RenderOpts, canvas: &mut Canvas) {
+        self.camera.render_into(&self.world, opts, canvas)
+    }
 }
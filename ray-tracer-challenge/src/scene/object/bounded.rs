@@ -1,12 +1,15 @@
 use std::{borrow::Borrow, ops::Deref};
 
 use crate::{
-    draw::color::Color,
-    math::{point::Point3d, vector::NormalizedVec3d},
-    scene::{intersect::Intersection, material::Material, ray::Ray},
+    math::point::Point3d,
+    scene::{
+        intersect::{Intersection, Intersections},
+        material::Material,
+        ray::Ray,
+    },
 };
 
-use super::Object;
+use super::{Object, PhysicalObject};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Bounds {
@@ -15,6 +18,20 @@ pub struct Bounds {
 }
 
 impl Bounds {
+    /// Bounds that contain nothing, so that no ray ever hits them
+    pub fn empty() -> Self {
+        Bounds {
+            minimum: Point3d::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            maximum: Point3d::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.minimum.x() > self.maximum.x()
+            || self.minimum.y() > self.maximum.y()
+            || self.minimum.z() > self.maximum.z()
+    }
+
     pub fn enumerate(&self) -> [Point3d; 8] {
         let min = &self.minimum;
         let max = &self.maximum;
@@ -60,11 +77,13 @@ impl Bounds {
     pub fn from_bounds<B: Borrow<Bounds>>(bounds: &[B]) -> Self {
         let points: Vec<_> = bounds
             .iter()
+            .map(|b| b.borrow())
+            .filter(|b| !b.is_empty())
             .flat_map(|b| {
                 let Bounds {
                     minimum: min,
                     maximum: max,
-                } = b.borrow();
+                } = b;
                 [min, max].into_iter()
             })
             .collect();
@@ -73,6 +92,23 @@ impl Bounds {
             maximum: Point3d::new(0.0, 0.0, 0.0),
         })
     }
+
+    /// The region inside both bounds, if they meet at all
+    pub fn overlap(&self, other: &Bounds) -> Option<Bounds> {
+        let minimum = Point3d::new(
+            self.minimum.x().max(other.minimum.x()),
+            self.minimum.y().max(other.minimum.y()),
+            self.minimum.z().max(other.minimum.z()),
+        );
+        let maximum = Point3d::new(
+            self.maximum.x().min(other.maximum.x()),
+            self.maximum.y().min(other.maximum.y()),
+            self.maximum.z().min(other.maximum.z()),
+        );
+
+        (minimum.x() <= maximum.x() && minimum.y() <= maximum.y() && minimum.z() <= maximum.z())
+            .then_some(Bounds { minimum, maximum })
+    }
 }
 
 impl Default for Bounds {
@@ -98,6 +134,10 @@ impl<T: Object> Bounded<T> {
     }
 
     fn test(&self, ray: &Ray) -> bool {
+        if self.bounds.is_empty() {
+            return false;
+        }
+
         let (xtmin, xtmax) = check_axis(
             self.bounds.minimum.x(),
             self.bounds.maximum.x(),
@@ -139,18 +179,37 @@ fn check_axis(min: f64, max: f64, origin: f64, speed: f64) -> (f64, f64) {
 }
 
 impl<T: Object> Object for Bounded<T> {
-    fn material(&self) -> &Material {
+    fn material(&self) -> Option<&Material> {
         self.child.material()
     }
 
-    fn intersect(&self, ray: &Ray) -> Vec<Intersection<&dyn Object, Color, NormalizedVec3d>> {
+    fn intersect_into<'a>(&'a self, ray: &Ray, xs: &mut Intersections<'a>) {
         if self.test(ray) {
-            self.child.intersect(ray)
+            self.child.intersect_into(ray, xs)
+        }
+    }
+
+    fn intersect_closest<'a>(
+        &'a self,
+        ray: &Ray,
+        t_max: f64,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Intersection<&'a dyn PhysicalObject>> {
+        if self.test(ray) {
+            self.child.intersect_closest(ray, t_max, xs)
         } else {
-            Vec::new()
+            None
         }
     }
 
+    fn occluded<'a>(&'a self, ray: &Ray, t_max: f64, xs: &mut Intersections<'a>) -> bool {
+        self.test(ray) && self.child.occluded(ray, t_max, xs)
+    }
+
+    fn contains(&self, point: &Point3d, time: f64) -> Option<bool> {
+        self.child.contains(point, time)
+    }
+
     fn bounds(&self) -> Bounds {
         self.bounds.clone()
     }
@@ -182,9 +241,56 @@ mod bounds_tests {
         assert_eq!(points, expected);
     }
 
+    #[test]
+    fn overlap_of_two_bounds() {
+        let a = Bounds {
+            minimum: Point3d::new(-1.0, -1.0, -1.0),
+            maximum: Point3d::new(1.0, 1.0, 1.0),
+        };
+        let b = Bounds {
+            minimum: Point3d::new(0.0, -2.0, 0.5),
+            maximum: Point3d::new(2.0, 0.5, 3.0),
+        };
+
+        assert_eq!(
+            a.overlap(&b),
+            Some(Bounds {
+                minimum: Point3d::new(0.0, -1.0, 0.5),
+                maximum: Point3d::new(1.0, 0.5, 1.0),
+            })
+        );
+    }
+
+    #[test]
+    fn overlap_of_disjoint_bounds() {
+        let a = Bounds {
+            minimum: Point3d::new(-1.0, -1.0, -1.0),
+            maximum: Point3d::new(1.0, 1.0, 1.0),
+        };
+        let b = Bounds {
+            minimum: Point3d::new(2.0, -1.0, -1.0),
+            maximum: Point3d::new(3.0, 1.0, 1.0),
+        };
+
+        assert_eq!(a.overlap(&b), None);
+    }
+
+    #[test]
+    fn empty_bounds_are_ignored_when_combining_bounds() {
+        let b = Bounds {
+            minimum: Point3d::new(-1.0, -1.0, -1.0),
+            maximum: Point3d::new(1.0, 1.0, 1.0),
+        };
+
+        assert!(Bounds::empty().is_empty());
+        assert!(!b.is_empty());
+        assert_eq!(Bounds::from_bounds(&[&b, &Bounds::empty()]), b);
+        assert_eq!(b.overlap(&Bounds::empty()), None);
+    }
+
     #[test]
     fn trying_to_create_bounds_from_no_points() {
-        assert_eq!(None, Bounds::from_points::<Point3d>(&vec![]));
+        assert_eq!(None, Bounds::from_points::<Point3d>(&[]));
     }
 
     #[test]
@@ -194,7 +300,7 @@ mod bounds_tests {
                 minimum: Point3d::new(0.0, 1.0, 2.0),
                 maximum: Point3d::new(0.0, 1.0, 2.0)
             }),
-            Bounds::from_points(&vec![Point3d::new(0.0, 1.0, 2.0)])
+            Bounds::from_points(&[Point3d::new(0.0, 1.0, 2.0)])
         );
     }
 
@@ -250,8 +356,10 @@ mod tests {
         assert_eq!(bounded_intersect.len(), 1);
         assert_eq!(bounded_intersect.len(), child_intersect.len());
         assert!(bounded_intersect[0] == child_intersect[0]);
-        assert_eq!(bounded_intersect[0].color, child_intersect[0].color);
-        assert_eq!(bounded_intersect[0].normal, child_intersect[0].normal);
+        assert_eq!(
+            bounded_intersect.shading(&bounded_intersect[0], &ray),
+            child_intersect.shading(&child_intersect[0], &ray)
+        );
     }
 
     #[test]
@@ -327,6 +435,22 @@ mod tests {
             a_ray_misses_neg_z_4: (Ray::new(Point3d::new(3.0, 1.9, -5.0), Vec3d::new(0.0, 0.0, 1.0)), false)
         }
 
+        #[test]
+        fn a_ray_never_intersects_empty_bounds() {
+            let shape = MockObject {
+                bounds: Bounds::empty(),
+                ..Default::default()
+            };
+            let bounded = Bounded::new(shape);
+
+            for ray in [
+                Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0)),
+                Ray::new(Point3d::new(0.0, 0.0, 0.0), Vec3d::new(1.0, 1.0, 1.0)),
+            ] {
+                assert!(!bounded.test(&ray));
+            }
+        }
+
         #[test]
         fn a_ray_intersects_a_bounding_box_going_to_infinity() {
             let shape = MockObject {
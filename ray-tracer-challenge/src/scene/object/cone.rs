@@ -1,7 +1,10 @@
 use crate::{
-    draw::color::Color,
     math::{point::Point3d, vector::NormalizedVec3d},
-    scene::{intersect::Intersection, material::Material, ray::Ray},
+    scene::{
+        intersect::{Intersection, Intersections},
+        material::Material,
+        ray::Ray,
+    },
 };
 
 use super::{bounded::Bounds, Object, PhysicalObject};
@@ -11,7 +14,7 @@ const EPSILON: f64 = 1e-8;
 /// A double-napped cone with slope 1 aligned along the y-axis, whose vertex is at the origin
 #[derive(Default)]
 pub struct Cone {
-    pub material: Material,
+    pub material: Option<Material>,
     pub minimum: Option<f64>,
     pub maximum: Option<f64>,
     pub closed: bool,
@@ -34,28 +37,16 @@ impl Cone {
         x.powi(2) + z.powi(2) <= y.abs()
     }
 
-    fn intersects_caps(&self, ray: &Ray) -> Vec<f64> {
+    fn intersects_caps(&self, ray: &Ray) -> [Option<f64>; 2] {
         if !self.closed || ray.direction.y().abs() < EPSILON {
-            Vec::new()
+            [None, None]
         } else {
-            let mut results = Vec::<f64>::new();
-
             // check for intersection with lower end cap
-            if let Some(min) = self.minimum {
-                let t = (min - ray.origin.y()) / ray.direction.y();
-                if self.check_cap(ray, min, t) {
-                    results.push(t);
-                }
-            }
-
-            if let Some(max) = self.maximum {
-                let t = (max - ray.origin.y()) / ray.direction.y();
-                if self.check_cap(ray, max, t) {
-                    results.push(t);
-                }
-            }
-
-            results
+            [self.minimum, self.maximum].map(|cap| {
+                cap.map(|y| (y, (y - ray.origin.y()) / ray.direction.y()))
+                    .filter(|&(y, t)| self.check_cap(ray, y, t))
+                    .map(|(_, t)| t)
+            })
         }
     }
 }
@@ -64,13 +55,15 @@ impl PhysicalObject for Cone {
     fn normal_at(&self, object_point: &Point3d) -> NormalizedVec3d {
         let dist2 = object_point.x().powi(2) + object_point.z().powi(2);
 
-        if self.maximum.map_or(false, |max| {
-            dist2 < max.powi(2) && object_point.y() >= max - EPSILON
-        }) {
+        if self
+            .maximum
+            .is_some_and(|max| dist2 < max.powi(2) && object_point.y() >= max - EPSILON)
+        {
             NormalizedVec3d::new(0.0, 1.0, 0.0).unwrap()
-        } else if self.minimum.map_or(false, |min| {
-            dist2 < min.powi(2) && object_point.y() <= min + EPSILON
-        }) {
+        } else if self
+            .minimum
+            .is_some_and(|min| dist2 < min.powi(2) && object_point.y() <= min + EPSILON)
+        {
             NormalizedVec3d::new(0.0, -1.0, 0.0).unwrap()
         } else {
             let y = f64::sqrt(object_point.x().powi(2) + object_point.z().powi(2));
@@ -78,17 +71,8 @@ impl PhysicalObject for Cone {
             NormalizedVec3d::new(object_point.x(), y, object_point.z()).unwrap()
         }
     }
-}
-
-impl Object for Cone {
-    fn material(&self) -> &Material {
-        &self.material
-    }
 
-    fn intersect(
-        &self,
-        object_ray: &Ray,
-    ) -> Vec<Intersection<&dyn Object, Color, NormalizedVec3d>> {
+    fn intersection_ts(&self, object_ray: &Ray) -> impl Iterator<Item = f64> {
         let a = object_ray.direction.x().powi(2) - object_ray.direction.y().powi(2)
             + object_ray.direction.z().powi(2);
         let b = 2.0 * object_ray.origin.x() * object_ray.direction.x()
@@ -100,42 +84,62 @@ impl Object for Cone {
         let a_is_zero = a.abs() < EPSILON;
         let b_is_zero = b.abs() < EPSILON;
 
-        let mut wall_xs = if a_is_zero && b_is_zero {
-            Vec::new()
+        let wall_xs = if a_is_zero && b_is_zero {
+            [None, None]
         } else if a_is_zero && !b_is_zero {
             let t = -c / (2.0 * b);
-            vec![t]
+            [Some(t), None]
         } else {
             let discriminant = b.powi(2) - 4.0 * a * c;
 
             if discriminant < 0.0 {
-                Vec::new()
+                [None, None]
             } else {
                 let t0 = (-b - f64::sqrt(discriminant)) / (2.0 * a);
                 let t1 = (-b + f64::sqrt(discriminant)) / (2.0 * a);
 
-                let mut results = Vec::<f64>::new();
-                let y0 = object_ray.origin.y() + t0 * object_ray.direction.y();
-                if self.in_bounds(y0) {
-                    results.push(t0)
-                }
-                let y1 = object_ray.origin.y() + t1 * object_ray.direction.y();
-                if self.in_bounds(y1) {
-                    results.push(t1)
-                }
-
-                results
+                [t0, t1].map(|t| {
+                    let y = object_ray.origin.y() + t * object_ray.direction.y();
+                    Some(t).filter(|_| self.in_bounds(y))
+                })
             }
         };
 
-        let mut cap_xs = self.intersects_caps(object_ray);
+        let cap_xs = self.intersects_caps(object_ray);
+
+        wall_xs.into_iter().chain(cap_xs).flatten()
+    }
+}
+
+impl Object for Cone {
+    fn material(&self) -> Option<&Material> {
+        self.material.as_ref()
+    }
+
+    fn intersect_into<'a>(&'a self, object_ray: &Ray, xs: &mut Intersections<'a>) {
+        super::basic_intersect_into(self, object_ray, xs)
+    }
+
+    fn intersect_closest<'a>(
+        &'a self,
+        object_ray: &Ray,
+        t_max: f64,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Intersection<&'a dyn PhysicalObject>> {
+        super::basic_intersect_closest(self, object_ray, t_max, xs)
+    }
 
-        wall_xs.append(&mut cap_xs);
+    fn occluded<'a>(&'a self, object_ray: &Ray, t_max: f64, _xs: &mut Intersections<'a>) -> bool {
+        super::basic_occluded(self, object_ray, t_max)
+    }
 
-        wall_xs
-            .into_iter()
-            .map(|t| super::build_basic_intersection(object_ray, t, self))
-            .collect()
+    /// Only closed or infinite cones enclose a region of space
+    fn contains(&self, object_point: &Point3d, _time: f64) -> Option<bool> {
+        let encloses = self.closed || (self.minimum.is_none() && self.maximum.is_none());
+        encloses.then(|| {
+            object_point.x().powi(2) + object_point.z().powi(2) < object_point.y().powi(2)
+                && self.in_bounds(object_point.y())
+        })
     }
 
     fn bounds(&self) -> Bounds {
@@ -233,12 +237,12 @@ mod tests {
 
             let xs = cone.intersect(&r);
 
-            for x in xs {
+            for x in xs.iter() {
                 let p = r.position(x.t());
                 let n = cone.normal_at(&p);
-                let c = cone.material().surface.color_at(&p);
-                assert_eq!(x.normal, n);
-                assert_eq!(x.color, c);
+                let c = xs.material(x).surface.color_at(&p);
+                assert_eq!(xs.normal_at(x, &r), n);
+                assert_eq!(xs.color_at(x, &r), c);
             }
         }
     }
@@ -307,4 +311,43 @@ mod tests {
             );
         }
     }
+
+    mod contains {
+        use super::*;
+
+        #[test]
+        fn a_closed_cone_contains_points_within_its_slope() {
+            let cone = Cone {
+                minimum: Some(-1.0),
+                maximum: Some(2.0),
+                closed: true,
+                ..Default::default()
+            };
+
+            assert_eq!(cone.contains(&Point3d::new(1.0, 1.5, 0.0), 0.0), Some(true));
+            assert_eq!(
+                cone.contains(&Point3d::new(0.5, -0.6, 0.0), 0.0),
+                Some(true)
+            );
+            assert_eq!(
+                cone.contains(&Point3d::new(1.0, 0.5, 0.0), 0.0),
+                Some(false)
+            );
+            assert_eq!(
+                cone.contains(&Point3d::new(0.0, 2.5, 0.0), 0.0),
+                Some(false)
+            );
+        }
+
+        #[test]
+        fn an_open_truncated_cone_encloses_nothing() {
+            let cone = Cone {
+                minimum: Some(-1.0),
+                maximum: Some(2.0),
+                ..Default::default()
+            };
+
+            assert_eq!(cone.contains(&Point3d::new(0.0, 1.0, 0.0), 0.0), None);
+        }
+    }
 }
@@ -1,11 +1,18 @@
 use crate::{
-    draw::color::Color,
-    math::vector::NormalizedVec3d,
-    scene::{intersect::Intersection, material::Material, ray::Ray},
+    math::point::Point3d,
+    scene::{
+        intersect::{self, Intersection, Intersections},
+        material::Material,
+        ray::Ray,
+    },
 };
 
-use super::{bounded::Bounds, Object};
+use super::{
+    bounded::{Bounded, Bounds},
+    Object,
+};
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum CsgOperation {
     Union,
     Intersection,
@@ -28,15 +35,12 @@ impl CsgOperation {
     }
 }
 
-pub struct Csg<T> {
-    pub left: T,
-    pub right: T,
+pub struct Csg {
+    pub left: Box<dyn Object>,
+    pub right: Box<dyn Object>,
     pub operation: CsgOperation,
-}
-
-struct SidedIntersection<T, C, N> {
-    pub i: Intersection<T, C, N>,
-    pub side: Side,
+    /// Inherited by either side without a material of its own
+    pub material: Option<Material>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -45,68 +49,170 @@ enum Side {
     Right,
 }
 
-impl<T> Csg<T> {
-    fn filter_intersections<U, C, N>(
+impl Csg {
+    pub fn new(
+        operation: CsgOperation,
+        left: impl Object + 'static,
+        right: impl Object + 'static,
+    ) -> Self {
+        Csg {
+            left: Box::new(left),
+            right: Box::new(right),
+            operation,
+            material: None,
+        }
+    }
+
+    /// The union of any number of objects, which must be at least two
+    pub fn union_of(children: Vec<Box<dyn Object>>) -> Result<Self, String> {
+        Csg::combine_all(CsgOperation::Union, children)
+    }
+
+    /// The intersection of any number of objects, which must be at least two
+    pub fn intersection_of(children: Vec<Box<dyn Object>>) -> Result<Self, String> {
+        Csg::combine_all(CsgOperation::Intersection, children)
+    }
+
+    /// Combines the children into a balanced tree, bounding each nested node so whole subtrees
+    /// are skipped by rays that miss them
+    fn combine_all(
+        operation: CsgOperation,
+        mut children: Vec<Box<dyn Object>>,
+    ) -> Result<Self, String> {
+        if children.len() < 2 {
+            return Err(format!(
+                "Combining objects with CSG needs at least two, got {}",
+                children.len()
+            ));
+        }
+
+        fn nest(operation: &CsgOperation, mut children: Vec<Box<dyn Object>>) -> Box<dyn Object> {
+            if children.len() == 1 {
+                return children.pop().unwrap();
+            }
+            let right = children.split_off(children.len() / 2);
+            Box::new(Bounded::new(Csg {
+                left: nest(operation, children),
+                right: nest(operation, right),
+                operation: operation.clone(),
+                material: None,
+            }))
+        }
+
+        let right = children.split_off(children.len() / 2);
+        Ok(Csg {
+            left: nest(&operation, children),
+            right: nest(&operation, right),
+            operation,
+            material: None,
+        })
+    }
+
+    /// Merges the left (`xs[..mid]`) and right (`xs[mid..]`) intersections, each already sorted,
+    /// in place, moving those allowed by the operation to the front. Returns how many were kept.
+    ///
+    /// `in_other` says whether the hit at `t` on one side is inside the other side, for sides
+    /// that can tell; otherwise that is worked out by counting the other side's hits so far.
+    fn filter_intersections<U>(
         &self,
-        xs: Vec<SidedIntersection<U, C, N>>,
-    ) -> Vec<Intersection<U, C, N>> {
+        xs: &mut [Intersection<U>],
+        mid: usize,
+        in_other: impl Fn(&Side, f64) -> Option<bool>,
+    ) -> usize {
         let mut is_in_left = false;
         let mut is_in_right = false;
-        xs.into_iter()
-            .filter_map(|x| {
-                let is_allowed =
-                    self.operation
-                        .intersection_allowed(&x.side, is_in_left, is_in_right);
 
-                match &x.side {
-                    Side::Left => is_in_left = !is_in_left,
-                    Side::Right => is_in_right = !is_in_right,
-                };
+        // Everything before `kept` is output; `xs[next..right]` is the rest of the left side
+        let mut kept = 0;
+        let mut next = 0;
+        let mut right = mid;
+        while next < xs.len() {
+            let side = if right < xs.len() && (next == right || xs[right].t() < xs[next].t()) {
+                xs[next..=right].rotate_right(1);
+                right += 1;
+                Side::Right
+            } else {
+                Side::Left
+            };
 
-                if is_allowed {
-                    Some(x.i)
-                } else {
-                    None
+            let t = xs[next].t();
+            let is_allowed = match side {
+                Side::Left => {
+                    let is_in_right = in_other(&side, t).unwrap_or(is_in_right);
+                    self.operation
+                        .intersection_allowed(&side, is_in_left, is_in_right)
+                }
+                Side::Right => {
+                    let is_in_left = in_other(&side, t).unwrap_or(is_in_left);
+                    self.operation
+                        .intersection_allowed(&side, is_in_left, is_in_right)
                 }
-            })
-            .collect()
+            };
+
+            match side {
+                Side::Left => is_in_left = !is_in_left,
+                Side::Right => is_in_right = !is_in_right,
+            };
+
+            if is_allowed {
+                xs.swap(kept, next);
+                kept += 1;
+            }
+            next += 1;
+        }
+
+        kept
     }
 }
 
-impl<T: Object> Object for Csg<T> {
-    fn material(&self) -> &Material {
-        unimplemented!()
-    }
-
-    fn intersect(&self, ray: &Ray) -> Vec<Intersection<&dyn Object, Color, NormalizedVec3d>> {
-        let left_xs = self
-            .left
-            .intersect(ray)
-            .into_iter()
-            .map(|i| SidedIntersection {
-                i,
-                side: Side::Left,
-            })
-            .collect::<Vec<_>>();
-        let mut right_xs = self
-            .right
-            .intersect(ray)
-            .into_iter()
-            .map(|i| SidedIntersection {
-                i,
-                side: Side::Right,
-            })
-            .collect::<Vec<_>>();
-
-        let mut all_xs = left_xs;
-        all_xs.append(&mut right_xs);
-        all_xs.sort_by(|a, b| a.i.t().partial_cmp(&b.i.t()).unwrap());
-
-        self.filter_intersections(all_xs)
+impl Object for Csg {
+    fn material(&self) -> Option<&Material> {
+        self.material.as_ref()
+    }
+
+    fn intersect_into<'a>(&'a self, ray: &Ray, xs: &mut Intersections<'a>) {
+        let start = xs.len();
+        let previous = xs.enter_solid(self);
+        let mid = xs.with_material(self.material.as_ref(), |xs| {
+            self.left.intersect_into(ray, xs);
+            let mid = xs.len();
+            self.right.intersect_into(ray, xs);
+            mid
+        });
+        xs.exit_frame(previous);
+
+        intersect::sort(&mut xs[start..mid]);
+        intersect::sort(&mut xs[mid..]);
+
+        let kept = self.filter_intersections(&mut xs[start..], mid - start, |side, t| {
+            let point = ray.position(t);
+            match side {
+                Side::Left => self.right.contains(&point, ray.time),
+                Side::Right => self.left.contains(&point, ray.time),
+            }
+        });
+        xs.truncate(start + kept);
+    }
+
+    fn contains(&self, point: &Point3d, time: f64) -> Option<bool> {
+        let in_left = self.left.contains(point, time)?;
+        let in_right = self.right.contains(point, time)?;
+        Some(match self.operation {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        })
     }
 
     fn bounds(&self) -> Bounds {
-        Bounds::from_bounds(&[self.left.bounds(), self.right.bounds()])
+        let left = self.left.bounds();
+        match self.operation {
+            CsgOperation::Union => Bounds::from_bounds(&[left, self.right.bounds()]),
+            CsgOperation::Intersection => left
+                .overlap(&self.right.bounds())
+                .unwrap_or_else(Bounds::empty),
+            CsgOperation::Difference => left,
+        }
     }
 }
 
@@ -162,19 +268,69 @@ mod csg_tests {
     use crate::{
         math::{matrix::InvertibleMatrix, point::Point3d, vector::Vec3d},
         scene::{
-            object::{cube::Cube, sphere::Sphere, transformed::Transformed},
+            intersect as is,
+            object::{
+                bounded::Bounded, cube::Cube, group::Group, sphere::Sphere,
+                transformed::Transformed,
+            },
             transformation,
         },
     };
 
     use super::*;
 
+    fn sphere_at(x: f64, z: f64) -> Box<dyn Object> {
+        Box::new(Transformed::new(
+            Sphere::default(),
+            InvertibleMatrix::try_from(transformation::translation(x, 0.0, z)).unwrap(),
+        ))
+    }
+
+    macro_rules! filter_tests {
+        ($($name:ident: $operation:expr, $expected:expr),*) => {
+            $(
+                #[test]
+                fn $name() {
+                    let c = Csg {
+                        left: Box::new(Sphere::default()),
+                        right: Box::new(Sphere::default()),
+                        operation: $operation,
+                        material: None,
+                    };
+                    let mut xs = vec![
+                        Intersection::new(0.0, "before"),
+                        Intersection::new(1.0, "left"),
+                        Intersection::new(3.0, "left"),
+                        Intersection::new(2.0, "right"),
+                        Intersection::new(4.0, "right"),
+                    ];
+
+                    let kept = c.filter_intersections(&mut xs[1..], 2, |_, _| None);
+                    xs.truncate(1 + kept);
+
+                    let result = xs.iter().map(|i| (i.t(), *i.object())).collect::<Vec<_>>();
+                    assert_eq!(result, $expected);
+                }
+            )*
+        };
+    }
+
+    filter_tests! {
+        filtering_a_union: CsgOperation::Union,
+            vec![(0.0, "before"), (1.0, "left"), (4.0, "right")],
+        filtering_an_intersection: CsgOperation::Intersection,
+            vec![(0.0, "before"), (2.0, "right"), (3.0, "left")],
+        filtering_a_difference: CsgOperation::Difference,
+            vec![(0.0, "before"), (1.0, "left"), (2.0, "right")]
+    }
+
     #[test]
     fn a_ray_misses_a_csg_object() {
-        let c = Csg::<Box<dyn Object>> {
+        let c = Csg {
             left: Box::new(Sphere::default()),
             right: Box::new(Cube::default()),
             operation: CsgOperation::Union,
+            material: None,
         };
         let r = Ray::new(Point3d::new(0.0, 2.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
 
@@ -184,13 +340,14 @@ mod csg_tests {
 
     #[test]
     fn a_ray_hits_a_csg_object() {
-        let c = Csg::<Box<dyn Object>> {
+        let c = Csg {
             left: Box::new(Sphere::default()),
             right: Box::new(Transformed::new(
                 Sphere::default(),
                 InvertibleMatrix::try_from(transformation::translation(0.0, 0.0, 0.5)).unwrap(),
             )),
             operation: CsgOperation::Union,
+            material: None,
         };
         let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
 
@@ -202,23 +359,47 @@ mod csg_tests {
         assert_eq!(xs.len(), 2);
 
         assert!(xs[0] == left_intersections[0]);
-        assert_eq!(xs[0].color, left_intersections[0].color);
-        assert_eq!(xs[0].normal, left_intersections[0].normal);
+        assert_eq!(
+            xs.shading(&xs[0], &r),
+            left_intersections.shading(&left_intersections[0], &r)
+        );
 
         assert!(xs[1] == right_intersections[1]);
-        assert_eq!(xs[1].color, right_intersections[1].color);
-        assert_eq!(xs[1].normal, right_intersections[1].normal);
+        assert_eq!(
+            xs.shading(&xs[1], &r),
+            right_intersections.shading(&right_intersections[1], &r)
+        );
+    }
+
+    #[test]
+    fn sides_without_a_material_inherit_the_csg_material() {
+        let c = Csg {
+            left: Box::new(Sphere::default()),
+            right: Box::new(Transformed::new(
+                Sphere::new(Default::default()),
+                InvertibleMatrix::try_from(transformation::translation(0.0, 0.0, 0.5)).unwrap(),
+            )),
+            operation: CsgOperation::Union,
+            material: Some(Default::default()),
+        };
+        let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+        let xs = c.intersect(&r);
+
+        assert!(xs.material(&xs[0]) == c.material().unwrap());
+        assert!(xs.material(&xs[1]) == c.right.material().unwrap());
     }
 
     #[test]
     fn bounds_cover_both_children() {
-        let c = Csg::<Box<dyn Object>> {
+        let c = Csg {
             left: Box::new(Sphere::default()),
             right: Box::new(Transformed::new(
                 Sphere::default(),
                 InvertibleMatrix::try_from(transformation::translation(0.0, 0.0, 0.5)).unwrap(),
             )),
             operation: CsgOperation::Union,
+            material: None,
         };
 
         assert_eq!(
@@ -229,4 +410,193 @@ mod csg_tests {
             }
         )
     }
+
+    #[test]
+    fn bounds_of_an_intersection_are_the_overlap_of_its_children() {
+        let c = Csg {
+            left: sphere_at(0.0, 0.0),
+            right: sphere_at(0.0, 1.0),
+            operation: CsgOperation::Intersection,
+            material: None,
+        };
+
+        assert_eq!(
+            c.bounds(),
+            Bounds {
+                minimum: Point3d::new(-1.0, -1.0, 0.0),
+                maximum: Point3d::new(1.0, 1.0, 1.0)
+            }
+        )
+    }
+
+    #[test]
+    fn bounds_of_an_intersection_of_disjoint_children_are_empty() {
+        let c = Csg {
+            left: sphere_at(-3.0, 0.0),
+            right: sphere_at(3.0, 0.0),
+            operation: CsgOperation::Intersection,
+            material: None,
+        };
+
+        assert!(c.bounds().is_empty());
+        let moved = Transformed::new(
+            c,
+            InvertibleMatrix::try_from(transformation::translation(0.0, 1.0, 0.0)).unwrap(),
+        );
+        assert!(moved.bounds().is_empty());
+        let bounded = Bounded::new(moved);
+        let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+        assert!(bounded.intersect(&r).is_empty());
+    }
+
+    #[test]
+    fn bounds_of_a_difference_are_those_of_the_left_child() {
+        let c = Csg::new(
+            CsgOperation::Difference,
+            Sphere::default(),
+            Transformed::new(
+                Cube::default(),
+                InvertibleMatrix::try_from(transformation::scaling(3.0, 3.0, 3.0)).unwrap(),
+            ),
+        );
+
+        assert_eq!(c.bounds(), c.left.bounds())
+    }
+
+    #[test]
+    fn a_union_of_many_objects_is_hit_wherever_they_are() {
+        let c = Csg::union_of(vec![
+            sphere_at(-3.0, 0.0),
+            sphere_at(0.0, 0.0),
+            sphere_at(3.0, 0.0),
+        ])
+        .unwrap();
+        let r = Ray::new(Point3d::new(-10.0, 0.0, 0.0), Vec3d::new(1.0, 0.0, 0.0));
+
+        let xs = c.intersect(&r);
+
+        assert_eq!(
+            is::test_utils::to_ts(&xs),
+            vec![6.0, 8.0, 9.0, 11.0, 12.0, 14.0]
+        );
+    }
+
+    #[test]
+    fn an_intersection_of_many_objects_is_the_region_common_to_all() {
+        let c = Csg::intersection_of(vec![
+            sphere_at(-0.5, 0.0),
+            sphere_at(0.0, 0.0),
+            sphere_at(0.5, 0.0),
+        ])
+        .unwrap();
+        let r = Ray::new(Point3d::new(-10.0, 0.0, 0.0), Vec3d::new(1.0, 0.0, 0.0));
+
+        let xs = c.intersect(&r);
+
+        assert_eq!(is::test_utils::to_ts(&xs), vec![9.5, 10.5]);
+        assert_eq!(
+            c.bounds(),
+            Bounds {
+                minimum: Point3d::new(-0.5, -1.0, -1.0),
+                maximum: Point3d::new(0.5, 1.0, 1.0)
+            }
+        );
+    }
+
+    #[test]
+    fn combining_fewer_than_two_objects_is_an_error() {
+        assert!(Csg::union_of(vec![sphere_at(0.0, 0.0)]).is_err());
+        assert!(Csg::intersection_of(vec![]).is_err());
+    }
+
+    #[test]
+    fn sides_that_enclose_a_region_are_checked_by_containment() {
+        // Counting hits would put the slab outside the overlapping spheres
+        let spheres: Group<Box<dyn Object>> =
+            Group::new(vec![sphere_at(-0.5, 0.0), sphere_at(0.5, 0.0)]);
+        let slab = Transformed::new(
+            Cube::default(),
+            InvertibleMatrix::try_from(transformation::scaling(0.1, 2.0, 2.0)).unwrap(),
+        );
+        let c = Csg::new(CsgOperation::Intersection, spheres, slab);
+        let r = Ray::new(Point3d::new(-10.0, 0.0, 0.0), Vec3d::new(1.0, 0.0, 0.0));
+
+        let xs = c.intersect(&r);
+
+        assert_eq!(is::test_utils::to_ts(&xs), vec![9.9, 10.1]);
+    }
+
+    #[test]
+    fn refracting_into_a_difference_through_its_carved_face() {
+        // The cube carves away the near end of the sphere, down to z = -0.5
+        let c = Csg {
+            left: Box::new(Sphere::unit()),
+            right: Box::new(Transformed::new(
+                Cube::default(),
+                InvertibleMatrix::try_from(transformation::sequence(&[
+                    transformation::scaling(2.0, 2.0, 1.25),
+                    transformation::translation(0.0, 0.0, -1.75),
+                ]))
+                .unwrap(),
+            )),
+            operation: CsgOperation::Difference,
+            material: Some(Material {
+                transparency: 1.0,
+                refractive_index: 1.5,
+                ..Default::default()
+            }),
+        };
+        let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+        let xs = c.intersect(&r);
+        assert_eq!(is::test_utils::to_ts(&xs), vec![4.5, 6.0]);
+
+        let entering = xs[0].prepare_computations(&r, &xs);
+        assert_eq!(entering.refraction_exiting, 1.0);
+        assert_eq!(entering.refraction_entering, 1.5);
+        let leaving = xs[1].prepare_computations(&r, &xs);
+        assert_eq!(leaving.refraction_exiting, 1.5);
+        assert_eq!(leaving.refraction_entering, 1.0);
+    }
+
+    #[test]
+    fn a_csg_object_contains_points_per_its_operation() {
+        let union = Csg::new(CsgOperation::Union, Sphere::unit(), Cube::default());
+        let intersection = Csg::new(CsgOperation::Intersection, Sphere::unit(), Cube::default());
+        let difference = Csg::new(CsgOperation::Difference, Cube::default(), Sphere::unit());
+        let corner = Point3d::new(0.9, 0.9, 0.0);
+
+        assert_eq!(union.contains(&corner, 0.0), Some(true));
+        assert_eq!(intersection.contains(&corner, 0.0), Some(false));
+        assert_eq!(difference.contains(&corner, 0.0), Some(true));
+        assert_eq!(
+            difference.contains(&Point3d::new(0.0, 0.0, 0.0), 0.0),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn occlusion_leaves_what_is_already_in_the_buffer() {
+        // Two spheres overlap between x = -0.5 and 0.5
+        let c = Csg::new(
+            CsgOperation::Intersection,
+            Transformed::new(
+                Sphere::unit(),
+                InvertibleMatrix::try_from(transformation::translation(-0.5, 0.0, 0.0)).unwrap(),
+            ),
+            Transformed::new(
+                Sphere::unit(),
+                InvertibleMatrix::try_from(transformation::translation(0.5, 0.0, 0.0)).unwrap(),
+            ),
+        );
+        let r = Ray::new(Point3d::new(-10.0, 0.0, 0.0), Vec3d::new(1.0, 0.0, 0.0));
+        let earlier = Sphere::unit();
+        let mut xs = Intersections::new();
+        earlier.intersect_into(&r, &mut xs);
+
+        assert!(c.occluded(&r, 10.0, &mut xs));
+        // Only the parts of the spheres outside the overlap lie before x = -0.5
+        assert!(!c.occluded(&r, 9.0, &mut xs));
+        assert_eq!(is::test_utils::to_ts(&xs), vec![9.0, 11.0]);
+    }
 }
@@ -1,14 +1,17 @@
 use crate::{
-    draw::color::Color,
     math::{point::Point3d, vector::NormalizedVec3d},
-    scene::{intersect::Intersection, material::Material, ray::Ray},
+    scene::{
+        intersect::{Intersection, Intersections},
+        material::Material,
+        ray::Ray,
+    },
 };
 
 use super::{bounded::Bounds, Object, PhysicalObject};
 
 #[derive(Default)]
 pub struct Cube {
-    pub material: Material,
+    pub material: Option<Material>,
 }
 
 impl PhysicalObject for Cube {
@@ -28,17 +31,8 @@ impl PhysicalObject for Cube {
         }
         .unwrap()
     }
-}
-
-impl Object for Cube {
-    fn material(&self) -> &Material {
-        &self.material
-    }
 
-    fn intersect(
-        &self,
-        object_ray: &Ray,
-    ) -> Vec<Intersection<&dyn Object, Color, NormalizedVec3d>> {
+    fn intersection_ts(&self, object_ray: &Ray) -> impl Iterator<Item = f64> {
         let (xtmin, xtmax) = check_axis(object_ray.origin.x(), object_ray.direction.x());
         let (ytmin, ytmax) = check_axis(object_ray.origin.y(), object_ray.direction.y());
         let (ztmin, ztmax) = check_axis(object_ray.origin.z(), object_ray.direction.z());
@@ -46,14 +40,39 @@ impl Object for Cube {
         let tmin = xtmin.max(ytmin).max(ztmin);
         let tmax = xtmax.min(ytmax).min(ztmax);
 
-        if tmin > tmax {
-            Vec::new()
-        } else {
-            [tmin, tmax]
-                .into_iter()
-                .map(|t| super::build_basic_intersection(object_ray, t, self))
-                .collect()
-        }
+        (tmin <= tmax).then_some([tmin, tmax]).into_iter().flatten()
+    }
+}
+
+impl Object for Cube {
+    fn material(&self) -> Option<&Material> {
+        self.material.as_ref()
+    }
+
+    fn intersect_into<'a>(&'a self, object_ray: &Ray, xs: &mut Intersections<'a>) {
+        super::basic_intersect_into(self, object_ray, xs)
+    }
+
+    fn intersect_closest<'a>(
+        &'a self,
+        object_ray: &Ray,
+        t_max: f64,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Intersection<&'a dyn PhysicalObject>> {
+        super::basic_intersect_closest(self, object_ray, t_max, xs)
+    }
+
+    fn occluded<'a>(&'a self, object_ray: &Ray, t_max: f64, _xs: &mut Intersections<'a>) -> bool {
+        super::basic_occluded(self, object_ray, t_max)
+    }
+
+    fn contains(&self, object_point: &Point3d, _time: f64) -> Option<bool> {
+        let furthest = object_point
+            .x()
+            .abs()
+            .max(object_point.y().abs())
+            .max(object_point.z().abs());
+        Some(furthest < 1.0)
     }
 
     fn bounds(&self) -> Bounds {
@@ -132,12 +151,12 @@ mod tests {
 
             let xs = cube.intersect(&r);
 
-            for x in xs {
+            for x in xs.iter() {
                 let p = r.position(x.t());
                 let n = cube.normal_at(&p);
-                let c = cube.material().surface.color_at(&p);
-                assert_eq!(x.normal, n);
-                assert_eq!(x.color, c);
+                let c = xs.material(x).surface.color_at(&p);
+                assert_eq!(xs.normal_at(x, &r), n);
+                assert_eq!(xs.color_at(x, &r), c);
             }
         }
     }
@@ -173,4 +192,17 @@ mod tests {
             cube_normal_8: (Point3d::new(-1.0, -1.0, -1.0), Vec3d::new(-1.0, 0.0, 0.0))
         }
     }
+
+    mod contains {
+        use super::*;
+
+        #[test]
+        fn a_cube_contains_points_strictly_inside_it() {
+            let c = Cube::default();
+
+            assert_eq!(c.contains(&Point3d::new(0.9, -0.9, 0.9), 0.0), Some(true));
+            assert_eq!(c.contains(&Point3d::new(1.0, 0.0, 0.0), 0.0), Some(false));
+            assert_eq!(c.contains(&Point3d::new(0.0, 0.0, -1.5), 0.0), Some(false));
+        }
+    }
 }
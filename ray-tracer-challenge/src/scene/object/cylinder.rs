@@ -1,7 +1,10 @@
 use crate::{
-    draw::color::Color,
     math::{point::Point3d, vector::NormalizedVec3d},
-    scene::{intersect::Intersection, material::Material, ray::Ray},
+    scene::{
+        intersect::{Intersection, Intersections},
+        material::Material,
+        ray::Ray,
+    },
 };
 
 use super::{bounded::Bounds, Object, PhysicalObject};
@@ -11,7 +14,7 @@ const EPSILON: f64 = 1e-8;
 /// A cylinder, by default with radius 1 and infinite length around the y-axis
 #[derive(Default)]
 pub struct Cylinder {
-    pub material: Material,
+    pub material: Option<Material>,
     pub minimum: Option<f64>,
     pub maximum: Option<f64>,
     pub closed: bool,
@@ -34,32 +37,22 @@ impl Cylinder {
         x.powi(2) + z.powi(2) <= 1.0
     }
 
-    fn intersects_caps(&self, ray: &Ray) -> Vec<f64> {
+    fn intersects_caps(&self, ray: &Ray) -> [Option<f64>; 2] {
         if !self.closed || ray.direction.y().abs() < EPSILON {
-            Vec::new()
+            [None, None]
         } else {
-            let mut results = Vec::<f64>::new();
-
             // check for intersection with lower end cap
             let tl = self
                 .minimum
-                .map(|min| (min - ray.origin.y()) / ray.direction.y());
-            if let Some(val) = tl {
-                if self.check_cap(ray, val) {
-                    results.push(val);
-                }
-            }
+                .map(|min| (min - ray.origin.y()) / ray.direction.y())
+                .filter(|&val| self.check_cap(ray, val));
 
             let tu = self
                 .maximum
-                .map(|max| (max - ray.origin.y()) / ray.direction.y());
-            if let Some(val) = tu {
-                if self.check_cap(ray, val) {
-                    results.push(val);
-                }
-            }
+                .map(|max| (max - ray.origin.y()) / ray.direction.y())
+                .filter(|&val| self.check_cap(ray, val));
 
-            results
+            [tl, tu]
         }
     }
 }
@@ -71,36 +64,27 @@ impl PhysicalObject for Cylinder {
         if dist2 < 1.0
             && self
                 .maximum
-                .map_or(false, |max| object_point.y() >= max - EPSILON)
+                .is_some_and(|max| object_point.y() >= max - EPSILON)
         {
             NormalizedVec3d::new(0.0, 1.0, 0.0).unwrap()
         } else if dist2 < 1.0
             && self
                 .minimum
-                .map_or(false, |min| object_point.y() <= min + EPSILON)
+                .is_some_and(|min| object_point.y() <= min + EPSILON)
         {
             NormalizedVec3d::new(0.0, -1.0, 0.0).unwrap()
         } else {
             NormalizedVec3d::new(object_point.x(), 0.0, object_point.z()).unwrap()
         }
     }
-}
-
-impl Object for Cylinder {
-    fn material(&self) -> &Material {
-        &self.material
-    }
 
-    fn intersect(
-        &self,
-        object_ray: &Ray,
-    ) -> Vec<Intersection<&dyn Object, Color, NormalizedVec3d>> {
+    fn intersection_ts(&self, object_ray: &Ray) -> impl Iterator<Item = f64> {
         let a = object_ray.direction.x().powi(2) + object_ray.direction.z().powi(2);
 
         // Ray is parallel to the y-axis
-        let mut wall_xs = if a.abs() < EPSILON {
+        let wall_xs = if a.abs() < EPSILON {
             // a is approximately 0
-            Vec::new()
+            [None, None]
         } else {
             let b = 2.0 * object_ray.origin.x() * object_ray.direction.x()
                 + 2.0 * object_ray.origin.z() * object_ray.direction.z();
@@ -109,33 +93,53 @@ impl Object for Cylinder {
             let discriminant = b.powi(2) - 4.0 * a * c;
 
             if discriminant < 0.0 {
-                Vec::new()
+                [None, None]
             } else {
                 let t0 = (-b - f64::sqrt(discriminant)) / (2.0 * a);
                 let t1 = (-b + f64::sqrt(discriminant)) / (2.0 * a);
 
-                let mut results = Vec::<f64>::new();
-                let y0 = object_ray.origin.y() + t0 * object_ray.direction.y();
-                if self.in_bounds(y0) {
-                    results.push(t0)
-                }
-                let y1 = object_ray.origin.y() + t1 * object_ray.direction.y();
-                if self.in_bounds(y1) {
-                    results.push(t1)
-                }
-
-                results
+                [t0, t1].map(|t| {
+                    let y = object_ray.origin.y() + t * object_ray.direction.y();
+                    Some(t).filter(|_| self.in_bounds(y))
+                })
             }
         };
 
-        let mut cap_xs = self.intersects_caps(object_ray);
+        let cap_xs = self.intersects_caps(object_ray);
+
+        wall_xs.into_iter().chain(cap_xs).flatten()
+    }
+}
+
+impl Object for Cylinder {
+    fn material(&self) -> Option<&Material> {
+        self.material.as_ref()
+    }
+
+    fn intersect_into<'a>(&'a self, object_ray: &Ray, xs: &mut Intersections<'a>) {
+        super::basic_intersect_into(self, object_ray, xs)
+    }
+
+    fn intersect_closest<'a>(
+        &'a self,
+        object_ray: &Ray,
+        t_max: f64,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Intersection<&'a dyn PhysicalObject>> {
+        super::basic_intersect_closest(self, object_ray, t_max, xs)
+    }
 
-        wall_xs.append(&mut cap_xs);
+    fn occluded<'a>(&'a self, object_ray: &Ray, t_max: f64, _xs: &mut Intersections<'a>) -> bool {
+        super::basic_occluded(self, object_ray, t_max)
+    }
 
-        wall_xs
-            .into_iter()
-            .map(|t| super::build_basic_intersection(object_ray, t, self))
-            .collect()
+    /// Only closed or infinite cylinders enclose a region of space
+    fn contains(&self, object_point: &Point3d, _time: f64) -> Option<bool> {
+        let encloses = self.closed || (self.minimum.is_none() && self.maximum.is_none());
+        encloses.then(|| {
+            object_point.x().powi(2) + object_point.z().powi(2) < 1.0
+                && self.in_bounds(object_point.y())
+        })
     }
 
     fn bounds(&self) -> Bounds {
@@ -191,12 +195,12 @@ mod tests {
 
             let xs = cylinder.intersect(&r);
 
-            for x in xs {
+            for x in xs.iter() {
                 let p = r.position(x.t());
                 let n = cylinder.normal_at(&p);
-                let c = cylinder.material().surface.color_at(&p);
-                assert_eq!(x.normal, n);
-                assert_eq!(x.color, c);
+                let c = xs.material(x).surface.color_at(&p);
+                assert_eq!(xs.normal_at(x, &r), n);
+                assert_eq!(xs.color_at(x, &r), c);
             }
         }
     }
@@ -377,4 +381,43 @@ mod tests {
             );
         }
     }
+
+    mod contains {
+        use super::*;
+
+        #[test]
+        fn a_closed_cylinder_contains_points_between_its_caps() {
+            let cyl = Cylinder {
+                minimum: Some(1.0),
+                maximum: Some(2.0),
+                closed: true,
+                ..Default::default()
+            };
+
+            assert_eq!(cyl.contains(&Point3d::new(0.5, 1.5, 0.0), 0.0), Some(true));
+            assert_eq!(cyl.contains(&Point3d::new(0.5, 2.5, 0.0), 0.0), Some(false));
+            assert_eq!(cyl.contains(&Point3d::new(1.5, 1.5, 0.0), 0.0), Some(false));
+        }
+
+        #[test]
+        fn an_infinite_cylinder_contains_points_near_its_axis() {
+            let cyl = Cylinder::default();
+
+            assert_eq!(
+                cyl.contains(&Point3d::new(0.0, 100.0, 0.5), 0.0),
+                Some(true)
+            );
+        }
+
+        #[test]
+        fn an_open_truncated_cylinder_encloses_nothing() {
+            let cyl = Cylinder {
+                minimum: Some(1.0),
+                maximum: Some(2.0),
+                ..Default::default()
+            };
+
+            assert_eq!(cyl.contains(&Point3d::new(0.0, 1.5, 0.0), 0.0), None);
+        }
+    }
 }
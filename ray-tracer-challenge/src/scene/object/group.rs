@@ -1,44 +1,73 @@
 use crate::{
-    draw::color::Color,
-    math::vector::NormalizedVec3d,
+    math::point::Point3d,
     scene::{
-        intersect::{self, Intersection},
+        intersect::{Intersection, Intersections},
         material::Material,
         ray::Ray,
     },
 };
 
-use super::{bounded::Bounds, Object};
+use super::{bounded::Bounds, Object, PhysicalObject};
 
 /// A group of multiple sub-objects
 pub struct Group<T> {
     children: Vec<T>,
+    /// Inherited by any children without a material of their own
+    pub material: Option<Material>,
 }
 
 impl<T: Object> Group<T> {
     pub fn new(children: Vec<T>) -> Self {
-        Group { children }
+        Group {
+            children,
+            material: None,
+        }
+    }
+
+    pub fn with_material(children: Vec<T>, material: Material) -> Self {
+        Group {
+            children,
+            material: Some(material),
+        }
     }
 }
 
 impl<T: Object> Object for Group<T> {
-    fn material(&self) -> &Material {
-        unimplemented!()
+    fn material(&self) -> Option<&Material> {
+        self.material.as_ref()
+    }
+
+    fn intersect_into<'a>(&'a self, object_ray: &Ray, xs: &mut Intersections<'a>) {
+        xs.with_material(self.material.as_ref(), |xs| {
+            self.children
+                .iter()
+                .for_each(|obj| obj.intersect_into(object_ray, xs))
+        });
     }
 
-    fn intersect(
-        &self,
+    fn intersect_closest<'a>(
+        &'a self,
         object_ray: &Ray,
-    ) -> Vec<Intersection<&dyn Object, Color, NormalizedVec3d>> {
-        let mut intersections: Vec<_> = self
-            .children
-            .iter()
-            .flat_map(|obj| obj.intersect(object_ray))
-            .collect();
+        t_max: f64,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Intersection<&'a dyn PhysicalObject>> {
+        xs.with_material(self.material.as_ref(), |xs| {
+            closest_among(&self.children, object_ray, t_max, xs)
+        })
+    }
 
-        intersect::sort(&mut intersections);
+    fn occluded<'a>(&'a self, object_ray: &Ray, t_max: f64, xs: &mut Intersections<'a>) -> bool {
+        self.children
+            .iter()
+            .any(|obj| obj.occluded(object_ray, t_max, xs))
+    }
 
-        intersections
+    /// A group encloses the region inside any of its children, as long as all of them enclose one
+    fn contains(&self, point: &Point3d, time: f64) -> Option<bool> {
+        self.children
+            .iter()
+            .map(|c| c.contains(point, time))
+            .try_fold(false, |inside, c| c.map(|c| inside || c))
     }
 
     fn bounds(&self) -> Bounds {
@@ -47,6 +76,19 @@ impl<T: Object> Object for Group<T> {
     }
 }
 
+/// The closest intersection with any of the objects, narrowing the search as hits are found
+pub(crate) fn closest_among<'a, T: Object>(
+    objects: &'a [T],
+    ray: &Ray,
+    t_max: f64,
+    xs: &mut Intersections<'a>,
+) -> Option<Intersection<&'a dyn PhysicalObject>> {
+    objects.iter().fold(None, |closest, obj| {
+        let t_max = closest.as_ref().map_or(t_max, |c: &Intersection<_>| c.t());
+        obj.intersect_closest(ray, t_max, xs).or(closest)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use crate::scene::intersect as is;
@@ -102,6 +144,52 @@ mod tests {
             assert_eq!(xs, vec![1.0, 3.0, 4.0, 6.0]);
         }
 
+        #[test]
+        fn closest_intersection_with_a_group_is_across_all_children() {
+            let far = Sphere::unit();
+            let near = Transformed::new(
+                Sphere::unit(),
+                InvertibleMatrix::try_from(transformation::translation(0.0, 0.0, -3.0)).unwrap(),
+            );
+            let g: Group<Box<dyn Object>> = Group::new(vec![Box::new(far), Box::new(near)]);
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+            let closest = g
+                .intersect_closest(&r, f64::INFINITY, &mut Intersections::new())
+                .unwrap();
+
+            assert_eq!(closest.t(), 1.0);
+            assert!(closest == g.intersect(&r)[0]);
+        }
+
+        #[test]
+        fn a_group_is_occluded_if_any_child_is() {
+            let s1 = Sphere::unit();
+            let s2 = Transformed::new(
+                Sphere::unit(),
+                InvertibleMatrix::try_from(transformation::translation(5.0, 0.0, 0.0)).unwrap(),
+            );
+            let g: Group<Box<dyn Object>> = Group::new(vec![Box::new(s1), Box::new(s2)]);
+
+            let r = Ray::new(Point3d::new(5.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+            assert!(g.occluded(&r, 10.0, &mut Intersections::new()));
+            assert!(!g.occluded(&r, 3.0, &mut Intersections::new()));
+
+            let r = Ray::new(Point3d::new(2.5, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+            assert!(!g.occluded(&r, 10.0, &mut Intersections::new()));
+        }
+
+        #[test]
+        fn intersecting_into_a_buffer_keeps_its_contents() {
+            let g = Group::new(vec![Sphere::unit()]);
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+            let mut xs = g.intersect(&r);
+
+            g.intersect_into(&r, &mut xs);
+
+            assert_eq!(is::test_utils::to_ts(&xs), vec![4.0, 6.0, 4.0, 6.0]);
+        }
+
         #[test]
         fn intersecting_a_transformed_group() {
             let s = Transformed::new(
@@ -129,9 +217,89 @@ mod tests {
             let group_xs = g.intersect(&r);
             let child_xs = g.children[0].intersect(&r);
 
-            assert!(group_xs == child_xs);
-            assert_eq!(group_xs[0].color, child_xs[0].color);
-            assert_eq!(group_xs[0].normal, child_xs[0].normal);
+            assert!(*group_xs == *child_xs);
+            assert_eq!(
+                group_xs.shading(&group_xs[0], &r),
+                child_xs.shading(&child_xs[0], &r)
+            );
+        }
+    }
+
+    mod material {
+        use crate::{
+            draw::color::Color,
+            math::{matrix::InvertibleMatrix, point::Point3d, vector::Vec3d},
+            scene::{
+                material::{self, Surface},
+                object::transformed::Transformed,
+                transformation,
+            },
+        };
+
+        use super::*;
+
+        fn red() -> Material {
+            Material {
+                surface: Surface::Color(Color::new(1.0, 0.0, 0.0)),
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn a_group_has_no_material_by_default() {
+            let g = Group::new(vec![Sphere::unit()]);
+
+            assert!(g.material().is_none());
+        }
+
+        #[test]
+        fn children_without_a_material_inherit_the_group_material() {
+            let g = Group::with_material(vec![Sphere::unit()], red());
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+            let xs = g.intersect(&r);
+
+            assert!(xs.material(&xs[0]) == g.material().unwrap());
+            assert_eq!(xs.color_at(&xs[0], &r), Color::new(1.0, 0.0, 0.0));
+        }
+
+        #[test]
+        fn children_with_a_material_keep_their_own() {
+            let g = Group::with_material(vec![Sphere::new(Default::default())], red());
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+            let xs = g.intersect(&r);
+
+            assert!(xs.material(&xs[0]) == g.children[0].material().unwrap());
+        }
+
+        #[test]
+        fn the_nearest_group_with_a_material_is_inherited_from() {
+            let inner = Group::with_material(vec![Sphere::unit()], red());
+            let outer = Group::with_material(
+                vec![Transformed::new(
+                    inner,
+                    InvertibleMatrix::try_from(transformation::scaling(2.0, 2.0, 2.0)).unwrap(),
+                )],
+                Default::default(),
+            );
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+            let mut xs = Intersections::new();
+            let closest = outer.intersect_closest(&r, f64::INFINITY, &mut xs).unwrap();
+
+            assert_eq!(closest.t(), 3.0);
+            assert_eq!(xs.color_at(&closest, &r), Color::new(1.0, 0.0, 0.0));
+        }
+
+        #[test]
+        fn objects_outside_any_group_use_the_default_material() {
+            let s = Sphere::unit();
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+            let xs = s.intersect(&r);
+
+            assert!(xs.material(&xs[0]) == material::default_material());
         }
     }
 
@@ -188,4 +356,33 @@ mod tests {
             )
         }
     }
+
+    mod contains {
+        use crate::{
+            math::point::Point3d,
+            scene::object::{cube::Cube, plane::Plane, test_utils::MockObject},
+        };
+
+        use super::*;
+
+        #[test]
+        fn a_group_contains_points_inside_any_child() {
+            let g: Group<Box<dyn Object>> =
+                Group::new(vec![Box::new(Sphere::unit()), Box::new(Cube::default())]);
+
+            assert_eq!(g.contains(&Point3d::new(0.9, 0.9, 0.0), 0.0), Some(true));
+            assert_eq!(g.contains(&Point3d::new(1.5, 0.0, 0.0), 0.0), Some(false));
+        }
+
+        #[test]
+        fn a_group_with_a_child_enclosing_nothing_encloses_nothing() {
+            let g: Group<Box<dyn Object>> = Group::new(vec![
+                Box::new(Sphere::unit()),
+                Box::new(Plane::default()),
+                Box::new(MockObject::default()),
+            ]);
+
+            assert_eq!(g.contains(&Point3d::new(0.0, 0.0, 0.0), 0.0), None);
+        }
+    }
 }
@@ -1,21 +1,38 @@
 use crate::{
-    draw::color::Color,
-    math::vector::NormalizedVec3d,
-    scene::{intersect::Intersection, material::Material, ray::Ray},
+    math::point::Point3d,
+    scene::{
+        intersect::{Intersection, Intersections},
+        material::Material,
+        ray::Ray,
+    },
 };
 
-use super::{bounded::Bounds, Object};
+use super::{bounded::Bounds, Object, PhysicalObject};
 
 impl<T: Object + ?Sized> Object for Box<T> {
-    fn material(&self) -> &Material {
+    fn material(&self) -> Option<&Material> {
         (**self).material()
     }
 
-    fn intersect(
-        &self,
+    fn intersect_into<'a>(&'a self, object_ray: &Ray, xs: &mut Intersections<'a>) {
+        (**self).intersect_into(object_ray, xs)
+    }
+
+    fn intersect_closest<'a>(
+        &'a self,
         object_ray: &Ray,
-    ) -> Vec<Intersection<&dyn Object, Color, NormalizedVec3d>> {
-        (**self).intersect(object_ray)
+        t_max: f64,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Intersection<&'a dyn PhysicalObject>> {
+        (**self).intersect_closest(object_ray, t_max, xs)
+    }
+
+    fn occluded<'a>(&'a self, object_ray: &Ray, t_max: f64, xs: &mut Intersections<'a>) -> bool {
+        (**self).occluded(object_ray, t_max, xs)
+    }
+
+    fn contains(&self, point: &Point3d, time: f64) -> Option<bool> {
+        (**self).contains(point, time)
     }
 
     fn bounds(&self) -> Bounds {
@@ -0,0 +1,202 @@
+use std::sync::Arc;
+
+use crate::{
+    math::point::Point3d,
+    scene::{
+        intersect::{Intersection, Intersections},
+        material::Material,
+        ray::Ray,
+    },
+};
+
+use super::{bounded::Bounds, Object, PhysicalObject};
+
+/// A copy of geometry shared with other instances, so that a mesh placed many times over with
+/// [Transformed](super::transformed::Transformed) is only kept in memory once
+pub struct Instance<T: ?Sized> {
+    geometry: Arc<T>,
+    /// Replaces the materials of everything in the geometry
+    pub material: Option<Material>,
+}
+
+impl<T: Object + ?Sized> Instance<T> {
+    pub fn new(geometry: Arc<T>) -> Self {
+        Instance {
+            geometry,
+            material: None,
+        }
+    }
+
+    pub fn with_material(geometry: Arc<T>, material: Material) -> Self {
+        Instance {
+            geometry,
+            material: Some(material),
+        }
+    }
+
+    pub fn geometry(&self) -> &Arc<T> {
+        &self.geometry
+    }
+
+    fn with_override<'a, R>(
+        &'a self,
+        xs: &mut Intersections<'a>,
+        f: impl FnOnce(&mut Intersections<'a>) -> R,
+    ) -> R {
+        match &self.material {
+            Some(material) => {
+                let previous = xs.enter_override(material);
+                let result = f(xs);
+                xs.exit_frame(previous);
+                result
+            }
+            None => f(xs),
+        }
+    }
+}
+
+impl<T: Object + ?Sized> Object for Instance<T> {
+    fn material(&self) -> Option<&Material> {
+        self.material.as_ref().or_else(|| self.geometry.material())
+    }
+
+    fn intersect_into<'a>(&'a self, object_ray: &Ray, xs: &mut Intersections<'a>) {
+        self.with_override(xs, |xs| self.geometry.intersect_into(object_ray, xs))
+    }
+
+    fn intersect_closest<'a>(
+        &'a self,
+        object_ray: &Ray,
+        t_max: f64,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Intersection<&'a dyn PhysicalObject>> {
+        self.with_override(xs, |xs| {
+            self.geometry.intersect_closest(object_ray, t_max, xs)
+        })
+    }
+
+    fn occluded<'a>(&'a self, object_ray: &Ray, t_max: f64, xs: &mut Intersections<'a>) -> bool {
+        self.geometry.occluded(object_ray, t_max, xs)
+    }
+
+    fn contains(&self, point: &Point3d, time: f64) -> Option<bool> {
+        self.geometry.contains(point, time)
+    }
+
+    fn bounds(&self) -> Bounds {
+        self.geometry.bounds()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        draw::color::{self, Color},
+        math::{matrix::InvertibleMatrix, vector::Vec3d},
+        scene::{
+            material::Surface,
+            object::{group::Group, sphere::Sphere, transformed::Transformed},
+            transformation,
+        },
+    };
+
+    use super::*;
+
+    fn colored(color: Color) -> Material {
+        Material {
+            surface: Surface::Color(color),
+            ..Default::default()
+        }
+    }
+
+    fn ray() -> Ray {
+        Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0))
+    }
+
+    #[test]
+    fn instances_share_their_geometry() {
+        let mesh = Arc::new(Group::new(vec![Sphere::unit()]));
+
+        let instances: Vec<_> = (0..1000)
+            .map(|n| {
+                Transformed::new(
+                    Instance::new(mesh.clone()),
+                    InvertibleMatrix::try_from(transformation::translation(n as f64, 0.0, 0.0))
+                        .unwrap(),
+                )
+            })
+            .collect();
+
+        assert_eq!(Arc::strong_count(&mesh), 1001);
+        assert_eq!(instances[0].bounds(), mesh.bounds());
+    }
+
+    #[test]
+    fn an_instance_is_hit_like_its_geometry() {
+        let instance = Instance::new(Arc::new(Sphere::unit()));
+
+        let xs = instance.intersect(&ray());
+
+        assert_eq!(xs.iter().map(|i| i.t()).collect::<Vec<_>>(), [4.0, 6.0]);
+    }
+
+    #[test]
+    fn without_a_material_an_instance_keeps_that_of_its_geometry() {
+        let instance = Instance::new(Arc::new(Sphere::new(colored(color::red()))));
+        let r = ray();
+
+        let xs = instance.intersect(&r);
+
+        assert_eq!(xs.color_at(&xs[0], &r), color::red());
+    }
+
+    #[test]
+    fn an_instances_material_replaces_those_of_its_geometry() {
+        let mesh: Arc<dyn Object> = Arc::new(Group::with_material(
+            vec![Sphere::new(colored(color::red()))],
+            colored(color::green()),
+        ));
+        let instance = Instance::with_material(mesh, colored(color::blue()));
+        let r = ray();
+
+        let xs = instance.intersect(&r);
+        let closest = instance
+            .intersect_closest(&r, f64::INFINITY, &mut Intersections::new())
+            .unwrap();
+
+        assert_eq!(xs.color_at(&xs[0], &r), color::blue());
+        assert_eq!(closest.t(), 4.0);
+    }
+
+    #[test]
+    fn the_outermost_instance_material_wins() {
+        let inner = Instance::with_material(Arc::new(Sphere::unit()), colored(color::red()));
+        let outer = Instance::with_material(Arc::new(inner), colored(color::green()));
+        let r = ray();
+
+        let xs = outer.intersect(&r);
+
+        assert_eq!(xs.color_at(&xs[0], &r), color::green());
+    }
+
+    #[test]
+    fn instances_in_different_places_share_a_mesh() {
+        let mesh = Arc::new(Sphere::unit());
+        let placed = |x: f64, material: Material| {
+            Transformed::new(
+                Instance::with_material(mesh.clone(), material),
+                InvertibleMatrix::try_from(transformation::translation(x, 0.0, 0.0)).unwrap(),
+            )
+        };
+        let scene = Group::new(vec![
+            placed(-3.0, colored(color::red())),
+            placed(3.0, colored(color::blue())),
+        ]);
+        let r = Ray::new(Point3d::new(3.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+        let xs = scene.intersect(&r);
+
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs.color_at(&xs[0], &r), color::blue());
+    }
+}
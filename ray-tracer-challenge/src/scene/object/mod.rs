@@ -1,34 +1,101 @@
-use crate::{
-    draw::color::Color,
-    math::{point::Point3d, vector::NormalizedVec3d},
-};
+use crate::math::{point::Point3d, vector::NormalizedVec3d};
 
 use self::bounded::Bounds;
 
-use super::{intersect::Intersection, material::Material, ray::Ray};
+use super::{
+    intersect::{self, Intersection, Intersections},
+    material::Material,
+    ray::Ray,
+};
 
 pub trait Object: Sync + Send {
-    fn material(&self) -> &Material;
-    fn intersect(&self, ray: &Ray) -> Vec<Intersection<&dyn Object, Color, NormalizedVec3d>>;
+    /// The material given to this object, if any. Objects without one take on the material of
+    /// the nearest group or CSG object enclosing them that has one.
+    fn material(&self) -> Option<&Material>;
+    /// Appends the intersections of the ray with this object to `xs`, in no particular order.
+    /// Implementations must leave anything already in `xs` untouched.
+    fn intersect_into<'a>(&'a self, ray: &Ray, xs: &mut Intersections<'a>);
     fn bounds(&self) -> Bounds;
+
+    /// All intersections of the ray with this object in a new buffer, sorted by `t`
+    fn intersect(&self, ray: &Ray) -> Intersections<'_> {
+        let mut xs = Intersections::new();
+        self.intersect_into(ray, &mut xs);
+        intersect::sort(&mut xs);
+        xs
+    }
+
+    /// The intersection [intersect::hit] would pick, ignoring any further along the ray than
+    /// `t_max`. Any transformations it is nested in are recorded in `xs`.
+    fn intersect_closest<'a>(
+        &'a self,
+        ray: &Ray,
+        t_max: f64,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Intersection<&'a dyn PhysicalObject>> {
+        let start = xs.len();
+        self.intersect_into(ray, xs);
+        let closest = intersect::closest(xs[start..].iter().copied(), t_max);
+        xs.truncate(start);
+        closest
+    }
+
+    /// Whether the ray intersects this object anywhere strictly between 0 and `t_max`. Objects
+    /// that need to gather intersections to tell may use `xs` as scratch space, leaving anything
+    /// already in it untouched.
+    fn occluded<'a>(&'a self, ray: &Ray, t_max: f64, xs: &mut Intersections<'a>) -> bool {
+        let start = xs.len();
+        self.intersect_into(ray, xs);
+        let occluded = xs[start..].iter().any(|i| i.t() > 0.0 && i.t() < t_max);
+        xs.truncate(start);
+        occluded
+    }
+
+    /// Whether the point is strictly inside the object at `time`, the time of the ray that found
+    /// it, or `None` if the object doesn't enclose a region of space (like a plane or an open
+    /// cylinder)
+    fn contains(&self, _point: &Point3d, _time: f64) -> Option<bool> {
+        None
+    }
 }
 
-trait PhysicalObject: Object {
+/// An object with a surface of its own, as opposed to one that arranges others
+pub trait PhysicalObject: Object {
     fn normal_at(&self, point: &Point3d) -> NormalizedVec3d;
+
+    /// The normal where an intersection landed, given the extra data recorded with it
+    fn hit_normal_at(&self, point: &Point3d, _uv: (f64, f64)) -> NormalizedVec3d {
+        self.normal_at(point)
+    }
+
+    /// The values of `t` at which the ray intersects the object, in order of discovery
+    fn intersection_ts(&self, ray: &Ray) -> impl Iterator<Item = f64>
+    where
+        Self: Sized;
 }
 
-fn build_basic_intersection<'a, T: PhysicalObject>(
+fn basic_intersect_into<'a, T: PhysicalObject>(
+    object: &'a T,
     ray: &Ray,
-    t: f64,
+    xs: &mut Intersections<'a>,
+) {
+    object
+        .intersection_ts(ray)
+        .for_each(|t| xs.push(Intersection::new(t, object)));
+}
+
+fn basic_intersect_closest<'a, T: PhysicalObject>(
     object: &'a T,
-) -> Intersection<&'a dyn Object, Color, NormalizedVec3d> {
-    let p = ray.position(t);
-    Intersection::new(
-        t,
-        object,
-        object.material().surface.color_at(&p),
-        object.normal_at(&p),
-    )
+    ray: &Ray,
+    t_max: f64,
+    xs: &Intersections<'a>,
+) -> Option<Intersection<&'a dyn PhysicalObject>> {
+    intersect::closest_t(object.intersection_ts(ray), t_max)
+        .map(|t| xs.in_current_frame(Intersection::new(t, object)))
+}
+
+fn basic_occluded<T: PhysicalObject>(object: &T, ray: &Ray, t_max: f64) -> bool {
+    object.intersection_ts(ray).any(|t| t > 0.0 && t < t_max)
 }
 
 pub mod bounded;
@@ -38,6 +105,8 @@ pub mod cube;
 pub mod cylinder;
 pub mod group;
 pub mod impls;
+pub mod instance;
+pub mod moving;
 pub mod plane;
 pub mod sphere;
 pub mod transformed;
@@ -60,24 +129,23 @@ pub mod test_utils {
         fn normal_at(&self, object_point: &Point3d) -> NormalizedVec3d {
             NormalizedVec3d::new(object_point.x(), object_point.y(), object_point.z()).unwrap()
         }
+
+        /// Hits at the ray's origin, so that its color and normal are taken from there
+        fn intersection_ts(&self, _object_ray: &Ray) -> impl Iterator<Item = f64> {
+            std::iter::once(0.0)
+        }
     }
 
     impl Object for MockObject {
-        fn material(&self) -> &Material {
-            &self.material
+        fn material(&self) -> Option<&Material> {
+            Some(&self.material)
         }
 
-        fn intersect(
-            &self,
-            object_ray: &Ray,
-        ) -> Vec<Intersection<&dyn Object, Color, NormalizedVec3d>> {
+        fn intersect_into<'a>(&'a self, object_ray: &Ray, xs: &mut Intersections<'a>) {
             if let Some(expected) = self.intersect_local_arg_expectation.as_ref() {
                 assert_eq!(object_ray, expected);
             }
-            let t = 1.0;
-            let color = self.material().surface.color_at(&object_ray.origin);
-            let normal = self.normal_at(&object_ray.origin);
-            vec![Intersection::new(t, self, color, normal)]
+            super::basic_intersect_into(self, object_ray, xs);
         }
 
         fn bounds(&self) -> Bounds {
@@ -0,0 +1,249 @@
+use crate::{
+    math::{matrix::InvertibleMatrix, point::Point3d, vector::Vec3d},
+    scene::{
+        animation::Interpolate,
+        intersect::{Intersection, Intersections},
+        material::Material,
+        ray::Ray,
+        transformation::Decomposed,
+    },
+};
+
+use super::{bounded::Bounds, Object, PhysicalObject};
+
+/// An object that moves from one transformation at time 0 to another at time 1, placed for each
+/// ray by the ray's time, so that it blurs when seen by a camera whose shutter stays open
+pub struct Moving<T> {
+    child: T,
+    start: Decomposed,
+    end: Decomposed,
+}
+
+impl<T: Object> Moving<T> {
+    /// Fails if either transformation shears, since only scaling, rotation and translation can be
+    /// blended smoothly
+    pub fn new(
+        child: T,
+        start: &InvertibleMatrix<4>,
+        end: &InvertibleMatrix<4>,
+    ) -> Result<Self, String> {
+        Ok(Moving {
+            child,
+            start: Decomposed::new(start)?,
+            end: Decomposed::new(end)?,
+        })
+    }
+
+    /// The transformation at `time`, held at the ends before 0 and after 1, or `None` if the
+    /// object is flattened at that moment
+    pub fn transform_at(&self, time: f64) -> Option<InvertibleMatrix<4>> {
+        let blended = self.start.interpolate(&self.end, time.clamp(0.0, 1.0));
+        InvertibleMatrix::try_from(blended.matrix()).ok()
+    }
+
+    fn local_ray(&self, object_ray: &Ray) -> Option<(Ray, InvertibleMatrix<4>)> {
+        let transform = self.transform_at(object_ray.time)?;
+        Some((object_ray.transform(transform.inverse()), transform))
+    }
+}
+
+impl<T: Object> Object for Moving<T> {
+    fn material(&self) -> Option<&Material> {
+        self.child.material()
+    }
+
+    fn intersect_into<'a>(&'a self, object_ray: &Ray, xs: &mut Intersections<'a>) {
+        let Some((local_ray, transform)) = self.local_ray(object_ray) else {
+            return;
+        };
+        let inverse_transpose = transform.inverse().transpose();
+        let previous = xs.enter_owned_frame(transform, inverse_transpose);
+        self.child.intersect_into(&local_ray, xs);
+        xs.exit_frame(previous);
+    }
+
+    fn intersect_closest<'a>(
+        &'a self,
+        object_ray: &Ray,
+        t_max: f64,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Intersection<&'a dyn PhysicalObject>> {
+        let (local_ray, transform) = self.local_ray(object_ray)?;
+        let inverse_transpose = transform.inverse().transpose();
+        let previous = xs.enter_owned_frame(transform, inverse_transpose);
+        let closest = self.child.intersect_closest(&local_ray, t_max, xs);
+        xs.exit_frame(previous);
+        closest
+    }
+
+    fn occluded<'a>(&'a self, object_ray: &Ray, t_max: f64, xs: &mut Intersections<'a>) -> bool {
+        self.local_ray(object_ray)
+            .is_some_and(|(local_ray, _)| self.child.occluded(&local_ray, t_max, xs))
+    }
+
+    /// Whether the object contains the point where it is at `time`
+    fn contains(&self, point: &Point3d, time: f64) -> Option<bool> {
+        let transform = self.transform_at(time)?;
+        self.child.contains(&(transform.inverse() * point), time)
+    }
+
+    fn bounds(&self) -> Bounds {
+        // However it turns, the child's corners stay within reach of its origin, which only ever
+        // moves in a straight line from the start to the end
+        let child_bounds = self.child.bounds();
+        if child_bounds.is_empty() {
+            return child_bounds;
+        }
+        let reach = child_bounds
+            .enumerate()
+            .iter()
+            .flat_map(|p| {
+                [&self.start.scale, &self.end.scale]
+                    .map(|s| Vec3d::new(p.x() * s.x(), p.y() * s.y(), p.z() * s.z()).mag())
+            })
+            .fold(0.0, f64::max);
+
+        let corners: Vec<Point3d> = [&self.start.translation, &self.end.translation]
+            .iter()
+            .flat_map(|t| {
+                [-reach, reach].into_iter().flat_map(move |x| {
+                    [-reach, reach].into_iter().flat_map(move |y| {
+                        [-reach, reach]
+                            .into_iter()
+                            .map(move |z| Point3d::new(t.x() + x, t.y() + y, t.z() + z))
+                    })
+                })
+            })
+            .collect();
+        Bounds::from_points(&corners).expect("should have been 16 corners")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        math::vector::{self, NormalizedVec3d},
+        scene::{
+            object::{
+                csg::{Csg, CsgOperation},
+                sphere::Sphere,
+                transformed::Transformed,
+            },
+            transformation,
+        },
+    };
+
+    use super::*;
+
+    fn sliding_sphere() -> Moving<Sphere> {
+        Moving::new(
+            Sphere::unit(),
+            &InvertibleMatrix::identity(),
+            &InvertibleMatrix::try_from(transformation::translation(4.0, 0.0, 0.0)).unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn ray_at(x: f64, time: f64) -> Ray {
+        Ray::new(Point3d::new(x, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0)).with_time(time)
+    }
+
+    #[test]
+    fn a_moving_object_cannot_shear() {
+        let shear = transformation::shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        let moving = Moving::new(
+            Sphere::unit(),
+            &InvertibleMatrix::identity(),
+            &InvertibleMatrix::try_from(shear).unwrap(),
+        );
+
+        assert!(moving.is_err());
+    }
+
+    #[test]
+    fn where_a_ray_finds_a_moving_object_depends_on_its_time() {
+        let s = sliding_sphere();
+
+        assert_eq!(s.intersect(&ray_at(0.0, 0.0)).len(), 2);
+        assert_eq!(s.intersect(&ray_at(0.0, 1.0)).len(), 0);
+        assert_eq!(s.intersect(&ray_at(2.0, 0.5)).len(), 2);
+        assert_eq!(s.intersect(&ray_at(4.0, 1.0)).len(), 2);
+    }
+
+    #[test]
+    fn a_moving_object_stays_put_outside_of_its_time_range() {
+        let s = sliding_sphere();
+
+        assert!(s.occluded(&ray_at(0.0, -1.0), f64::INFINITY, &mut Intersections::new()));
+        assert!(s.occluded(&ray_at(4.0, 2.0), f64::INFINITY, &mut Intersections::new()));
+    }
+
+    #[test]
+    fn the_normal_on_a_moving_object_follows_it() {
+        let s = sliding_sphere();
+        let r = Ray::new(Point3d::new(-5.0, 0.0, 0.0), Vec3d::new(1.0, 0.0, 0.0)).with_time(0.5);
+        let xs = s.intersect(&r);
+
+        assert_eq!(xs[0].t(), 6.0);
+        vector::test_utils::assert_vec_approx_equals(
+            &xs.normal_at(&xs[0], &r),
+            &NormalizedVec3d::new(-1.0, 0.0, 0.0).unwrap(),
+        );
+    }
+
+    #[test]
+    fn a_moving_object_contains_points_where_it_is_at_the_given_time() {
+        let s = sliding_sphere();
+        let p = Point3d::new(4.0, 0.0, 0.0);
+
+        assert_eq!(s.contains(&p, 0.0), Some(false));
+        assert_eq!(s.contains(&p, 1.0), Some(true));
+    }
+
+    #[test]
+    fn refracting_into_a_csg_with_a_moving_part_at_a_later_time() {
+        let glass = Material {
+            transparency: 1.0,
+            refractive_index: 1.5,
+            ..Default::default()
+        };
+        let c = Csg {
+            left: Box::new(sliding_sphere()),
+            right: Box::new(Transformed::new(
+                Sphere::unit(),
+                InvertibleMatrix::try_from(transformation::translation(0.0, 5.0, 0.0)).unwrap(),
+            )),
+            operation: CsgOperation::Union,
+            material: Some(glass),
+        };
+        let r = ray_at(4.0, 1.0);
+
+        let xs = c.intersect(&r);
+        let comps = xs[0].prepare_computations(&r, &xs);
+
+        assert_eq!(comps.refraction_exiting, 1.0);
+        assert_eq!(comps.refraction_entering, 1.5);
+    }
+
+    #[test]
+    fn a_spinning_object_is_bounded_wherever_it_turns() {
+        let stretched = Moving::new(
+            Sphere::unit(),
+            &InvertibleMatrix::try_from(transformation::scaling(2.0, 1.0, 1.0)).unwrap(),
+            &InvertibleMatrix::try_from(transformation::sequence(&[
+                transformation::scaling(2.0, 1.0, 1.0),
+                transformation::rotation_y(std::f64::consts::FRAC_PI_2),
+                transformation::translation(0.0, 3.0, 0.0),
+            ]))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let bounds = stretched.bounds();
+        for time in [0.0, 0.25, 0.5, 0.75, 1.0] {
+            let moved =
+                Transformed::new(Sphere::unit(), stretched.transform_at(time).unwrap()).bounds();
+            assert_eq!(Bounds::from_bounds(&[&bounds, &moved]), bounds);
+        }
+    }
+}
@@ -1,7 +1,10 @@
 use crate::{
-    draw::color::Color,
     math::{point::Point3d, vector::NormalizedVec3d},
-    scene::{intersect::Intersection, material::Material, ray::Ray},
+    scene::{
+        intersect::{Intersection, Intersections},
+        material::Material,
+        ray::Ray,
+    },
 };
 
 use super::{bounded::Bounds, Object, PhysicalObject};
@@ -9,33 +12,42 @@ use super::{bounded::Bounds, Object, PhysicalObject};
 /// A plane: by default, a plane in xz
 #[derive(Default)]
 pub struct Plane {
-    pub material: Material,
+    pub material: Option<Material>,
 }
 
 impl PhysicalObject for Plane {
     fn normal_at(&self, _: &Point3d) -> NormalizedVec3d {
         NormalizedVec3d::new(0.0, 1.0, 0.0).unwrap()
     }
+
+    fn intersection_ts(&self, object_ray: &Ray) -> impl Iterator<Item = f64> {
+        // If ray y direction is 0 (epsilon comparison cause floating point)
+        (f64::abs(object_ray.direction.y()) >= 1e-8)
+            .then(|| -object_ray.origin.y() / object_ray.direction.y())
+            .into_iter()
+    }
 }
 
 impl Object for Plane {
-    fn material(&self) -> &Material {
-        &self.material
+    fn material(&self) -> Option<&Material> {
+        self.material.as_ref()
+    }
+
+    fn intersect_into<'a>(&'a self, object_ray: &Ray, xs: &mut Intersections<'a>) {
+        super::basic_intersect_into(self, object_ray, xs)
     }
 
-    fn intersect(
-        &self,
+    fn intersect_closest<'a>(
+        &'a self,
         object_ray: &Ray,
-    ) -> Vec<Intersection<&dyn Object, Color, NormalizedVec3d>> {
-        // If ray y direction is 0 (epsilon comparison cause floating point)
-        if f64::abs(object_ray.direction.y()) < 1e-8 {
-            Vec::new()
-        } else {
-            [-object_ray.origin.y() / object_ray.direction.y()]
-                .into_iter()
-                .map(|t| super::build_basic_intersection(object_ray, t, self))
-                .collect()
-        }
+        t_max: f64,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Intersection<&'a dyn PhysicalObject>> {
+        super::basic_intersect_closest(self, object_ray, t_max, xs)
+    }
+
+    fn occluded<'a>(&'a self, object_ray: &Ray, t_max: f64, _xs: &mut Intersections<'a>) -> bool {
+        super::basic_occluded(self, object_ray, t_max)
     }
 
     fn bounds(&self) -> Bounds {
@@ -111,12 +123,12 @@ mod tests {
 
         let xs = plane.intersect(&r);
 
-        for x in xs {
+        for x in xs.iter() {
             let p = r.position(x.t());
             let n = plane.normal_at(&p);
-            let c = plane.material().surface.color_at(&p);
-            assert_eq!(x.normal, n);
-            assert_eq!(x.color, c);
+            let c = xs.material(x).surface.color_at(&p);
+            assert_eq!(xs.normal_at(x, &r), n);
+            assert_eq!(xs.color_at(x, &r), c);
         }
     }
 }
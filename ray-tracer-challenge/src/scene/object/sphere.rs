@@ -1,23 +1,28 @@
 use crate::{
-    draw::color::Color,
     math::{point::Point3d, vector::NormalizedVec3d},
-    scene::{intersect::Intersection, material::Material, ray::Ray},
+    scene::{
+        intersect::{Intersection, Intersections},
+        material::Material,
+        ray::Ray,
+    },
 };
 
 use super::{bounded::Bounds, Object, PhysicalObject};
 
 /// A sphere: by default, a unit sphere (of radius 1 and its origin at (0, 0, 0))
 pub struct Sphere {
-    pub material: Material,
+    pub material: Option<Material>,
 }
 
 impl Sphere {
     pub fn new(material: Material) -> Self {
-        Sphere { material }
+        Sphere {
+            material: Some(material),
+        }
     }
 
     pub fn unit() -> Self {
-        Sphere::new(Default::default())
+        Sphere { material: None }
     }
 }
 
@@ -25,17 +30,8 @@ impl PhysicalObject for Sphere {
     fn normal_at(&self, object_point: &Point3d) -> NormalizedVec3d {
         NormalizedVec3d::try_from(object_point - &Point3d::new(0.0, 0.0, 0.0)).unwrap()
     }
-}
 
-impl Object for Sphere {
-    fn material(&self) -> &Material {
-        &self.material
-    }
-
-    fn intersect(
-        &self,
-        object_ray: &Ray,
-    ) -> Vec<Intersection<&dyn Object, Color, NormalizedVec3d>> {
+    fn intersection_ts(&self, object_ray: &Ray) -> impl Iterator<Item = f64> {
         let sphere_to_ray = &object_ray.origin - &Point3d::new(0.0, 0.0, 0.0);
 
         let a = object_ray.direction.dot(&object_ray.direction);
@@ -44,18 +40,42 @@ impl Object for Sphere {
 
         let discriminant = b * b - 4.0 * a * c;
 
-        if discriminant < 0.0 {
-            Vec::new()
-        } else {
+        let ts = (discriminant >= 0.0).then(|| {
             let disc_sqrt = f64::sqrt(discriminant);
             let t1 = (-b - disc_sqrt) / (2.0 * a);
             let t2 = (-b + disc_sqrt) / (2.0 * a);
-
             [t1, t2]
-                .into_iter()
-                .map(|t| super::build_basic_intersection(object_ray, t, self))
-                .collect()
-        }
+        });
+
+        ts.into_iter().flatten()
+    }
+}
+
+impl Object for Sphere {
+    fn material(&self) -> Option<&Material> {
+        self.material.as_ref()
+    }
+
+    fn intersect_into<'a>(&'a self, object_ray: &Ray, xs: &mut Intersections<'a>) {
+        super::basic_intersect_into(self, object_ray, xs)
+    }
+
+    fn intersect_closest<'a>(
+        &'a self,
+        object_ray: &Ray,
+        t_max: f64,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Intersection<&'a dyn PhysicalObject>> {
+        super::basic_intersect_closest(self, object_ray, t_max, xs)
+    }
+
+    fn occluded<'a>(&'a self, object_ray: &Ray, t_max: f64, _xs: &mut Intersections<'a>) -> bool {
+        super::basic_occluded(self, object_ray, t_max)
+    }
+
+    fn contains(&self, object_point: &Point3d, _time: f64) -> Option<bool> {
+        let from_center = object_point - &Point3d::new(0.0, 0.0, 0.0);
+        Some(from_center.dot(&from_center) < 1.0)
     }
 
     fn bounds(&self) -> Bounds {
@@ -73,13 +93,11 @@ impl Default for Sphere {
 }
 
 pub fn glass_sphere() -> Sphere {
-    Sphere {
-        material: Material {
-            transparency: 1.0,
-            refractive_index: 1.52,
-            ..Default::default()
-        },
-    }
+    Sphere::new(Material {
+        transparency: 1.0,
+        refractive_index: 1.52,
+        ..Default::default()
+    })
 }
 
 #[cfg(test)]
@@ -152,16 +170,65 @@ mod tests {
 
             let xs = s.intersect(&r);
 
-            for x in xs {
+            for x in xs.iter() {
                 let p = r.position(x.t());
                 let n = s.normal_at(&p);
-                let c = s.material().surface.color_at(&p);
-                assert_eq!(x.normal, n);
-                assert_eq!(x.color, c);
+                let c = xs.material(x).surface.color_at(&p);
+                assert_eq!(xs.normal_at(x, &r), n);
+                assert_eq!(xs.color_at(x, &r), c);
             }
         }
     }
 
+    mod closest_and_occluded {
+        use super::*;
+
+        #[test]
+        fn closest_intersection_is_the_nearer_one() {
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+            let s = Sphere::unit();
+
+            assert_eq!(
+                s.intersect_closest(&r, f64::INFINITY, &mut Intersections::new())
+                    .unwrap()
+                    .t(),
+                4.0
+            );
+            assert!(s
+                .intersect_closest(&r, 3.0, &mut Intersections::new())
+                .is_none());
+        }
+
+        #[test]
+        fn closest_intersection_from_inside_is_in_front() {
+            let r = Ray::new(Point3d::new(0.0, 0.0, 0.0), Vec3d::new(0.0, 0.0, 1.0));
+            let s = Sphere::unit();
+
+            let mut xs = Intersections::new();
+            let x = s.intersect_closest(&r, f64::INFINITY, &mut xs).unwrap();
+
+            assert_eq!(x.t(), 1.0);
+            assert_eq!(
+                xs.normal_at(&x, &r),
+                s.normal_at(&Point3d::new(0.0, 0.0, 1.0))
+            );
+        }
+
+        #[test]
+        fn sphere_occludes_only_within_t_max() {
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+            let s = Sphere::unit();
+
+            assert!(s.occluded(&r, 10.0, &mut Intersections::new()));
+            assert!(!s.occluded(&r, 4.0, &mut Intersections::new()));
+            assert!(!s.occluded(
+                &Ray::new(Point3d::new(0.0, 0.0, 5.0), r.direction),
+                10.0,
+                &mut Intersections::new()
+            ));
+        }
+    }
+
     mod normal {
         use super::*;
 
@@ -228,12 +295,22 @@ mod tests {
                 ambient: 1.0,
                 ..Default::default()
             };
-            let s = Sphere {
-                material: m,
-                ..Default::default()
-            };
+            let s = Sphere::new(m);
+
+            assert_eq!(s.material().unwrap().ambient, 1.0);
+        }
+    }
+
+    mod contains {
+        use super::*;
+
+        #[test]
+        fn a_sphere_contains_points_strictly_inside_it() {
+            let s = Sphere::unit();
 
-            assert_eq!(s.material.ambient, 1.0);
+            assert_eq!(s.contains(&Point3d::new(0.0, 0.5, 0.0), 0.0), Some(true));
+            assert_eq!(s.contains(&Point3d::new(0.0, 1.0, 0.0), 0.0), Some(false));
+            assert_eq!(s.contains(&Point3d::new(2.0, 0.0, 0.0), 0.0), Some(false));
         }
     }
 }
@@ -1,13 +1,16 @@
 use crate::{
-    draw::color::Color,
     math::{
         matrix::{InvertibleMatrix, SquareMatrix},
-        vector::NormalizedVec3d,
+        point::Point3d,
+    },
+    scene::{
+        intersect::{Intersection, Intersections},
+        material::Material,
+        ray::Ray,
     },
-    scene::{intersect::Intersection, material::Material, ray::Ray},
 };
 
-use super::{bounded::Bounds, Object};
+use super::{bounded::Bounds, Object, PhysicalObject};
 
 pub struct Transformed<T> {
     child: T,
@@ -32,26 +35,46 @@ impl<T: Object> Transformed<T> {
 }
 
 impl<T: Object> Object for Transformed<T> {
-    fn material(&self) -> &Material {
+    fn material(&self) -> Option<&Material> {
         self.child.material()
     }
 
-    fn intersect(
-        &self,
+    fn intersect_into<'a>(&'a self, object_ray: &Ray, xs: &mut Intersections<'a>) {
+        let local_ray = object_ray.transform(self.transform.inverse());
+        let previous = xs.enter_frame(&self.transform, &self.inverse_transpose);
+        self.child.intersect_into(&local_ray, xs);
+        xs.exit_frame(previous);
+    }
+
+    fn intersect_closest<'a>(
+        &'a self,
         object_ray: &Ray,
-    ) -> Vec<Intersection<&dyn Object, Color, NormalizedVec3d>> {
+        t_max: f64,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Intersection<&'a dyn PhysicalObject>> {
         let local_ray = object_ray.transform(self.transform.inverse());
-        let mut xs = self.child.intersect(&local_ray);
-        xs.iter_mut().for_each(|x| {
-            let world_normal = &self.inverse_transpose * &*x.normal;
-            let normalized = NormalizedVec3d::try_from(world_normal).unwrap();
-            x.normal = normalized;
-        });
-        xs
+        let previous = xs.enter_frame(&self.transform, &self.inverse_transpose);
+        let closest = self.child.intersect_closest(&local_ray, t_max, xs);
+        xs.exit_frame(previous);
+        closest
+    }
+
+    fn occluded<'a>(&'a self, object_ray: &Ray, t_max: f64, xs: &mut Intersections<'a>) -> bool {
+        let local_ray = object_ray.transform(self.transform.inverse());
+        self.child.occluded(&local_ray, t_max, xs)
+    }
+
+    fn contains(&self, point: &Point3d, time: f64) -> Option<bool> {
+        self.child
+            .contains(&(self.transform.inverse() * point), time)
     }
 
     fn bounds(&self) -> Bounds {
-        let enumerated_points = self.child.bounds().enumerate();
+        let child_bounds = self.child.bounds();
+        if child_bounds.is_empty() {
+            return child_bounds;
+        }
+        let enumerated_points = child_bounds.enumerate();
         let transformed_points = enumerated_points.map(|p| &*self.transform * &p);
         Bounds::from_points(&transformed_points).expect("should have been 8 transformed points")
     }
@@ -60,10 +83,14 @@ impl<T: Object> Object for Transformed<T> {
 #[cfg(test)]
 mod tests {
     use crate::{
+        draw::color::Color,
         math::{point::Point3d, vector::Vec3d},
         scene::{
-            intersect as is, material::Surface, object::test_utils::MockObject,
-            pattern::test_utils::MockPattern, transformation,
+            intersect as is,
+            material::Surface,
+            object::{sphere::Sphere, test_utils::MockObject},
+            pattern::test_utils::MockPattern,
+            transformation,
         },
     };
 
@@ -119,7 +146,7 @@ mod tests {
 
     mod normal {
         use crate::{
-            math::vector::{self, Vec3d},
+            math::vector::{self, NormalizedVec3d, Vec3d},
             scene::transformation,
         };
 
@@ -132,13 +159,11 @@ mod tests {
                 InvertibleMatrix::try_from(transformation::translation(0.0, 1.0, 0.0)).unwrap(),
             );
 
-            let i = s.intersect(&Ray::new(
-                Point3d::new(1.0, 0.0, 0.0),
-                Vec3d::new(1.0, 0.0, 0.0),
-            ));
+            let r = Ray::new(Point3d::new(1.0, 0.0, 0.0), Vec3d::new(1.0, 0.0, 0.0));
+            let i = s.intersect(&r);
 
             vector::test_utils::assert_vec_approx_equals(
-                &*i[0].normal,
+                &i.normal_at(&i[0], &r),
                 &NormalizedVec3d::new(1.0, -1.0, 0.0).unwrap(),
             );
         }
@@ -147,7 +172,7 @@ mod tests {
         fn computing_normal_on_transformed_shape() {
             let s = Transformed::new(
                 MockObject::default(),
-                InvertibleMatrix::try_from(transformation::sequence(&vec![
+                InvertibleMatrix::try_from(transformation::sequence(&[
                     transformation::rotation_z(std::f64::consts::PI / 5.0),
                     transformation::scaling(1.0, 0.5, 1.0),
                 ]))
@@ -155,16 +180,35 @@ mod tests {
             );
 
             let t = std::f64::consts::SQRT_2 / 2.0;
-            let i = s.intersect(&Ray::new(
-                Point3d::new(0.0, t, -t),
-                Vec3d::new(1.0, 0.0, 0.0),
-            ));
+            let r = Ray::new(Point3d::new(0.0, t, -t), Vec3d::new(1.0, 0.0, 0.0));
+            let i = s.intersect(&r);
 
             vector::test_utils::assert_vec_approx_equals(
-                &*i[0].normal,
+                &i.normal_at(&i[0], &r),
                 &Vec3d::new(0.0, 0.97014, -0.24254),
             );
         }
+
+        #[test]
+        fn closest_intersection_has_the_transformed_normal() {
+            let s = Transformed::new(
+                MockObject::default(),
+                InvertibleMatrix::try_from(transformation::sequence(&[
+                    transformation::rotation_z(std::f64::consts::PI / 5.0),
+                    transformation::scaling(1.0, 0.5, 1.0),
+                ]))
+                .unwrap(),
+            );
+
+            let t = std::f64::consts::SQRT_2 / 2.0;
+            let r = Ray::new(Point3d::new(0.0, t, -t), Vec3d::new(1.0, 0.0, 0.0));
+
+            let mut xs = Intersections::new();
+            let closest = s.intersect_closest(&r, f64::INFINITY, &mut xs).unwrap();
+            let all = s.intersect(&r);
+
+            assert_eq!(xs.normal_at(&closest, &r), all.normal_at(&all[0], &r));
+        }
     }
 
     mod color_at {
@@ -189,12 +233,10 @@ mod tests {
                 InvertibleMatrix::try_from(transformation::scaling(2.0, 2.0, 2.0)).unwrap(),
             );
 
-            let i = shape.intersect(&Ray::new(
-                Point3d::new(2.0, 3.0, 4.0),
-                Vec3d::new(1.0, 0.0, 0.0),
-            ));
+            let r = Ray::new(Point3d::new(2.0, 3.0, 4.0), Vec3d::new(1.0, 0.0, 0.0));
+            let i = shape.intersect(&r);
 
-            assert_eq!(i[0].color, Color::new(1.0, 1.5, 2.0));
+            assert_eq!(i.color_at(&i[0], &r), Color::new(1.0, 1.5, 2.0));
         }
 
         #[test]
@@ -214,12 +256,44 @@ mod tests {
                 InvertibleMatrix::try_from(transformation::scaling(2.0, 2.0, 2.0)).unwrap(),
             );
 
-            let i = shape.intersect(&Ray::new(
-                Point3d::new(2.5, 3.0, 3.5),
-                Vec3d::new(1.0, 0.0, 0.0),
-            ));
+            let r = Ray::new(Point3d::new(2.5, 3.0, 3.5), Vec3d::new(1.0, 0.0, 0.0));
+            let i = shape.intersect(&r);
+
+            assert_eq!(i.color_at(&i[0], &r), Color::new(0.75, 0.5, 0.25));
+        }
+    }
+
+    #[test]
+    fn a_world_space_pattern_stays_put_as_the_object_moves() {
+        use crate::{
+            draw::color,
+            scene::{
+                object::plane::Plane,
+                pattern::{checker3d::Checker3d, Space},
+            },
+        };
+
+        let tile = |x: f64| {
+            Transformed::new(
+                Plane {
+                    material: Some(Material {
+                        surface: Surface::Pattern(Box::new(Checker3d {
+                            space: Space::World,
+                            ..Default::default()
+                        })),
+                        ..Default::default()
+                    }),
+                },
+                InvertibleMatrix::try_from(transformation::translation(x, 0.5, 0.0)).unwrap(),
+            )
+        };
 
-            assert_eq!(i[0].color, Color::new(0.75, 0.5, 0.25));
+        for (x, expected) in [(0.25, color::white()), (1.25, color::black())] {
+            let r = Ray::new(Point3d::new(x, 5.0, 0.25), Vec3d::new(0.0, -1.0, 0.0));
+            for tile in [tile(0.0), tile(0.7)] {
+                let xs = tile.intersect(&r);
+                assert_eq!(xs.color_at(&xs[0], &r), expected);
+            }
         }
     }
 
@@ -228,7 +302,7 @@ mod tests {
         let r = Ray::new(Point3d::new(0.0, 0.0, 0.0), Vec3d::new(0.0, 0.0, 1.0));
         let t1 = transformation::translation(5.0, 0.0, 0.0);
         let t2 = transformation::scaling(2.0, 2.0, 2.0);
-        let sequenced = transformation::sequence(&vec![t1.clone(), t2.clone()]);
+        let sequenced = transformation::sequence(&[t1.clone(), t2.clone()]);
 
         let expectation = Ray::new(Point3d::new(-5.0, 0.0, 0.0), Vec3d::new(0.0, 0.0, 0.5));
 
@@ -272,8 +346,10 @@ mod tests {
             .iter()
             .zip(transformed_at_once_xs.iter())
             .for_each(|(outer, at_once)| {
-                assert_eq!(outer.normal, at_once.normal);
-                assert_eq!(outer.color, at_once.color);
+                assert_eq!(
+                    outer_transformed_xs.shading(outer, &r),
+                    transformed_at_once_xs.shading(at_once, &r)
+                );
             })
     }
 
@@ -348,4 +424,19 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn a_transformed_object_contains_points_in_its_transformed_region() {
+        let s = Transformed::new(
+            Sphere::unit(),
+            InvertibleMatrix::try_from(transformation::sequence(&[
+                transformation::scaling(2.0, 2.0, 2.0),
+                transformation::translation(5.0, 0.0, 0.0),
+            ]))
+            .unwrap(),
+        );
+
+        assert_eq!(s.contains(&Point3d::new(6.5, 0.0, 0.0), 0.0), Some(true));
+        assert_eq!(s.contains(&Point3d::new(0.0, 0.0, 0.0), 0.0), Some(false));
+    }
 }
@@ -1,15 +1,18 @@
 use std::fmt::Debug;
 
 use crate::{
-    draw::color::Color,
     math::{
         point::Point3d,
         vector::{NormalizedVec3d, Vec3d},
     },
-    scene::{intersect::Intersection, material::Material, ray::Ray},
+    scene::{
+        intersect::{Intersection, Intersections},
+        material::Material,
+        ray::Ray,
+    },
 };
 
-use super::{bounded::Bounds, Object};
+use super::{bounded::Bounds, Object, PhysicalObject};
 
 const EPSILON: f64 = 1e-8;
 
@@ -23,11 +26,11 @@ pub struct Triangle {
     points: [Point3d; 3],
     edges: [Vec3d; 2],
     normal: TriangleNormal,
-    material: Material,
+    material: Option<Material>,
 }
 
 impl Triangle {
-    pub fn flat(points: [Point3d; 3], material: Material) -> Self {
+    pub fn flat(points: [Point3d; 3], material: Option<Material>) -> Self {
         let e1 = &points[1] - &points[0];
         let e2 = &points[2] - &points[0];
         let normal = NormalizedVec3d::try_from(e2.cross(&e1)).unwrap();
@@ -40,7 +43,7 @@ impl Triangle {
         }
     }
 
-    pub fn smooth(vertices: [(Point3d, Vec3d); 3], material: Material) -> Self {
+    pub fn smooth(vertices: [(Point3d, Vec3d); 3], material: Option<Material>) -> Self {
         let [(p1, v1), (p2, v2), (p3, v3)] = vertices;
 
         let e1 = &p2 - &p1;
@@ -55,51 +58,98 @@ impl Triangle {
     }
 }
 
-impl Object for Triangle {
-    fn material(&self) -> &Material {
-        &self.material
-    }
-
-    fn intersect(&self, ray: &Ray) -> Vec<Intersection<&dyn Object, Color, NormalizedVec3d>> {
+impl Triangle {
+    /// Where the ray hits the triangle, as `t` and the barycentric coordinates `u` and `v`
+    fn hit(&self, ray: &Ray) -> Option<(f64, f64, f64)> {
         let dir_cross_e2 = ray.direction.cross(&self.edges[1]);
         let det = self.edges[0].dot(&dir_cross_e2);
 
         if det.abs() < EPSILON {
-            vec![]
+            return None;
+        }
+
+        let f = 1.0 / det;
+        let p1_to_origin = &ray.origin - &self.points[0];
+        let u = f * p1_to_origin.dot(&dir_cross_e2);
+
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let origin_cross_e1 = p1_to_origin.cross(&self.edges[0]);
+        let v = f * ray.direction.dot(&origin_cross_e1);
+
+        if v < 0.0 || (u + v) > 1.0 {
+            None
         } else {
-            let f = 1.0 / det;
-            let p1_to_origin = &ray.origin - &self.points[0];
-            let u = f * p1_to_origin.dot(&dir_cross_e2);
-
-            if !(0.0..=1.0).contains(&u) {
-                vec![]
-            } else {
-                let origin_cross_e1 = p1_to_origin.cross(&self.edges[0]);
-                let v = f * ray.direction.dot(&origin_cross_e1);
-
-                if v < 0.0 || (u + v) > 1.0 {
-                    vec![]
-                } else {
-                    let t = f * self.edges[1].dot(&origin_cross_e1);
-
-                    let p = ray.position(t);
-                    let color = self.material().surface.color_at(&p);
-                    let normal = match &self.normal {
-                        TriangleNormal::Flat(n) => n.clone(),
-                        TriangleNormal::Smooth([v1, v2, v3]) => NormalizedVec3d::try_from(
-                            &(&(v2 * u) + &(v3 * v)) + &(v1 * (1.0 - u - v)),
-                        )
-                        .unwrap(),
-                    };
-
-                    let i = Intersection::new(t, self as &dyn Object, color, normal);
-
-                    vec![i]
-                }
+            let t = f * self.edges[1].dot(&origin_cross_e1);
+            Some((t, u, v))
+        }
+    }
+
+    /// The barycentric coordinates `u` and `v` of a point on the triangle
+    fn barycentric(&self, point: &Point3d) -> (f64, f64) {
+        let [e1, e2] = &self.edges;
+        let p1_to_point = point - &self.points[0];
+
+        let d11 = e1.dot(e1);
+        let d12 = e1.dot(e2);
+        let d22 = e2.dot(e2);
+        let dp1 = p1_to_point.dot(e1);
+        let dp2 = p1_to_point.dot(e2);
+        let denominator = d11 * d22 - d12 * d12;
+
+        let u = (d22 * dp1 - d12 * dp2) / denominator;
+        let v = (d11 * dp2 - d12 * dp1) / denominator;
+        (u, v)
+    }
+}
+
+impl PhysicalObject for Triangle {
+    fn normal_at(&self, point: &Point3d) -> NormalizedVec3d {
+        self.hit_normal_at(point, self.barycentric(point))
+    }
+
+    fn hit_normal_at(&self, _point: &Point3d, (u, v): (f64, f64)) -> NormalizedVec3d {
+        match &self.normal {
+            TriangleNormal::Flat(n) => n.clone(),
+            TriangleNormal::Smooth([v1, v2, v3]) => {
+                NormalizedVec3d::try_from(&(&(v2 * u) + &(v3 * v)) + &(v1 * (1.0 - u - v))).unwrap()
             }
         }
     }
 
+    fn intersection_ts(&self, ray: &Ray) -> impl Iterator<Item = f64> {
+        self.hit(ray).map(|(t, _, _)| t).into_iter()
+    }
+}
+
+impl Object for Triangle {
+    fn material(&self) -> Option<&Material> {
+        self.material.as_ref()
+    }
+
+    fn intersect_into<'a>(&'a self, ray: &Ray, xs: &mut Intersections<'a>) {
+        if let Some((t, u, v)) = self.hit(ray) {
+            xs.push(Intersection::with_uv(t, self, (u, v)));
+        }
+    }
+
+    fn intersect_closest<'a>(
+        &'a self,
+        ray: &Ray,
+        t_max: f64,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Intersection<&'a dyn PhysicalObject>> {
+        self.hit(ray)
+            .filter(|&(t, _, _)| (0.0..=t_max).contains(&t))
+            .map(|(t, u, v)| xs.in_current_frame(Intersection::with_uv(t, self, (u, v))))
+    }
+
+    fn occluded<'a>(&'a self, ray: &Ray, t_max: f64, _xs: &mut Intersections<'a>) -> bool {
+        self.hit(ray).is_some_and(|(t, _, _)| t > 0.0 && t < t_max)
+    }
+
     fn bounds(&self) -> Bounds {
         Bounds::from_points(&self.points).expect("triangle should have points")
     }
@@ -215,14 +265,32 @@ mod tests {
 
             assert_eq!(xs.len(), 1);
             assert_eq!(xs[0].t(), 2.0);
-            for x in xs {
+            for x in xs.iter() {
                 let p = r.position(x.t());
                 let n = NormalizedVec3d::new(0.0, 0.0, -1.0).unwrap();
-                let c = t.material().surface.color_at(&p);
-                assert_eq!(x.normal, n);
-                assert_eq!(x.color, c);
+                let c = xs.material(x).surface.color_at(&p);
+                assert_eq!(xs.normal_at(x, &r), n);
+                assert_eq!(xs.color_at(x, &r), c);
             }
         }
+
+        #[test]
+        fn closest_and_occluded_respect_t_max() {
+            let t = test_triangle();
+            let r = Ray::new(Point3d::new(0.0, 0.5, -2.0), Vec3d::new(0.0, 0.0, 1.0));
+
+            assert_eq!(
+                t.intersect_closest(&r, 5.0, &mut Intersections::new())
+                    .unwrap()
+                    .t(),
+                2.0
+            );
+            assert!(t
+                .intersect_closest(&r, 1.0, &mut Intersections::new())
+                .is_none());
+            assert!(t.occluded(&r, 5.0, &mut Intersections::new()));
+            assert!(!t.occluded(&r, 2.0, &mut Intersections::new()));
+        }
     }
 
     mod smooth {
@@ -245,10 +313,30 @@ mod tests {
             let is = t.intersect(&r);
 
             vector::test_utils::assert_vec_approx_equals(
-                &is[0].normal,
+                &is.normal_at(&is[0], &r),
                 &Vec3d::new(-0.55470, 0.83205, 0.0),
             )
         }
+
+        #[test]
+        fn normal_at_a_point_matches_the_normal_at_an_intersection_there() {
+            let t = Triangle::smooth(
+                [
+                    (Point3d::new(0.0, 1.0, 0.0), Vec3d::new(0.0, 1.0, 0.0)),
+                    (Point3d::new(-1.0, 0.0, 0.0), Vec3d::new(-1.0, 0.0, 0.0)),
+                    (Point3d::new(1.0, 0.0, 0.0), Vec3d::new(1.0, 0.0, 0.0)),
+                ],
+                Default::default(),
+            );
+            let r = Ray::new(Point3d::new(-0.2, 0.3, -2.0), Vec3d::new(0.0, 0.0, 1.0));
+
+            let is = t.intersect(&r);
+
+            vector::test_utils::assert_vec_approx_equals(
+                &t.normal_at(&r.position(is[0].t())),
+                &is.normal_at(&is[0], &r),
+            )
+        }
     }
 
     mod bounds {
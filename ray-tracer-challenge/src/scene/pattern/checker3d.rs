@@ -3,12 +3,13 @@ use crate::{
     math::matrix::InvertibleMatrix,
 };
 
-use super::Pattern;
+use super::{Pattern, Space};
 
 pub struct Checker3d {
     pub a: Color,
     pub b: Color,
     pub transform: InvertibleMatrix<4>,
+    pub space: Space,
 }
 
 impl Pattern for Checker3d {
@@ -16,6 +17,10 @@ impl Pattern for Checker3d {
         &self.transform
     }
 
+    fn space(&self) -> Space {
+        self.space
+    }
+
     fn at_local(&self, point: &crate::math::point::Point3d) -> Color {
         if (f64::floor(point.x()) + f64::floor(point.y()) + f64::floor(point.z())) as i64 % 2 == 0 {
             self.a.clone()
@@ -31,6 +36,7 @@ impl Default for Checker3d {
             a: color::white(),
             b: color::black(),
             transform: Default::default(),
+            space: Default::default(),
         }
     }
 }
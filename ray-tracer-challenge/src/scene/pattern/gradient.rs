@@ -3,13 +3,14 @@ use crate::{
     math::matrix::InvertibleMatrix,
 };
 
-use super::Pattern;
+use super::{Pattern, Space};
 
 /// A pattern linearly transitioning from color A to B every 1 unit in the x direction
 pub struct Gradient {
     pub a: Color,
     pub b: Color,
     pub transform: InvertibleMatrix<4>,
+    pub space: Space,
 }
 
 impl Pattern for Gradient {
@@ -17,6 +18,10 @@ impl Pattern for Gradient {
         &self.transform
     }
 
+    fn space(&self) -> Space {
+        self.space
+    }
+
     fn at_local(&self, point: &crate::math::point::Point3d) -> Color {
         let distance = &self.b - &self.a;
         let fraction = point.x() - f64::floor(point.x());
@@ -31,6 +36,7 @@ impl Default for Gradient {
             a: color::white(),
             b: color::black(),
             transform: Default::default(),
+            space: Default::default(),
         }
     }
 }
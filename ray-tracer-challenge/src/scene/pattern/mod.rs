@@ -9,6 +9,21 @@ pub trait Pattern: Sync + Send {
     fn at(&self, point: &Point3d) -> Color {
         self.at_local(&(self.transform().inverse() * point))
     }
+
+    /// The space the pattern's transformation places it in
+    fn space(&self) -> Space {
+        Space::Object
+    }
+}
+
+/// Where a pattern is laid out
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Space {
+    /// On the object, moving, turning and scaling along with it
+    #[default]
+    Object,
+    /// In the world, so that objects move through the pattern and neighbouring objects share it
+    World,
 }
 
 pub mod checker3d;
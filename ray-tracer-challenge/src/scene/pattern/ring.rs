@@ -3,13 +3,14 @@ use crate::{
     math::{matrix::InvertibleMatrix, point::Point3d},
 };
 
-use super::Pattern;
+use super::{Pattern, Space};
 
 /// A pattern alternating between colors A and B in 1-unit rings from the origin on the xz plane
 pub struct Ring {
     pub a: Color,
     pub b: Color,
     pub transform: InvertibleMatrix<4>,
+    pub space: Space,
 }
 
 impl Pattern for Ring {
@@ -17,6 +18,10 @@ impl Pattern for Ring {
         &self.transform
     }
 
+    fn space(&self) -> Space {
+        self.space
+    }
+
     fn at_local(&self, point: &Point3d) -> Color {
         let radius = f64::sqrt(point.x() * point.x() + point.z() * point.z());
 
@@ -34,6 +39,7 @@ impl Default for Ring {
             a: color::white(),
             b: color::black(),
             transform: Default::default(),
+            space: Default::default(),
         }
     }
 }
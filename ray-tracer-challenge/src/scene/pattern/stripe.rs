@@ -3,7 +3,7 @@ use crate::{
     math::{matrix::InvertibleMatrix, point::Point3d},
 };
 
-use super::Pattern;
+use super::{Pattern, Space};
 
 /// A pattern alternating between colors A and B every 1 unit in the x direction
 #[derive(Debug, Clone, PartialEq)]
@@ -11,6 +11,7 @@ pub struct Stripe {
     pub a: Color,
     pub b: Color,
     pub transform: InvertibleMatrix<4>,
+    pub space: Space,
 }
 
 impl Pattern for Stripe {
@@ -18,6 +19,10 @@ impl Pattern for Stripe {
         &self.transform
     }
 
+    fn space(&self) -> Space {
+        self.space
+    }
+
     fn at_local(&self, point: &Point3d) -> Color {
         if f64::floor(point.x()) as i64 % 2 == 0 {
             self.a.clone()
@@ -33,6 +38,7 @@ impl Default for Stripe {
             a: color::white(),
             b: color::black(),
             transform: Default::default(),
+            space: Default::default(),
         }
     }
 }
@@ -0,0 +1,100 @@
+use std::f64::consts;
+
+use crate::{
+    draw::color::{self, Color},
+    math::{kd_tree::KdTree, point::Point3d, vector::Vec3d},
+};
+
+/// How to build the map of light focused onto diffuse surfaces by glass and mirrors
+#[derive(Debug, Clone, PartialEq)]
+pub struct Caustics {
+    /// How many photons to send out from the lights, shared between them
+    pub photons: usize,
+    /// How far around a shaded point to look for photons that landed there
+    pub radius: f64,
+}
+
+/// A bundle of light that landed on a surface
+#[derive(Debug, Clone, PartialEq)]
+pub struct Photon {
+    /// The direction the photon was traveling in when it landed
+    pub direction: Vec3d,
+    pub power: Color,
+}
+
+/// Where photons landed, for working out how much light they bring to any point nearby
+pub struct PhotonMap {
+    photons: KdTree<Photon>,
+    radius: f64,
+}
+
+impl PhotonMap {
+    pub fn new(photons: Vec<(Point3d, Photon)>, radius: f64) -> Self {
+        PhotonMap {
+            photons: KdTree::new(photons),
+            radius,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    /// The light arriving at `point` on the side of the surface that `normal` points out of,
+    /// averaged over the photons within the map's radius
+    pub fn irradiance(&self, point: &Point3d, normal: &Vec3d) -> Color {
+        let mut total = color::black();
+        self.photons.within(point, self.radius, |_, photon| {
+            if photon.direction.dot(normal) < 0.0 {
+                total = &total + &photon.power;
+            }
+        });
+
+        &total * (1.0 / (consts::PI * self.radius * self.radius))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn photon(x: f64, direction: Vec3d) -> (Point3d, Photon) {
+        (
+            Point3d::new(x, 0.0, 0.0),
+            Photon {
+                direction,
+                power: Color::new(1.0, 0.5, 0.0),
+            },
+        )
+    }
+
+    #[test]
+    fn irradiance_is_the_power_landing_per_unit_area() {
+        let down = Vec3d::new(0.0, -1.0, 0.0);
+        let map = PhotonMap::new(
+            vec![
+                photon(0.0, down.clone()),
+                photon(0.5, down.clone()),
+                photon(3.0, down),
+            ],
+            1.0,
+        );
+
+        let e = map.irradiance(&Point3d::new(0.0, 0.0, 0.0), &Vec3d::new(0.0, 1.0, 0.0));
+
+        assert_eq!(e, &Color::new(2.0, 1.0, 0.0) * (1.0 / consts::PI));
+    }
+
+    #[test]
+    fn photons_arriving_from_behind_the_surface_are_ignored() {
+        let map = PhotonMap::new(vec![photon(0.0, Vec3d::new(0.0, 1.0, 0.0))], 1.0);
+
+        let e = map.irradiance(&Point3d::new(0.0, 0.0, 0.0), &Vec3d::new(0.0, 1.0, 0.0));
+
+        assert_eq!(e, color::black());
+    }
+}
@@ -4,11 +4,21 @@ use crate::math::{matrix::SquareMatrix, point::Point3d, vector::Vec3d};
 pub struct Ray {
     pub origin: Point3d,
     pub direction: Vec3d,
+    /// The moment the ray is cast, which decides where moving objects are
+    pub time: f64,
 }
 
 impl Ray {
     pub fn new(origin: Point3d, direction: Vec3d) -> Self {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    pub fn with_time(self, time: f64) -> Self {
+        Ray { time, ..self }
     }
 
     pub fn position(&self, t: f64) -> Point3d {
@@ -16,7 +26,11 @@ impl Ray {
     }
 
     pub fn transform(&self, transform: &SquareMatrix<4>) -> Self {
-        Ray::new(transform * &self.origin, transform * &self.direction)
+        Ray {
+            origin: transform * &self.origin,
+            direction: transform * &self.direction,
+            time: self.time,
+        }
     }
 }
 
@@ -34,6 +48,7 @@ mod tests {
 
         assert_eq!(r.origin, origin);
         assert_eq!(r.direction, direction);
+        assert_eq!(r.time, 0.0);
     }
 
     #[test]
@@ -73,5 +88,14 @@ mod tests {
             assert_eq!(r2.origin, Point3d::new(2.0, 6.0, 12.0));
             assert_eq!(r2.direction, Vec3d::new(0.0, 3.0, 0.0));
         }
+
+        #[test]
+        fn a_transformed_ray_keeps_its_time() {
+            let r = Ray::new(Point3d::new(1.0, 2.0, 3.0), Vec3d::new(0.0, 1.0, 0.0)).with_time(0.5);
+
+            let r2 = r.transform(&transformation::translation(3.0, 4.0, 5.0));
+
+            assert_eq!(r2.time, 0.5);
+        }
     }
 }
@@ -3,9 +3,15 @@ use core::f64;
 use crate::math::{
     matrix::{Matrix, SquareMatrix},
     point::Point3d,
+    quaternion::Quaternion,
     vector::Vec3d,
 };
 
+use super::animation::Interpolate;
+
+/// How far from square the axes of a transformation can be before it counts as shearing
+const SHEAR_TOLERANCE: f64 = 1e-9;
+
 pub fn translation(x: f64, y: f64, z: f64) -> SquareMatrix<4> {
     Matrix::new([
         [1.0, 0.0, 0.0, x],
@@ -85,6 +91,79 @@ pub fn view_transform(from: &Point3d, to: &Point3d, up: &Vec3d) -> SquareMatrix<
     &orientation * &translation(-from.x(), -from.y(), -from.z())
 }
 
+/// A transformation split into a scaling, then a rotation, then a translation, so that two of
+/// them can be blended without the object squashing on the way from one to the other
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decomposed {
+    pub scale: Vec3d,
+    pub rotation: Quaternion,
+    pub translation: Vec3d,
+}
+
+impl Decomposed {
+    /// Fails for transformations that shear or project, which can't be split this way
+    pub fn new(m: &SquareMatrix<4>) -> Result<Self, String> {
+        if (m.at(3, 0), m.at(3, 1), m.at(3, 2), m.at(3, 3)) != (0.0, 0.0, 0.0, 1.0) {
+            return Err(String::from(
+                "A projective transformation can't be decomposed",
+            ));
+        }
+
+        let column = |j: usize| Vec3d::new(m.at(0, j), m.at(1, j), m.at(2, j));
+        let (x, y, z) = (column(0), column(1), column(2));
+        // A mirror image is a negative scaling, since a rotation can't flip the object over
+        let flip = if x.cross(&y).dot(&z) < 0.0 { -1.0 } else { 1.0 };
+        let scale = Vec3d::new(x.mag() * flip, y.mag(), z.mag());
+
+        let (Some(x), Some(y), Some(z)) = (x.norm(), y.norm(), z.norm()) else {
+            return Err(String::from(
+                "A transformation that flattens can't be decomposed",
+            ));
+        };
+        if [x.dot(&y), y.dot(&z), z.dot(&x)]
+            .iter()
+            .any(|d| d.abs() > SHEAR_TOLERANCE)
+        {
+            return Err(String::from(
+                "A transformation that shears can't be decomposed",
+            ));
+        }
+
+        let x = &x * flip;
+        let rotation = Matrix::new([
+            [x.x(), y.x(), z.x(), 0.0],
+            [x.y(), y.y(), z.y(), 0.0],
+            [x.z(), y.z(), z.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+
+        Ok(Decomposed {
+            scale,
+            rotation: Quaternion::from_rotation(&rotation),
+            translation: Vec3d::new(m.at(0, 3), m.at(1, 3), m.at(2, 3)),
+        })
+    }
+
+    pub fn matrix(&self) -> SquareMatrix<4> {
+        let (s, t) = (&self.scale, &self.translation);
+        sequence(&[
+            scaling(s.x(), s.y(), s.z()),
+            self.rotation.matrix(),
+            translation(t.x(), t.y(), t.z()),
+        ])
+    }
+}
+
+impl Interpolate for Decomposed {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        Decomposed {
+            scale: self.scale.interpolate(&other.scale, t),
+            rotation: self.rotation.slerp(&other.rotation, t),
+            translation: self.translation.interpolate(&other.translation, t),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::math::{point::Point3d, vector::Vec3d};
@@ -363,4 +442,60 @@ mod tests {
             matrix::test_utils::assert_matrix_approx_equals(&t, &expected);
         }
     }
+
+    mod decomposed {
+        use std::f64::consts;
+
+        use crate::math::matrix::test_utils::assert_matrix_approx_equals;
+
+        use super::*;
+
+        #[test]
+        fn decomposing_and_recomposing_a_transformation() {
+            let m = sequence(&[
+                scaling(1.0, 2.0, 3.0),
+                rotation_x(0.5),
+                rotation_z(-1.5),
+                translation(4.0, -5.0, 6.0),
+            ]);
+
+            let d = Decomposed::new(&m).unwrap();
+
+            assert!((&d.scale - &Vec3d::new(1.0, 2.0, 3.0)).mag() < 1e-9);
+            assert_eq!(d.translation, Vec3d::new(4.0, -5.0, 6.0));
+            assert_matrix_approx_equals(&d.matrix(), &m);
+        }
+
+        #[test]
+        fn decomposing_a_mirror_image() {
+            let m = scaling(1.0, -1.0, 1.0);
+
+            assert_matrix_approx_equals(&Decomposed::new(&m).unwrap().matrix(), &m);
+        }
+
+        #[test]
+        fn a_shearing_cannot_be_decomposed() {
+            assert!(Decomposed::new(&shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0)).is_err());
+        }
+
+        #[test]
+        fn interpolating_decomposed_transformations() {
+            let a = Decomposed::new(&translation(0.0, 0.0, 0.0)).unwrap();
+            let b = Decomposed::new(&sequence(&[
+                scaling(3.0, 3.0, 3.0),
+                rotation_y(consts::FRAC_PI_2),
+                translation(2.0, 0.0, 0.0),
+            ]))
+            .unwrap();
+
+            assert_matrix_approx_equals(
+                &a.interpolate(&b, 0.5).matrix(),
+                &sequence(&[
+                    scaling(2.0, 2.0, 2.0),
+                    rotation_y(consts::FRAC_PI_4),
+                    translation(1.0, 0.0, 0.0),
+                ]),
+            );
+        }
+    }
 }
@@ -1,13 +1,14 @@
-use std::collections::HashSet;
+use std::{borrow::Cow, collections::HashSet, f64::consts, slice};
 
-use by_address::ByAddress;
+use rand::Rng;
 
 use crate::{
     draw::color::{self, Color},
     math::{
         matrix::{InvertibleMatrix, SquareMatrix},
         point::Point3d,
-        vector::NormalizedVec3d,
+        sampling,
+        vector::{NormalizedVec3d, Vec3d},
     },
     scene::{
         material::{Material, Surface},
@@ -16,18 +17,39 @@ use crate::{
 };
 
 use super::{
-    intersect::{self, Intersection, Precomputation},
-    light::PointLight,
-    material::lighting,
-    object::{sphere::Sphere, transformed::Transformed, Object},
+    camera::{Integrator, RenderOpts},
+    environment::Environment,
+    intersect::{self, Intersection, Intersections, Precomputation},
+    light::{AreaLight, Falloff, PointLight},
+    material::{ambient, lighting},
+    medium::{Fog, Volume},
+    object::{group, sphere::Sphere, transformed::Transformed, Object, PhysicalObject},
+    photon_map::PhotonMap,
     ray::Ray,
 };
 
+mod caustics;
+mod path_tracing;
+
 pub struct World {
     pub objects: Vec<Box<dyn Object>>,
     pub lights: Vec<PointLight>,
+    pub area_lights: Vec<AreaLight>,
     pub max_reflection_depth: usize,
-    pub void_color: Color,
+    pub environment: Environment,
+    pub ambient_occlusion: Option<AmbientOcclusion>,
+    pub fog: Option<Fog>,
+    pub volumes: Vec<Volume>,
+    /// Light focused onto diffuse surfaces by glass and mirrors, filled in by `build_caustics`.
+    /// It adds to the light that shadow rays let through glass, which stays evenly tinted.
+    pub photon_map: Option<PhotonMap>,
+}
+
+/// Darkens the ambient term by how much of the hemisphere above a hit is blocked by nearby geometry
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmbientOcclusion {
+    pub samples: usize,
+    pub max_distance: f64,
 }
 
 impl World {
@@ -38,134 +60,623 @@ impl World {
                 .map(|s| Box::new(s) as Box<dyn Object>)
                 .collect(),
             lights: vec![basic_light()],
+            area_lights: Vec::new(),
             max_reflection_depth: 5,
-            void_color: color::black(),
+            environment: Default::default(),
+            ambient_occlusion: None,
+            fog: None,
+            volumes: Vec::new(),
+            photon_map: None,
         }
     }
 
-    fn intersect(&self, ray: &Ray) -> Vec<Intersection<&dyn Object, Color, NormalizedVec3d>> {
-        let mut intersections = self
-            .objects
+    #[cfg(test)]
+    fn intersect(&self, ray: &Ray) -> Intersections<'_> {
+        let mut xs = Intersections::new();
+        self.intersect_into(ray, &mut xs);
+        xs
+    }
+
+    /// Replaces the contents of `xs` with the sorted intersections of the ray with the world
+    fn intersect_into<'a>(&'a self, ray: &Ray, xs: &mut Intersections<'a>) {
+        xs.clear();
+        self.objects
+            .iter()
+            .for_each(|object| object.intersect_into(ray, xs));
+        intersect::sort(xs);
+    }
+
+    fn intersect_closest<'a>(
+        &'a self,
+        ray: &Ray,
+        t_max: f64,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Intersection<&'a dyn PhysicalObject>> {
+        group::closest_among(&self.objects, ray, t_max, xs)
+    }
+
+    fn occluded<'a>(&'a self, ray: &Ray, t_max: f64, xs: &mut Intersections<'a>) -> bool {
+        self.objects.iter().any(|o| o.occluded(ray, t_max, xs))
+    }
+
+    /// Blends `color`, seen at `t_max` along the ray, with the fog and volumes in front of it,
+    /// using `xs` as scratch space
+    fn through_media<'a>(
+        &'a self,
+        ray: &Ray,
+        t_max: f64,
+        color: Color,
+        xs: &mut Intersections<'a>,
+    ) -> Color {
+        let blend = |color: Color, medium: &Color, transmittance: f64| {
+            &(&color * transmittance) + &(medium * (1.0 - transmittance))
+        };
+
+        let color = self.volumes.iter().fold(color, |c, v| {
+            blend(c, &v.color, v.transmittance(ray, t_max, xs))
+        });
+        match &self.fog {
+            Some(fog) => blend(
+                color,
+                &fog.color,
+                fog.transmittance(t_max * ray.direction.mag()),
+            ),
+            None => color,
+        }
+    }
+
+    /// The fraction of light along the ray that makes it through the fog and volumes before `t_max`
+    fn media_transmittance<'a>(&'a self, ray: &Ray, t_max: f64, xs: &mut Intersections<'a>) -> f64 {
+        let fog = self
+            .fog
+            .as_ref()
+            .map_or(1.0, |fog| fog.transmittance(t_max * ray.direction.mag()));
+        self.volumes
+            .iter()
+            .fold(fog, |acc, v| acc * v.transmittance(ray, t_max, xs))
+    }
+
+    /// Shades a hit, using `xs` as scratch space for any rays cast along the way
+    fn shade_hit<'a>(
+        &'a self,
+        comps: &Precomputation<'a, &'a dyn PhysicalObject>,
+        remaining: usize,
+        glossy_samples: usize,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Color> {
+        self.hit_contributions(comps, remaining, glossy_samples, xs)
+            .map(|c| &c.direct + &(&c.reflected + &c.refracted))
+    }
+
+    /// The light leaving a hit toward the eye, split up by where it came from, or `None` if
+    /// nothing lights the hit and it doesn't glow. Caustics and the surface's own glow reach the
+    /// eye without bouncing, so they count as direct light.
+    fn hit_contributions<'a>(
+        &'a self,
+        comps: &Precomputation<'a, &'a dyn PhysicalObject>,
+        remaining: usize,
+        glossy_samples: usize,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Contributions> {
+        let lit = self
+            .light_contributions(comps, remaining, glossy_samples, xs)
+            .reduce(|acc, c| Contributions {
+                direct: &acc.direct + &c.direct,
+                reflected: &acc.reflected + &c.reflected,
+                refracted: &acc.refracted + &c.refracted,
+            });
+
+        let caustic = self.photon_map.as_ref().map(|map| {
+            let irradiance = map.irradiance(&comps.point, &comps.normal_v);
+            &(&comps.object_color * comps.material.diffuse) * &irradiance
+        });
+        let emissive = Some(&comps.material.emissive)
+            .filter(|e| **e != color::black())
+            .cloned();
+
+        [caustic, emissive]
+            .into_iter()
+            .flatten()
+            .fold(lit, |lit, extra| match lit {
+                Some(c) => Some(Contributions {
+                    direct: &c.direct + &extra,
+                    ..c
+                }),
+                None => Some(Contributions {
+                    direct: extra,
+                    reflected: color::black(),
+                    refracted: color::black(),
+                }),
+            })
+    }
+
+    fn light_contributions<'a, 'b>(
+        &'a self,
+        comps: &'b Precomputation<'a, &'a dyn PhysicalObject>,
+        remaining: usize,
+        glossy_samples: usize,
+        xs: &'b mut Intersections<'a>,
+    ) -> impl Iterator<Item = Contributions> + use<'a, 'b> {
+        let visibility = self
+            .ambient_occlusion
+            .as_ref()
+            .map(|ao| self.ambient_visibility(comps, ao, xs));
+
+        self.light_samples(&comps.over_point).map(move |samples| {
+            let surface_color = self.direct_light(comps, &samples, visibility, xs);
+
+            let reflected_color = self.reflected_color(comps, remaining, glossy_samples, xs);
+            let refracted_color = self.refracted_color(comps, remaining, glossy_samples, xs);
+
+            let m = comps.material;
+            if m.reflectivity > 0.0 && m.transparency > 0.0 {
+                let reflectance = comps.schlick();
+                Contributions {
+                    direct: surface_color,
+                    reflected: &reflected_color * reflectance,
+                    refracted: &refracted_color * (1.0 - reflectance),
+                }
+            } else {
+                Contributions {
+                    direct: surface_color,
+                    reflected: reflected_color,
+                    refracted: refracted_color,
+                }
+            }
+        })
+    }
+
+    /// Each light as seen from `point`, with an area light standing in as a handful of point
+    /// lights spread over it
+    fn light_samples<'a, 'b>(
+        &'a self,
+        point: &'b Point3d,
+    ) -> impl Iterator<Item = Cow<'a, [PointLight]>> + use<'a, 'b> {
+        let point_lights = self
+            .lights
             .iter()
-            .flat_map(|object| object.intersect(ray))
-            .collect::<Vec<_>>();
-        intersect::sort(&mut intersections);
+            .map(|l| Cow::Borrowed(slice::from_ref(l)));
+        let area_lights = self
+            .area_lights
+            .iter()
+            .map(move |l| Cow::Owned(l.point_lights(point)));
 
-        intersections
+        point_lights.chain(area_lights)
     }
 
-    fn shade_hit(&self, comps: &Precomputation<&dyn Object>, remaining: usize) -> Option<Color> {
-        self.lights
+    /// The average light reaching the hit straight from `samples`, all standing in for one light,
+    /// with the ambient term scaled by `visibility`
+    fn direct_light<'a>(
+        &'a self,
+        comps: &Precomputation<'a, &'a dyn PhysicalObject>,
+        samples: &[PointLight],
+        visibility: Option<f64>,
+        xs: &mut Intersections<'a>,
+    ) -> Color {
+        samples
             .iter()
             .map(|light| {
-                let shadow_attenuation = self.shadow_attenuation(&comps.over_point, light);
+                let shadow_attenuation =
+                    self.shadow_attenuation(&comps.over_point, comps.time, light, xs);
 
-                let surface_color = lighting(
-                    comps.object.material(),
+                let mut surface_color = lighting(
+                    comps.material,
                     &comps.point,
                     &comps.object_color,
                     light,
                     &comps.eye_v,
                     &comps.normal_v,
-                    shadow_attenuation,
+                    &shadow_attenuation,
                 );
-
-                let reflected_color = self.reflected_color(comps, remaining);
-                let refracted_color = self.refracted_color(comps, remaining);
-
-                let m = comps.object.material();
-                if m.reflectivity > 0.0 && m.transparency > 0.0 {
-                    let reflectance = comps.schlick();
-                    &surface_color
-                        + &(&(&reflected_color * reflectance)
-                            + &(&refracted_color * (1.0 - reflectance)))
-                } else {
-                    &surface_color + &(&reflected_color + &refracted_color)
+                if let Some(visibility) = visibility {
+                    let ambient = ambient(comps.material, &comps.point, &comps.object_color, light);
+                    surface_color = &surface_color - &(&ambient * (1.0 - visibility));
                 }
+                surface_color
             })
             .reduce(|acc, c| &acc + &c)
+            .map_or_else(color::black, |c| &c * (1.0 / samples.len() as f64))
     }
 
-    fn color_at_internal(&self, ray: &Ray, remaining: usize) -> Color {
-        let xs = self.intersect(ray);
+    /// Finds what the ray hits first, using `xs` as scratch space
+    fn first_hit<'a>(
+        &'a self,
+        ray: &Ray,
+        xs: &mut Intersections<'a>,
+    ) -> Option<Precomputation<'a, &'a dyn PhysicalObject>> {
+        xs.clear();
+        match self.intersect_closest(ray, f64::INFINITY, xs) {
+            // Only refraction needs to know what else the ray passes through
+            Some(h) if xs.material(&h).transparency == 0.0 => Some(h.prepare_computations(ray, xs)),
+            Some(_) => {
+                self.intersect_into(ray, xs);
+                intersect::hit(xs).map(|h| h.prepare_computations(ray, xs))
+            }
+            None => None,
+        }
+    }
 
-        intersect::hit(&xs)
-            .and_then(|h| {
-                let comps = h.prepare_computations(ray, &xs);
-                self.shade_hit(&comps, remaining)
-            })
-            .unwrap_or(self.void_color.clone())
+    fn color_at_internal<'a>(
+        &'a self,
+        ray: &Ray,
+        remaining: usize,
+        glossy_samples: usize,
+        xs: &mut Intersections<'a>,
+    ) -> Color {
+        let comps = self.first_hit(ray, xs);
+
+        // The precomputation doesn't borrow the intersections, so `xs` can be reused while shading
+        let distance = comps.as_ref().map_or(f64::INFINITY, |comps| comps.t);
+        let color = comps
+            .as_ref()
+            .and_then(|comps| self.shade_hit(comps, remaining, glossy_samples, xs))
+            .unwrap_or_else(|| self.environment.color_at(&ray.direction));
+        let color = &color * &inside_transmittance(comps.as_ref(), ray);
+        self.through_media(ray, distance, color, xs)
     }
 
     pub fn color_at(&self, ray: &Ray) -> Color {
-        self.color_at_internal(ray, self.max_reflection_depth)
+        self.color_at_with(ray, &RenderOpts::default())
     }
 
-    fn shadow_attenuation(&self, point: &Point3d, light: &PointLight) -> f64 {
+    /// Like `color_at`, but with the ray tracing settings from `opts`
+    pub fn color_at_with(&self, ray: &Ray, opts: &RenderOpts) -> Color {
+        self.color_at_into(ray, opts, &mut Intersections::new())
+    }
+
+    /// Like `color_at_with`, but reusing `xs` as scratch space, so that a caller tracing many rays
+    /// doesn't allocate a new buffer for each
+    pub fn color_at_into<'a>(
+        &'a self,
+        ray: &Ray,
+        opts: &RenderOpts,
+        xs: &mut Intersections<'a>,
+    ) -> Color {
+        match &opts.integrator {
+            Integrator::Whitted => {
+                self.color_at_internal(ray, self.max_reflection_depth, opts.glossy_samples, xs)
+            }
+            Integrator::PathTracing(settings) => self.path_traced_color(ray, settings, xs),
+        }
+    }
+
+    /// Like `color_at`, but also reports the auxiliary values at the ray's first hit. These are
+    /// always worked out by the Whitted integrator.
+    pub fn sample(&self, ray: &Ray) -> Sample {
+        self.sample_with(ray, &RenderOpts::default())
+    }
+
+    /// Like `sample`, but with the ray tracing settings from `opts`
+    pub fn sample_with(&self, ray: &Ray, opts: &RenderOpts) -> Sample {
+        let mut xs = Intersections::new();
+        let mut scratch = Intersections::new();
+
+        let shaded = self
+            .intersect_with_owner(ray, &mut xs)
+            .map(|(h, object_id)| {
+                let comps = h.prepare_computations(ray, &xs);
+                let transmittance = inside_transmittance(Some(&comps), ray);
+                let contributions = self.hit_contributions(
+                    &comps,
+                    self.max_reflection_depth,
+                    opts.glossy_samples,
+                    &mut scratch,
+                );
+
+                let info = HitInfo {
+                    distance: h.t(),
+                    normal: comps.normal_v,
+                    object_id,
+                    albedo: comps.object_color,
+                    contributions: contributions.clone().unwrap_or(Contributions {
+                        direct: color::black(),
+                        reflected: color::black(),
+                        refracted: color::black(),
+                    }),
+                };
+                (info, contributions.is_some(), transmittance)
+            });
+
+        // As with `color_at`, the environment shows through a surface that nothing lights
+        let color = match &shaded {
+            Some((info, true, _)) => {
+                let c = &info.contributions;
+                &c.direct + &(&c.reflected + &c.refracted)
+            }
+            _ => self.environment.color_at(&ray.direction),
+        };
+        let color = match &shaded {
+            Some((_, _, transmittance)) => &color * transmittance,
+            None => color,
+        };
+        let hit = shaded.map(|(info, _, _)| info);
+        let distance = hit.as_ref().map_or(f64::INFINITY, |info| info.distance);
+        let color = self.through_media(ray, distance, color, &mut scratch);
+
+        Sample { color, hit }
+    }
+
+    /// Fills `xs` like `intersect_into`, returning the hit along with the index into `objects` of
+    /// the top-level object it belongs to
+    fn intersect_with_owner<'a>(
+        &'a self,
+        ray: &Ray,
+        xs: &mut Intersections<'a>,
+    ) -> Option<(Intersection<&'a dyn PhysicalObject>, usize)> {
+        xs.clear();
+        let mut hit: Option<(Intersection<&'a dyn PhysicalObject>, usize)> = None;
+        for (index, object) in self.objects.iter().enumerate() {
+            let start = xs.len();
+            object.intersect_into(ray, xs);
+            let t_max = hit.as_ref().map_or(f64::INFINITY, |(h, _)| h.t());
+            if let Some(h) = intersect::closest(xs[start..].iter().copied(), t_max) {
+                hit = Some((h, index));
+            }
+        }
+        intersect::sort(xs);
+        hit
+    }
+
+    /// The fraction of cosine-weighted hemisphere rays from the hit that escape without hitting
+    /// anything within the configured distance
+    fn ambient_visibility<'a>(
+        &'a self,
+        comps: &Precomputation<'_, &dyn PhysicalObject>,
+        ao: &AmbientOcclusion,
+        xs: &mut Intersections<'a>,
+    ) -> f64 {
+        if ao.samples == 0 {
+            return 1.0;
+        }
+
+        let mut rng = sampling::rng_for_point(&comps.over_point);
+        let unoccluded = (0..ao.samples)
+            .filter(|_| {
+                let direction = sampling::cosine_weighted_hemisphere(&mut rng, &comps.normal_v);
+                let r = Ray::new(comps.over_point.clone(), direction).with_time(comps.time);
+                !self.occluded(&r, ao.max_distance, xs)
+            })
+            .count();
+
+        unoccluded as f64 / ao.samples as f64
+    }
+
+    fn shadow_attenuation<'a>(
+        &'a self,
+        point: &Point3d,
+        time: f64,
+        light: &PointLight,
+        xs: &mut Intersections<'a>,
+    ) -> Color {
         let v = &light.position - point;
         let distance = v.mag();
         let direction = v.norm();
 
         direction
             .map(|d| {
-                let r = Ray {
-                    origin: point.clone(),
-                    direction: d,
+                let r = Ray::new(point.clone(), d).with_time(time);
+
+                let media = self.media_transmittance(&r, distance, xs);
+                if !self.occluded(&r, distance, xs) {
+                    return &color::white() * media;
+                }
+                let closest = self.intersect_closest(&r, distance, xs);
+                if closest.is_some_and(|h| {
+                    h.t() > 0.0 && h.t() < distance && xs.material(&h).transparency == 0.0
+                }) {
+                    return color::black();
+                }
+
+                self.intersect_into(&r, xs);
+                let start = xs.partition_point(|i| i.t() <= 0.0);
+                let end = xs.partition_point(|i| i.t() < distance);
+                let between = &xs[start..end.max(start)];
+
+                let tint = |k: usize| {
+                    let i = &between[k];
+                    let material = xs.material(i);
+                    let tint = &color::white() * material.transparency;
+                    let Some(absorption) = &material.absorption else {
+                        return tint;
+                    };
+
+                    let same = |j: &&Intersection<&dyn PhysicalObject>| {
+                        std::ptr::addr_eq(*j.object(), *i.object())
+                    };
+                    // Having crossed the object an odd number of times, the point is inside it
+                    let length = if xs[..start + k].iter().filter(same).count() % 2 == 1 {
+                        i.t()
+                    } else {
+                        let exit = xs[start + k + 1..].iter().find(same);
+                        exit.map_or(distance, |j| j.t().min(distance)) - i.t()
+                    };
+                    &tint * &absorption.transmittance(length)
                 };
-                let intersections = self.intersect(&r);
-                let mut seen =
-                    HashSet::<ByAddress<&dyn Object>>::with_capacity(intersections.len());
-                intersections
-                    .iter()
-                    .filter(|i| i.t() > 0.0 && i.t() < distance)
-                    .take_while(|i| seen.insert(ByAddress(*i.object())))
-                    .map(|i| i.object().material().transparency)
-                    .product()
+
+                // Stop at the first object seen for the second time
+                let mut seen = HashSet::new();
+                let transmitted = (0..between.len())
+                    .take_while(|&k| seen.insert(*between[k].object() as *const _ as *const ()))
+                    .fold(color::white(), |acc, k| &acc * &tint(k));
+                &transmitted * media
             })
-            .unwrap_or(1.0)
+            .unwrap_or(color::white())
     }
 
-    fn reflected_color(&self, comps: &Precomputation<&dyn Object>, remaining: usize) -> Color {
-        if remaining == 0 || comps.object.material().reflectivity == 0.0 {
+    fn reflected_color<'a>(
+        &'a self,
+        comps: &Precomputation<'_, &dyn PhysicalObject>,
+        remaining: usize,
+        glossy_samples: usize,
+        xs: &mut Intersections<'a>,
+    ) -> Color {
+        if remaining == 0 || comps.material.reflectivity == 0.0 {
             color::black()
         } else {
-            let reflect_ray = Ray::new(comps.over_point.clone(), (*comps.reflect_v).clone());
-            let color = self.color_at_internal(&reflect_ray, remaining - 1);
-            &color * comps.object.material().reflectivity
+            let ray = Ray::new(comps.over_point.clone(), (*comps.reflect_v).clone());
+            let color = self.glossy_color_at(
+                &ray.with_time(comps.time),
+                &comps.normal_v,
+                comps.material.roughness,
+                remaining,
+                glossy_samples,
+                xs,
+            );
+            &color * comps.material.reflectivity
         }
     }
 
-    fn refracted_color(&self, comps: &Precomputation<&dyn Object>, remaining: usize) -> Color {
-        if remaining == 0 || comps.object.material().transparency == 0.0 {
-            color::black()
-        } else {
-            // Snell's law: n_ratio = n1/n2
-            let n_ratio = comps.refraction_exiting / comps.refraction_entering;
-            let cos_i = comps.eye_v.dot(&comps.normal_v);
-            let sin2_t = n_ratio * n_ratio * (1.0 - cos_i * cos_i);
+    fn refracted_color<'a>(
+        &'a self,
+        comps: &Precomputation<'_, &dyn PhysicalObject>,
+        remaining: usize,
+        glossy_samples: usize,
+        xs: &mut Intersections<'a>,
+    ) -> Color {
+        if remaining == 0 || comps.material.transparency == 0.0 {
+            return color::black();
+        }
 
-            if sin2_t > 1.0 {
-                // Total internal reflection
-                color::black()
-            } else {
-                let cos_t = f64::sqrt(1.0 - sin2_t);
-                let direction =
-                    &(&*comps.normal_v * (n_ratio * cos_i - cos_t)) - &(&*comps.eye_v * n_ratio);
-                let refract_ray = Ray::new(comps.under_point.clone(), direction);
-                &self.color_at_internal(&refract_ray, remaining - 1)
-                    * comps.object.material().transparency
-            }
+        // Total internal reflection lets no light through
+        refraction_direction(comps).map_or_else(color::black, |direction| {
+            let ray = Ray::new(comps.under_point.clone(), direction);
+            let color = self.glossy_color_at(
+                &ray.with_time(comps.time),
+                &comps.normal_v,
+                comps.material.roughness,
+                remaining,
+                glossy_samples,
+                xs,
+            );
+            &color * comps.material.transparency
+        })
+    }
+
+    /// The average color seen along rays spread around `ray` by `roughness`, all kept on the same
+    /// side of the surface as the ray. Only the first rough bounce is split into `samples` rays, so
+    /// the cost doesn't multiply with the reflection depth.
+    fn glossy_color_at<'a>(
+        &'a self,
+        ray: &Ray,
+        normal: &Vec3d,
+        roughness: f64,
+        remaining: usize,
+        samples: usize,
+        xs: &mut Intersections<'a>,
+    ) -> Color {
+        if roughness == 0.0 {
+            return self.color_at_internal(ray, remaining - 1, samples, xs);
         }
+
+        let samples = samples.max(1);
+        let mut rng = sampling::rng_for_point(&ray.origin);
+
+        let total = (0..samples)
+            .map(|_| {
+                let ray = Ray {
+                    direction: glossy_direction(&mut rng, &ray.direction, normal, roughness),
+                    ..ray.clone()
+                };
+                self.color_at_internal(&ray, remaining - 1, 1, xs)
+            })
+            .reduce(|acc, c| &acc + &c)
+            .unwrap();
+        &total * (1.0 / samples as f64)
+    }
+}
+
+/// The fraction of light that makes it along the ray to the hit. A ray hitting the inside of an
+/// object has traveled through it to get there, so the object's material may have absorbed some.
+fn inside_transmittance(
+    comps: Option<&Precomputation<'_, &dyn PhysicalObject>>,
+    ray: &Ray,
+) -> Color {
+    comps
+        .filter(|comps| comps.inside)
+        .and_then(|comps| {
+            let a = comps.material.absorption.as_ref()?;
+            Some(a.transmittance(comps.t * ray.direction.mag()))
+        })
+        .unwrap_or_else(color::white)
+}
+
+/// The direction light refracts in at the hit, by Snell's law, or `None` for total internal
+/// reflection
+fn refraction_direction(comps: &Precomputation<'_, &dyn PhysicalObject>) -> Option<Vec3d> {
+    let n_ratio = comps.refraction_exiting / comps.refraction_entering;
+    let cos_i = comps.eye_v.dot(&comps.normal_v);
+    let sin2_t = n_ratio * n_ratio * (1.0 - cos_i * cos_i);
+
+    if sin2_t > 1.0 {
+        None
+    } else {
+        let cos_t = f64::sqrt(1.0 - sin2_t);
+        Some(&(&*comps.normal_v * (n_ratio * cos_i - cos_t)) - &(&*comps.eye_v * n_ratio))
+    }
+}
+
+/// A random direction spread around `direction` by `roughness`, kept on the same side of the
+/// surface with the given normal
+fn glossy_direction<R: Rng + ?Sized>(
+    rng: &mut R,
+    direction: &Vec3d,
+    normal: &Vec3d,
+    roughness: f64,
+) -> Vec3d {
+    let axis = direction.norm().unwrap_or_else(|| direction.clone());
+    let side = axis.dot(normal).signum();
+    let half_angle = roughness.clamp(0.0, 1.0) * consts::FRAC_PI_2;
+
+    let d = sampling::in_cone(rng, &axis, half_angle);
+    // Mirror directions that would cross the surface back to the intended side
+    if d.dot(normal) * side < 0.0 {
+        &d - &(normal * (2.0 * d.dot(normal)))
+    } else {
+        d
     }
 }
 
+/// The portions of a surface's color coming from each kind of light path
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contributions {
+    /// Light arriving straight from the light sources
+    pub direct: Color,
+    pub reflected: Color,
+    pub refracted: Color,
+}
+
+/// The shading values at the first surface hit by a ray
+#[derive(Debug, Clone, PartialEq)]
+pub struct HitInfo {
+    pub distance: f64,
+    /// World-space normal, facing the ray
+    pub normal: NormalizedVec3d,
+    /// Index into `World::objects` of the object that was hit
+    pub object_id: usize,
+    /// Surface color before any lighting is applied
+    pub albedo: Color,
+    pub contributions: Contributions,
+}
+
+/// A ray's final color, along with its first hit if it struck anything
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub color: Color,
+    pub hit: Option<HitInfo>,
+}
+
 impl Default for World {
     fn default() -> Self {
         Self {
             objects: Default::default(),
             lights: Default::default(),
+            area_lights: Vec::new(),
             max_reflection_depth: 5,
-            void_color: color::black(),
+            environment: Default::default(),
+            ambient_occlusion: None,
+            fog: None,
+            volumes: Vec::new(),
+            photon_map: None,
         }
     }
 }
@@ -174,6 +685,7 @@ fn basic_light() -> PointLight {
     PointLight {
         position: Point3d::new(-10.0, 10.0, -10.0),
         intensity: Color::new(1.0, 1.0, 1.0),
+        falloff: Falloff::None,
     }
 }
 
@@ -199,7 +711,15 @@ fn basic_spheres() -> Vec<Transformed<Sphere>> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{draw::color, math::vector::Vec3d, scene::object::plane::Plane};
+    use crate::{
+        draw::color,
+        math::vector::Vec3d,
+        scene::{
+            environment::Sky,
+            material::Absorption,
+            object::{moving::Moving, plane::Plane},
+        },
+    };
 
     use super::*;
 
@@ -243,8 +763,8 @@ mod tests {
         let is = w.intersect(&r);
         let i = &is[0];
 
-        let comps = i.prepare_computations(&r, &vec![]);
-        let c = w.shade_hit(&comps, TEST_DEPTH);
+        let comps = i.prepare_computations(&r, &is);
+        let c = w.shade_hit(&comps, TEST_DEPTH, 1, &mut Intersections::new());
 
         color::test_utils::assert_colors_approx_equal(
             &c.unwrap(),
@@ -258,13 +778,14 @@ mod tests {
         w.lights = vec![PointLight {
             position: Point3d::new(0.0, 0.25, 0.0),
             intensity: color::white(),
+            falloff: Falloff::None,
         }];
         let r = Ray::new(Point3d::new(0.0, 0.0, 0.0), Vec3d::new(0.0, 0.0, 1.0));
         let is = w.intersect(&r);
         let i = &is[2];
 
-        let comps = i.prepare_computations(&r, &vec![]);
-        let c = w.shade_hit(&comps, TEST_DEPTH);
+        let comps = i.prepare_computations(&r, &is);
+        let c = w.shade_hit(&comps, TEST_DEPTH, 1, &mut Intersections::new());
 
         color::test_utils::assert_colors_approx_equal(
             &c.unwrap(),
@@ -280,8 +801,8 @@ mod tests {
         let is = w.intersect(&r);
         let i = &is[0];
 
-        let comps = i.prepare_computations(&r, &vec![]);
-        let c = w.shade_hit(&comps, TEST_DEPTH);
+        let comps = i.prepare_computations(&r, &is);
+        let c = w.shade_hit(&comps, TEST_DEPTH, 1, &mut Intersections::new());
 
         assert_eq!(c, None);
     }
@@ -294,8 +815,8 @@ mod tests {
         let is = w.intersect(&r);
         let i = &is[0];
 
-        let comps = i.prepare_computations(&r, &vec![]);
-        let c = w.shade_hit(&comps, TEST_DEPTH);
+        let comps = i.prepare_computations(&r, &is);
+        let c = w.shade_hit(&comps, TEST_DEPTH, 1, &mut Intersections::new());
 
         color::test_utils::assert_colors_approx_equal(
             &c.unwrap(),
@@ -313,19 +834,17 @@ mod tests {
             lights: vec![PointLight {
                 position: Point3d::new(0.0, 0.0, -10.0),
                 intensity: color::white(),
+                falloff: Falloff::None,
             }],
-            objects: vec![Box::<Sphere>::new(Default::default()), Box::new(shape)],
+            objects: vec![Box::<Sphere>::default(), Box::new(shape)],
             ..Default::default()
         };
-        let r = Ray {
-            origin: Point3d::new(0.0, 0.0, 5.0),
-            direction: Vec3d::new(0.0, 0.0, 1.0),
-        };
+        let r = Ray::new(Point3d::new(0.0, 0.0, 5.0), Vec3d::new(0.0, 0.0, 1.0));
         let is = w.intersect(&r);
         let i = &is[2];
 
-        let comps = i.prepare_computations(&r, &vec![]);
-        let c = w.shade_hit(&comps, TEST_DEPTH);
+        let comps = i.prepare_computations(&r, &is);
+        let c = w.shade_hit(&comps, TEST_DEPTH, 1, &mut Intersections::new());
 
         assert_eq!(c, Some(Color::new(0.1, 0.1, 0.1)));
     }
@@ -333,9 +852,9 @@ mod tests {
     #[test]
     fn color_when_a_ray_misses() {
         let mut w = World::basic();
-        w.void_color = color::blue();
+        w.environment = Environment::constant(color::blue());
         let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 1.0, 0.0));
-        let c = w.color_at_internal(&r, TEST_DEPTH);
+        let c = w.color_at_internal(&r, TEST_DEPTH, 1, &mut Intersections::new());
 
         assert_eq!(c, color::blue());
     }
@@ -344,18 +863,59 @@ mod tests {
     fn color_when_a_ray_hits() {
         let w = World::basic();
         let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
-        let c = w.color_at_internal(&r, TEST_DEPTH);
+        let c = w.color_at_internal(&r, TEST_DEPTH, 1, &mut Intersections::new());
 
         color::test_utils::assert_colors_approx_equal(&c, &Color::new(0.38066, 0.47583, 0.2855));
     }
 
+    #[test]
+    fn color_when_a_ray_misses_comes_from_the_environment() {
+        let mut w = World::basic();
+        w.environment = Environment::new(Sky::Gradient {
+            bottom: color::black(),
+            top: color::white(),
+        });
+        let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 1.0, 0.0));
+        let c = w.color_at_internal(&r, TEST_DEPTH, 1, &mut Intersections::new());
+
+        assert_eq!(c, color::white());
+    }
+
+    #[test]
+    fn reflections_that_escape_the_scene_see_the_environment() {
+        let mirror = Plane {
+            material: Some(Material {
+                surface: Surface::Color(color::black()),
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                reflectivity: 1.0,
+                ..Default::default()
+            }),
+        };
+        let w = World {
+            objects: vec![Box::new(mirror)],
+            lights: vec![basic_light()],
+            environment: Environment::new(Sky::Gradient {
+                bottom: color::black(),
+                top: color::white(),
+            }),
+            ..Default::default()
+        };
+        let r = Ray::new(Point3d::new(0.0, 1.0, 0.0), Vec3d::new(0.0, -1.0, 0.0));
+
+        let c = w.color_at_internal(&r, TEST_DEPTH, 1, &mut Intersections::new());
+
+        assert_eq!(c, color::white());
+    }
+
     #[test]
     fn color_with_an_intersection_behind_the_ray() {
         let mut spheres = basic_spheres();
         let outer = &mut spheres[0];
-        outer.child().material.ambient = 1.0;
+        outer.child().material.as_mut().unwrap().ambient = 1.0;
         let inner = &mut spheres[1];
-        inner.child().material.ambient = 1.0;
+        inner.child().material.as_mut().unwrap().ambient = 1.0;
 
         let w = World {
             objects: spheres
@@ -367,12 +927,109 @@ mod tests {
         };
         let r = Ray::new(Point3d::new(0.0, 0.0, 0.75), Vec3d::new(0.0, 0.0, -1.0));
 
-        let c = w.color_at_internal(&r, TEST_DEPTH);
-        let inner_surface = &w.objects[1].material().surface;
+        let c = w.color_at_internal(&r, TEST_DEPTH, 1, &mut Intersections::new());
+        let inner_surface = &w.objects[1].material().unwrap().surface;
 
         assert!(matches!(inner_surface, Surface::Color(col) if col == &c));
     }
 
+    mod sample {
+        use super::*;
+
+        #[test]
+        fn sampling_a_ray_that_misses() {
+            let mut w = World::basic();
+            w.environment = Environment::constant(color::blue());
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 1.0, 0.0));
+
+            let s = w.sample(&r);
+
+            assert_eq!(s.color, color::blue());
+            assert_eq!(s.hit, None);
+        }
+
+        #[test]
+        fn sampling_a_ray_that_hits() {
+            let w = World::basic();
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+            let s = w.sample(&r);
+            let hit = s.hit.unwrap();
+
+            assert_eq!(s.color, w.color_at(&r));
+            assert_eq!(hit.distance, 4.0);
+            assert_eq!(hit.normal, NormalizedVec3d::new(0.0, 0.0, -1.0).unwrap());
+            assert_eq!(hit.object_id, 0);
+            assert_eq!(hit.albedo, Color::new(0.8, 1.0, 0.6));
+            assert_eq!(hit.contributions.direct, s.color);
+            assert_eq!(hit.contributions.reflected, color::black());
+            assert_eq!(hit.contributions.refracted, color::black());
+        }
+
+        #[test]
+        fn sampling_separates_reflected_light() {
+            let shape = Transformed::new(
+                Plane {
+                    material: Some(Material {
+                        reflectivity: 0.5,
+                        ..Default::default()
+                    }),
+                },
+                InvertibleMatrix::try_from(transformation::translation(0.0, -1.0, 0.0)).unwrap(),
+            );
+            let mut w = World::basic();
+            w.objects.push(Box::new(shape));
+
+            let sqrt2 = std::f64::consts::SQRT_2;
+            let r = Ray::new(
+                Point3d::new(0.0, 0.0, -3.0),
+                Vec3d::new(0.0, -sqrt2 / 2.0, sqrt2 / 2.0),
+            );
+            let hit = w.sample(&r).hit.unwrap();
+
+            assert_eq!(hit.object_id, 2);
+            color::test_utils::assert_colors_approx_equal(
+                &hit.contributions.reflected,
+                &Color::new(0.19033, 0.23791, 0.14274),
+            );
+        }
+
+        #[test]
+        fn sampling_reports_the_hit_in_an_unlit_world() {
+            let mut w = World::basic();
+            w.lights.clear();
+            w.environment = Environment::constant(color::blue());
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+            let s = w.sample(&r);
+            let hit = s.hit.unwrap();
+
+            assert_eq!(s.color, w.color_at(&r));
+            assert_eq!(hit.distance, 4.0);
+            assert_eq!(hit.normal, NormalizedVec3d::new(0.0, 0.0, -1.0).unwrap());
+            assert_eq!(hit.object_id, 0);
+            assert_eq!(hit.contributions.direct, color::black());
+        }
+
+        #[test]
+        fn the_hit_object_is_the_nearest_of_overlapping_objects() {
+            let mut w = World::basic();
+            w.objects.insert(
+                0,
+                Box::new(Transformed::new(
+                    Sphere::unit(),
+                    InvertibleMatrix::try_from(transformation::translation(0.0, 0.0, 5.0)).unwrap(),
+                )),
+            );
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+            let hit = w.sample(&r).hit.unwrap();
+
+            assert_eq!(hit.object_id, 1);
+            assert_eq!(hit.distance, 4.0);
+        }
+    }
+
     mod shadow {
         use super::*;
 
@@ -380,35 +1037,69 @@ mod tests {
         fn no_shadow_when_nothing_collinear_with_point_and_light() {
             let w = World::basic();
             let p = Point3d::new(0.0, 10.0, 0.0);
-            assert_eq!(w.shadow_attenuation(&p, &w.lights[0]), 1.0);
+            assert_eq!(
+                w.shadow_attenuation(&p, 0.0, &w.lights[0], &mut Intersections::new()),
+                color::white()
+            );
         }
 
         #[test]
         fn shadow_when_an_object_is_between_point_and_light() {
             let w = World::basic();
             let p = Point3d::new(10.0, -10.0, 10.0);
-            assert_eq!(w.shadow_attenuation(&p, &w.lights[0]), 0.0);
+            assert_eq!(
+                w.shadow_attenuation(&p, 0.0, &w.lights[0], &mut Intersections::new()),
+                color::black()
+            );
+        }
+
+        #[test]
+        fn a_moving_object_casts_its_shadow_where_it_is_at_the_time() {
+            let mut w = World::basic();
+            let outer = Moving::new(
+                Sphere::unit(),
+                &InvertibleMatrix::identity(),
+                &InvertibleMatrix::try_from(transformation::translation(0.0, 10.0, 0.0)).unwrap(),
+            )
+            .unwrap();
+            w.objects = vec![Box::new(outer)];
+            let p = Point3d::new(10.0, -10.0, 10.0);
+
+            assert_eq!(
+                w.shadow_attenuation(&p, 0.0, &w.lights[0], &mut Intersections::new()),
+                color::black()
+            );
+            assert_eq!(
+                w.shadow_attenuation(&p, 1.0, &w.lights[0], &mut Intersections::new()),
+                color::white()
+            );
         }
 
         #[test]
         fn no_shadow_when_an_object_is_behind_the_light() {
             let w = World::basic();
             let p = Point3d::new(-20.0, 20.0, -20.0);
-            assert_eq!(w.shadow_attenuation(&p, &w.lights[0]), 1.0);
+            assert_eq!(
+                w.shadow_attenuation(&p, 0.0, &w.lights[0], &mut Intersections::new()),
+                color::white()
+            );
         }
 
         #[test]
         fn no_shadow_when_an_object_is_behind_the_point() {
             let w = World::basic();
             let p = Point3d::new(-2.0, 2.0, -2.0);
-            assert_eq!(w.shadow_attenuation(&p, &w.lights[0]), 1.0);
+            assert_eq!(
+                w.shadow_attenuation(&p, 0.0, &w.lights[0], &mut Intersections::new()),
+                color::white()
+            );
         }
 
         #[test]
         fn partial_shadow_when_somewhat_transparent_objects_are_between_point_and_light() {
             let mut spheres = basic_spheres();
-            spheres[0].child().material.transparency = 0.5;
-            spheres[1].child().material.transparency = 1.0;
+            spheres[0].child().material.as_mut().unwrap().transparency = 0.5;
+            spheres[1].child().material.as_mut().unwrap().transparency = 1.0;
             let w = World {
                 objects: spheres
                     .into_iter()
@@ -418,7 +1109,47 @@ mod tests {
                 ..Default::default()
             };
             let p = Point3d::new(10.0, -10.0, 10.0);
-            assert_eq!(w.shadow_attenuation(&p, &w.lights[0]), 0.5);
+            assert_eq!(
+                w.shadow_attenuation(&p, 0.0, &w.lights[0], &mut Intersections::new()),
+                Color::new(0.5, 0.5, 0.5)
+            );
+        }
+
+        fn tinted_glass_world() -> World {
+            World {
+                objects: vec![Box::new(Sphere::new(Material {
+                    transparency: 1.0,
+                    absorption: Some(Absorption {
+                        color: Color::new(1.0, 0.5, 0.25),
+                        density: 1.0,
+                    }),
+                    ..Default::default()
+                }))],
+                lights: vec![basic_light()],
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn tinted_glass_casts_a_colored_shadow_by_its_thickness() {
+            let w = tinted_glass_world();
+            let p = Point3d::new(10.0, -10.0, 10.0);
+
+            color::test_utils::assert_colors_approx_equal(
+                &w.shadow_attenuation(&p, 0.0, &w.lights[0], &mut Intersections::new()),
+                &Color::new(1.0, 0.25, 0.0625),
+            );
+        }
+
+        #[test]
+        fn a_point_inside_tinted_glass_is_tinted_by_the_way_out() {
+            let w = tinted_glass_world();
+            let p = Point3d::new(0.0, 0.0, 0.0);
+
+            color::test_utils::assert_colors_approx_equal(
+                &w.shadow_attenuation(&p, 0.0, &w.lights[0], &mut Intersections::new()),
+                &Color::new(1.0, 0.5, 0.25),
+            );
         }
     }
 
@@ -431,7 +1162,7 @@ mod tests {
         fn reflected_color_for_a_nonreflective_material() {
             let mut spheres = basic_spheres();
             let inner = &mut spheres[1];
-            inner.child().material.ambient = 1.0;
+            inner.child().material.as_mut().unwrap().ambient = 1.0;
 
             let w = World {
                 objects: spheres
@@ -445,8 +1176,8 @@ mod tests {
             let is = w.intersect(&r);
             let i = &is[0];
 
-            let comps = i.prepare_computations(&r, &vec![]);
-            let color = w.reflected_color(&comps, TEST_DEPTH);
+            let comps = i.prepare_computations(&r, &is);
+            let color = w.reflected_color(&comps, TEST_DEPTH, 1, &mut Intersections::new());
 
             assert_eq!(color, color::black());
         }
@@ -455,10 +1186,10 @@ mod tests {
         fn reflected_color_for_reflective_material() {
             let shape = Transformed::new(
                 Plane {
-                    material: Material {
+                    material: Some(Material {
                         reflectivity: 0.5,
                         ..Default::default()
-                    },
+                    }),
                 },
                 InvertibleMatrix::try_from(transformation::translation(0.0, -1.0, 0.0)).unwrap(),
             );
@@ -473,8 +1204,8 @@ mod tests {
             let is = w.intersect(&r);
             let i = &is[0];
 
-            let comps = i.prepare_computations(&r, &vec![]);
-            let color = w.reflected_color(&comps, TEST_DEPTH);
+            let comps = i.prepare_computations(&r, &is);
+            let color = w.reflected_color(&comps, TEST_DEPTH, 1, &mut Intersections::new());
 
             color::test_utils::assert_colors_approx_equal(
                 &color,
@@ -486,10 +1217,10 @@ mod tests {
         fn shade_hit_with_a_reflective_material() {
             let shape = Transformed::new(
                 Plane {
-                    material: Material {
+                    material: Some(Material {
                         reflectivity: 0.5,
                         ..Default::default()
-                    },
+                    }),
                 },
                 InvertibleMatrix::try_from(transformation::translation(0.0, -1.0, 0.0)).unwrap(),
             );
@@ -504,8 +1235,10 @@ mod tests {
             let is = w.intersect(&r);
             let i = &is[0];
 
-            let comps = i.prepare_computations(&r, &vec![]);
-            let color = w.shade_hit(&comps, TEST_DEPTH).unwrap();
+            let comps = i.prepare_computations(&r, &is);
+            let color = w
+                .shade_hit(&comps, TEST_DEPTH, 1, &mut Intersections::new())
+                .unwrap();
 
             color::test_utils::assert_colors_approx_equal(
                 &color,
@@ -518,22 +1251,23 @@ mod tests {
             let light = PointLight {
                 position: Point3d::new(0.0, 0.0, 0.0),
                 intensity: color::white(),
+                falloff: Falloff::None,
             };
             let lower = Transformed::new(
                 Plane {
-                    material: Material {
+                    material: Some(Material {
                         reflectivity: 1.0,
                         ..Default::default()
-                    },
+                    }),
                 },
                 InvertibleMatrix::try_from(transformation::translation(0.0, -1.0, 0.0)).unwrap(),
             );
             let upper = Transformed::new(
                 Plane {
-                    material: Material {
+                    material: Some(Material {
                         reflectivity: 1.0,
                         ..Default::default()
-                    },
+                    }),
                 },
                 InvertibleMatrix::try_from(transformation::translation(0.0, 1.0, 0.0)).unwrap(),
             );
@@ -544,17 +1278,17 @@ mod tests {
             };
             let r = Ray::new(Point3d::new(0.0, 0.0, 0.0), Vec3d::new(0.0, 1.0, 0.0));
 
-            w.color_at_internal(&r, TEST_DEPTH);
+            w.color_at_internal(&r, TEST_DEPTH, 1, &mut Intersections::new());
         }
 
         #[test]
         fn reflected_color_at_maximum_recursive_depth() {
             let shape = Transformed::new(
                 Plane {
-                    material: Material {
+                    material: Some(Material {
                         reflectivity: 0.5,
                         ..Default::default()
-                    },
+                    }),
                 },
                 InvertibleMatrix::try_from(transformation::translation(0.0, -1.0, 0.0)).unwrap(),
             );
@@ -569,8 +1303,8 @@ mod tests {
             let is = w.intersect(&r);
             let i = &is[0];
 
-            let comps = i.prepare_computations(&r, &vec![]);
-            let color = w.reflected_color(&comps, 0);
+            let comps = i.prepare_computations(&r, &is);
+            let color = w.reflected_color(&comps, 0, 1, &mut Intersections::new());
 
             color::test_utils::assert_colors_approx_equal(&color, &color::black());
         }
@@ -592,7 +1326,7 @@ mod tests {
             let xs = shape.intersect(&r);
 
             let comps = xs[0].prepare_computations(&r, &xs);
-            let c = w.refracted_color(&comps, 5);
+            let c = w.refracted_color(&comps, 5, 1, &mut Intersections::new());
 
             assert_eq!(c, color::black());
         }
@@ -607,7 +1341,7 @@ mod tests {
             let xs = shape_ref.intersect(&r);
 
             let comps = xs[0].prepare_computations(&r, &xs);
-            let c = w.refracted_color(&comps, 0);
+            let c = w.refracted_color(&comps, 0, 1, &mut Intersections::new());
 
             assert_eq!(c, color::black());
         }
@@ -616,8 +1350,8 @@ mod tests {
         fn the_refracted_color_under_total_internal_reflection() {
             let mut spheres = basic_spheres();
             let shape = &mut spheres[0];
-            shape.child().material.transparency = 1.0;
-            shape.child().material.refractive_index = 1.5;
+            shape.child().material.as_mut().unwrap().transparency = 1.0;
+            shape.child().material.as_mut().unwrap().refractive_index = 1.5;
             let mut w = World::basic();
             w.objects = spheres
                 .into_iter()
@@ -630,7 +1364,7 @@ mod tests {
             let xs = shape_ref.intersect(&r);
 
             let comps = xs[1].prepare_computations(&r, &xs);
-            let c = w.refracted_color(&comps, 5);
+            let c = w.refracted_color(&comps, 5, 1, &mut Intersections::new());
 
             assert_eq!(c, color::black());
         }
@@ -639,13 +1373,14 @@ mod tests {
         fn the_refracted_color_with_a_refracted_ray() {
             let mut spheres = basic_spheres();
             let a = &mut spheres[0];
-            a.child().material.ambient = 1.0;
-            a.child().material.surface = Surface::Pattern(Box::new(MockPattern {
-                transform: InvertibleMatrix::identity(),
-            }));
+            a.child().material.as_mut().unwrap().ambient = 1.0;
+            a.child().material.as_mut().unwrap().surface =
+                Surface::Pattern(Box::new(MockPattern {
+                    transform: InvertibleMatrix::identity(),
+                }));
             let b = &mut spheres[1];
-            b.child().material.transparency = 1.0;
-            b.child().material.refractive_index = 1.5;
+            b.child().material.as_mut().unwrap().transparency = 1.0;
+            b.child().material.as_mut().unwrap().refractive_index = 1.5;
             let mut w = World::basic();
             w.objects = spheres
                 .into_iter()
@@ -653,10 +1388,10 @@ mod tests {
                 .collect();
 
             let r = Ray::new(Point3d::new(0.0, 0.0, 0.1), Vec3d::new(0.0, 1.0, 0.0));
-            let xs: Vec<_> = w.intersect(&r);
+            let xs = w.intersect(&r);
 
             let comps = xs[2].prepare_computations(&r, &xs);
-            let c = w.refracted_color(&comps, 5);
+            let c = w.refracted_color(&comps, 5, 1, &mut Intersections::new());
 
             color::test_utils::assert_colors_approx_equal(&c, &Color::new(0.0, 0.99888, 0.04721));
         }
@@ -666,21 +1401,21 @@ mod tests {
             let mut w = World::basic();
             let floor = Transformed::new(
                 Plane {
-                    material: Material {
+                    material: Some(Material {
                         transparency: 0.5,
                         refractive_index: 1.5,
                         ..Default::default()
-                    },
+                    }),
                 },
                 InvertibleMatrix::try_from(transformation::translation(0.0, -1.0, 0.0)).unwrap(),
             );
             let ball = Transformed::new(
                 Sphere {
-                    material: Material {
+                    material: Some(Material {
                         surface: Surface::Color(color::red()),
                         ambient: 0.5,
                         ..Default::default()
-                    },
+                    }),
                 },
                 InvertibleMatrix::try_from(transformation::translation(0.0, -3.5, -0.5)).unwrap(),
             );
@@ -695,7 +1430,9 @@ mod tests {
             let xs = w.intersect(&r);
 
             let comps = xs[0].prepare_computations(&r, &xs);
-            let color = w.shade_hit(&comps, 5).unwrap();
+            let color = w
+                .shade_hit(&comps, 5, 1, &mut Intersections::new())
+                .unwrap();
 
             color::test_utils::assert_colors_approx_equal(
                 &color,
@@ -704,27 +1441,473 @@ mod tests {
         }
     }
 
+    #[test]
+    fn light_refracted_through_tinted_glass_is_absorbed_along_the_way() {
+        let w = World {
+            objects: vec![Box::new(Sphere::new(Material {
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                transparency: 1.0,
+                absorption: Some(Absorption {
+                    color: Color::new(1.0, 0.5, 0.25),
+                    density: 1.0,
+                }),
+                ..Default::default()
+            }))],
+            lights: vec![basic_light()],
+            environment: Environment::constant(color::white()),
+            ..Default::default()
+        };
+        let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+        color::test_utils::assert_colors_approx_equal(
+            &w.color_at(&r),
+            &Color::new(1.0, 0.25, 0.0625),
+        );
+    }
+
+    #[test]
+    fn sampling_from_inside_tinted_glass_is_absorbed_too() {
+        let w = World {
+            objects: vec![Box::new(Sphere::new(Material {
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                transparency: 1.0,
+                absorption: Some(Absorption {
+                    color: Color::new(1.0, 0.5, 0.25),
+                    density: 1.0,
+                }),
+                ..Default::default()
+            }))],
+            lights: vec![basic_light()],
+            environment: Environment::constant(color::white()),
+            ..Default::default()
+        };
+        let r = Ray::new(Point3d::new(0.0, 0.0, 0.0), Vec3d::new(0.0, 0.0, 1.0));
+
+        color::test_utils::assert_colors_approx_equal(&w.sample(&r).color, &w.color_at(&r));
+        color::test_utils::assert_colors_approx_equal(
+            &w.sample(&r).color,
+            &Color::new(1.0, 0.5, 0.25),
+        );
+    }
+
+    mod ambient_occlusion {
+        use super::*;
+
+        fn shade_inside_of_inner_sphere(ambient_occlusion: Option<AmbientOcclusion>) -> Color {
+            let mut w = World::basic();
+            w.ambient_occlusion = ambient_occlusion;
+            w.lights = vec![PointLight {
+                position: Point3d::new(0.0, 0.25, 0.0),
+                intensity: color::white(),
+                falloff: Falloff::None,
+            }];
+            let r = Ray::new(Point3d::new(0.0, 0.0, 0.0), Vec3d::new(0.0, 0.0, 1.0));
+            let is = w.intersect(&r);
+
+            let comps = is[2].prepare_computations(&r, &is);
+            w.shade_hit(&comps, TEST_DEPTH, 1, &mut Intersections::new())
+                .unwrap()
+        }
+
+        #[test]
+        fn unoccluded_hit_keeps_its_ambient_term() {
+            let mut w = World::basic();
+            w.ambient_occlusion = Some(AmbientOcclusion {
+                samples: 16,
+                max_distance: 10.0,
+            });
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+            let is = w.intersect(&r);
+
+            let comps = is[0].prepare_computations(&r, &is);
+            let c = w.shade_hit(&comps, TEST_DEPTH, 1, &mut Intersections::new());
+
+            color::test_utils::assert_colors_approx_equal(
+                &c.unwrap(),
+                &Color::new(0.38066, 0.47583, 0.2855),
+            );
+        }
+
+        #[test]
+        fn fully_enclosed_hit_loses_its_ambient_term() {
+            let c = shade_inside_of_inner_sphere(Some(AmbientOcclusion {
+                samples: 16,
+                max_distance: 10.0,
+            }));
+
+            color::test_utils::assert_colors_approx_equal(
+                &c,
+                &Color::new(0.80498, 0.80498, 0.80498),
+            );
+        }
+
+        #[test]
+        fn occluders_beyond_the_max_distance_are_ignored() {
+            let c = shade_inside_of_inner_sphere(Some(AmbientOcclusion {
+                samples: 16,
+                max_distance: 1e-3,
+            }));
+
+            color::test_utils::assert_colors_approx_equal(
+                &c,
+                &Color::new(0.90498, 0.90498, 0.90498),
+            );
+        }
+
+        #[test]
+        fn ambient_occlusion_is_deterministic() {
+            let ao = AmbientOcclusion {
+                samples: 4,
+                max_distance: 0.5,
+            };
+
+            assert_eq!(
+                shade_inside_of_inner_sphere(Some(ao.clone())),
+                shade_inside_of_inner_sphere(Some(ao))
+            );
+        }
+    }
+
+    mod glossy {
+        use crate::scene::object::plane::Plane;
+
+        use super::*;
+
+        /// A mirror floor that only shows its reflection, under a sky that is brightest straight up
+        fn mirror_under_sky(roughness: f64) -> World {
+            World {
+                objects: vec![Box::new(Plane {
+                    material: Some(Material {
+                        ambient: 0.0,
+                        diffuse: 0.0,
+                        specular: 0.0,
+                        reflectivity: 1.0,
+                        roughness,
+                        ..Default::default()
+                    }),
+                })],
+                lights: vec![basic_light()],
+                environment: Environment::new(Sky::Gradient {
+                    bottom: color::black(),
+                    top: color::white(),
+                }),
+                ..Default::default()
+            }
+        }
+
+        fn looking_down(w: &World, glossy_samples: usize) -> Color {
+            let r = Ray::new(Point3d::new(0.0, 1.0, 0.0), Vec3d::new(0.0, -1.0, 0.0));
+            w.color_at_with(
+                &r,
+                &RenderOpts {
+                    glossy_samples,
+                    ..Default::default()
+                },
+            )
+        }
+
+        #[test]
+        fn a_smooth_mirror_reflects_a_single_ray() {
+            let w = mirror_under_sky(0.0);
+
+            assert_eq!(looking_down(&w, 16), color::white());
+        }
+
+        #[test]
+        fn a_rough_mirror_blurs_its_reflection_without_passing_through_itself() {
+            let w = mirror_under_sky(0.5);
+            let c = looking_down(&w, 16);
+
+            assert!(c.r() < 1.0);
+            assert!(c.r() > 0.5);
+        }
+
+        #[test]
+        fn more_glossy_samples_converge_on_the_average_over_the_cone() {
+            let w = mirror_under_sky(0.5);
+
+            // The mean cosine over a cone with a half-angle of 45 degrees
+            let mean_y = (1.0 + std::f64::consts::FRAC_PI_4.cos()) / 2.0;
+            let expected = 0.5 * (mean_y + 1.0);
+            assert!((looking_down(&w, 1024).r() - expected).abs() < 0.01);
+        }
+
+        #[test]
+        fn glossy_reflections_are_deterministic() {
+            let w = mirror_under_sky(0.5);
+
+            assert_eq!(looking_down(&w, 8), looking_down(&w, 8));
+        }
+
+        #[test]
+        fn rough_glass_blurs_what_is_seen_through_it() {
+            let glass = |roughness| World {
+                objects: vec![Box::new(Sphere::new(Material {
+                    ambient: 0.0,
+                    diffuse: 0.0,
+                    specular: 0.0,
+                    transparency: 1.0,
+                    refractive_index: 1.5,
+                    roughness,
+                    ..Default::default()
+                }))],
+                lights: vec![basic_light()],
+                environment: Environment::new(Sky::Gradient {
+                    bottom: color::black(),
+                    top: color::white(),
+                }),
+                ..Default::default()
+            };
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+            let opts = RenderOpts {
+                glossy_samples: 16,
+                ..Default::default()
+            };
+
+            assert_ne!(
+                glass(0.0).color_at_with(&r, &opts),
+                glass(0.3).color_at_with(&r, &opts)
+            );
+        }
+    }
+
+    mod emission {
+        use crate::scene::{
+            light::{AreaLight, LightShape},
+            object::plane::Plane,
+        };
+
+        use super::*;
+
+        fn glowing_sphere() -> Sphere {
+            Sphere::new(Material {
+                emissive: Color::new(0.5, 0.25, 0.0),
+                ..Default::default()
+            })
+        }
+
+        fn floor_under(light: AreaLight, occluder: Option<Sphere>) -> World {
+            let floor = Plane {
+                material: Some(Material {
+                    specular: 0.0,
+                    ..Default::default()
+                }),
+            };
+            let mut objects: Vec<Box<dyn Object>> = vec![Box::new(floor)];
+            objects.push(light.object().unwrap());
+            objects.extend(occluder.map(|o| Box::new(o) as Box<dyn Object>));
+            World {
+                objects,
+                area_lights: vec![light],
+                ..Default::default()
+            }
+        }
+
+        fn sphere_light(samples: usize) -> AreaLight {
+            AreaLight {
+                shape: LightShape::Sphere {
+                    center: Point3d::new(0.0, 10.0, 0.0),
+                    radius: 2.0,
+                },
+                intensity: color::white(),
+                samples,
+                falloff: Falloff::None,
+            }
+        }
+
+        fn looking_down_at(x: f64, w: &World) -> Color {
+            let r = Ray::new(Point3d::new(x, 1.0, 0.0), Vec3d::new(0.0, -1.0, 0.0));
+            w.color_at(&r)
+        }
+
+        #[test]
+        fn an_emissive_surface_glows_without_any_lights() {
+            let w = World {
+                objects: vec![Box::new(glowing_sphere())],
+                ..Default::default()
+            };
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+            assert_eq!(w.color_at(&r), Color::new(0.5, 0.25, 0.0));
+            assert_eq!(w.sample(&r).color, Color::new(0.5, 0.25, 0.0));
+        }
+
+        #[test]
+        fn emission_is_added_to_the_lit_color() {
+            let lit_by = |sphere: Sphere| World {
+                objects: vec![Box::new(sphere)],
+                lights: vec![basic_light()],
+                ..Default::default()
+            };
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+            let plain = lit_by(Sphere::unit()).color_at(&r);
+            let glowing = lit_by(glowing_sphere()).color_at(&r);
+
+            assert_eq!(glowing, &plain + &Color::new(0.5, 0.25, 0.0));
+        }
+
+        #[test]
+        fn an_area_light_lights_the_scene_without_shadowing_itself() {
+            let w = floor_under(sphere_light(4), None);
+
+            // Diffuse light from nearly straight above, plus ambient
+            let c = looking_down_at(0.0, &w);
+            assert!((c.r() - 1.0).abs() < 0.02);
+        }
+
+        #[test]
+        fn an_area_light_is_visible_as_its_glowing_object() {
+            let w = floor_under(sphere_light(4), None);
+            let r = Ray::new(Point3d::new(0.0, 1.0, 0.0), Vec3d::new(0.0, 1.0, 0.0));
+
+            assert_eq!(w.color_at(&r), color::white());
+        }
+
+        #[test]
+        fn an_area_light_casts_soft_shadows() {
+            let occluder = Transformed::new(
+                Sphere::unit(),
+                InvertibleMatrix::try_from(transformation::translation(0.0, 5.0, 0.0)).unwrap(),
+            );
+            let mut w = floor_under(sphere_light(64), None);
+            w.objects.push(Box::new(occluder));
+
+            let umbra = looking_down_at(0.0, &w);
+            let penumbra = looking_down_at(2.0, &w);
+            let lit = looking_down_at(6.0, &w);
+
+            assert_eq!(umbra, Color::new(0.1, 0.1, 0.1));
+            assert!(penumbra.r() > umbra.r() && penumbra.r() < lit.r());
+        }
+    }
+
+    mod media {
+        use crate::scene::medium::{Fog, Volume};
+
+        use super::*;
+
+        fn fog(density: f64) -> Option<Fog> {
+            Some(Fog {
+                color: Color::new(0.5, 0.5, 0.5),
+                density,
+            })
+        }
+
+        #[test]
+        fn a_miss_fades_entirely_into_the_fog() {
+            let w = World {
+                environment: Environment::constant(color::white()),
+                fog: fog(0.1),
+                ..Default::default()
+            };
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+            assert_eq!(w.color_at(&r), Color::new(0.5, 0.5, 0.5));
+        }
+
+        #[test]
+        fn a_hit_is_blended_with_the_fog_by_distance() {
+            let mut w = World::basic();
+            // Lit only by ambient light, so the fog between it and the light doesn't matter
+            w.objects = vec![Box::new(Sphere::new(Material {
+                surface: Surface::Color(color::red()),
+                ambient: 1.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                ..Default::default()
+            }))];
+            w.fog = fog(0.1);
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+            let t = f64::exp(-0.4);
+            color::test_utils::assert_colors_approx_equal(
+                &w.color_at(&r),
+                &(&(&color::red() * t) + &(&Color::new(0.5, 0.5, 0.5) * (1.0 - t))),
+            );
+        }
+
+        #[test]
+        fn a_volume_tints_rays_passing_through_it() {
+            let w = World {
+                environment: Environment::constant(color::white()),
+                volumes: vec![Volume::new(Sphere::unit(), 0.5, color::black()).unwrap()],
+                ..Default::default()
+            };
+            let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+            color::test_utils::assert_colors_approx_equal(
+                &w.color_at(&r),
+                &(&color::white() * f64::exp(-1.0)),
+            );
+        }
+
+        #[test]
+        fn fog_thins_the_light_reaching_a_point() {
+            let mut w = World::basic();
+            w.fog = fog(0.1);
+            let p = Point3d::new(0.0, 10.0, 0.0);
+
+            let attenuation =
+                w.shadow_attenuation(&p, 0.0, &w.lights[0], &mut Intersections::new());
+
+            let t = f64::exp(-0.1 * 200f64.sqrt());
+            color::test_utils::assert_colors_approx_equal(&attenuation, &Color::new(t, t, t));
+        }
+
+        #[test]
+        fn a_volume_between_point_and_light_casts_a_partial_shadow() {
+            let mut w = World::basic();
+            w.objects.clear();
+            w.volumes
+                .push(Volume::new(Sphere::unit(), 1.0, color::black()).unwrap());
+            let p = Point3d::new(10.0, -10.0, 10.0);
+
+            let attenuation =
+                w.shadow_attenuation(&p, 0.0, &w.lights[0], &mut Intersections::new());
+
+            let t = f64::exp(-2.0);
+            color::test_utils::assert_colors_approx_equal(&attenuation, &Color::new(t, t, t));
+        }
+
+        #[test]
+        fn an_opaque_object_still_casts_a_full_shadow_through_fog() {
+            let mut w = World::basic();
+            w.fog = fog(0.1);
+            let p = Point3d::new(10.0, -10.0, 10.0);
+
+            assert_eq!(
+                w.shadow_attenuation(&p, 0.0, &w.lights[0], &mut Intersections::new()),
+                color::black()
+            );
+        }
+    }
+
     #[test]
     fn shade_hit_with_a_reflective_transparent_material() {
         let mut w = World::basic();
         let floor = Transformed::new(
             Plane {
-                material: Material {
+                material: Some(Material {
                     transparency: 0.5,
                     refractive_index: 1.5,
                     reflectivity: 0.5,
                     ..Default::default()
-                },
+                }),
             },
             InvertibleMatrix::try_from(transformation::translation(0.0, -1.0, 0.0)).unwrap(),
         );
         let ball = Transformed::new(
             Sphere {
-                material: Material {
+                material: Some(Material {
                     surface: Surface::Color(color::red()),
                     ambient: 0.5,
                     ..Default::default()
-                },
+                }),
             },
             InvertibleMatrix::try_from(transformation::translation(0.0, -3.5, -0.5)).unwrap(),
         );
@@ -739,7 +1922,9 @@ mod tests {
         let xs = w.intersect(&r);
 
         let comps = xs[0].prepare_computations(&r, &xs);
-        let color = w.shade_hit(&comps, 5).unwrap();
+        let color = w
+            .shade_hit(&comps, 5, 1, &mut Intersections::new())
+            .unwrap();
 
         color::test_utils::assert_colors_approx_equal(
             &color,
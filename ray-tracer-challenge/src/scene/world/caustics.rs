@@ -0,0 +1,312 @@
+use std::f64::consts;
+
+use rand::Rng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use crate::{
+    draw::color::Color,
+    math::{point::Point3d, sampling, vector::Vec3d},
+    scene::{
+        intersect::Intersections,
+        light::Falloff,
+        photon_map::{Caustics, Photon, PhotonMap},
+        ray::Ray,
+    },
+};
+
+use super::{refraction_direction, World};
+
+impl World {
+    /// Sends photons out from the lights and keeps the ones that glass and mirrors focus onto
+    /// diffuse surfaces, so that `color_at` can show the caustics they make
+    pub fn build_caustics(&mut self, settings: &Caustics) {
+        type Emit<'a> = Box<dyn Fn(&mut Xoshiro256PlusPlus) -> Ray + 'a>;
+        let point_lights = self.lights.iter().map(|l| {
+            // A point light shines equally in every direction, over the whole sphere
+            let emit: Emit = Box::new(|rng| {
+                let direction = sampling::in_cone(rng, &Vec3d::new(0.0, 1.0, 0.0), consts::PI);
+                Ray::new(l.position.clone(), direction)
+            });
+            (&l.intensity, &l.falloff, emit)
+        });
+        let area_lights = self.area_lights.iter().map(|l| {
+            let emit: Emit = Box::new(|rng| l.shape.random_emission(rng));
+            (&l.intensity, &l.falloff, emit)
+        });
+        let sources: Vec<_> = point_lights.chain(area_lights).collect();
+
+        let mut photons = Vec::new();
+        let mut xs = Intersections::new();
+        let per_light = settings.photons / sources.len().max(1);
+        for (k, (intensity, falloff, emit)) in sources.iter().enumerate() {
+            let mut rng = sampling::rng_for_point(&Point3d::new(k as f64, 0.0, 0.0));
+            // Every light gives off as much power in all as a point light of the same intensity
+            // does over the whole sphere, however it spreads it out
+            let power = *intensity * (4.0 * consts::PI / per_light as f64);
+
+            for _ in 0..per_light {
+                let ray = emit(&mut rng);
+                photons.extend(self.trace_photon(ray, power.clone(), falloff, &mut rng, &mut xs));
+            }
+        }
+
+        self.photon_map = Some(PhotonMap::new(photons, settings.radius));
+    }
+
+    /// Follows a photon through any number of mirror bounces and refractions to where it lands on
+    /// a diffuse surface, or `None` if it never touches glass or a mirror on the way there
+    fn trace_photon<'a>(
+        &'a self,
+        mut ray: Ray,
+        mut power: Color,
+        falloff: &Falloff,
+        rng: &mut Xoshiro256PlusPlus,
+        xs: &mut Intersections<'a>,
+    ) -> Option<(Point3d, Photon)> {
+        for bounce in 0..=self.max_reflection_depth {
+            let comps = self.first_hit(&ray, xs)?;
+            let m = comps.material;
+
+            if bounce == 0 {
+                // Photons spreading out already dim with the square of the distance, so swap that
+                // dimming for the light's own falloff on the way to the first surface they hit
+                let distance = comps.t * ray.direction.mag();
+                power = &power * (distance * distance * falloff.factor(distance));
+            } else if m.transparency == 0.0 && m.diffuse > 0.0 {
+                let photon = Photon {
+                    direction: ray.direction.clone(),
+                    power,
+                };
+                return Some((comps.point, photon));
+            }
+            if let Some(absorption) = m.absorption.as_ref().filter(|_| comps.inside) {
+                power = &power * &absorption.transmittance(comps.t * ray.direction.mag());
+            }
+
+            let (reflect, refract) = if m.reflectivity > 0.0 && m.transparency > 0.0 {
+                let reflectance = comps.schlick();
+                (
+                    m.reflectivity * reflectance,
+                    m.transparency * (1.0 - reflectance),
+                )
+            } else {
+                (m.reflectivity, m.transparency)
+            };
+
+            // Carry on down one path, with odds matching how much light takes it, or be absorbed
+            let total = reflect + refract;
+            if total > 1.0 {
+                power = &power * total;
+            }
+            let choice = rng.gen::<f64>() * total.max(1.0);
+            ray = if choice < reflect {
+                Ray::new(comps.over_point.clone(), (*comps.reflect_v).clone())
+            } else if choice < total {
+                Ray::new(comps.under_point.clone(), refraction_direction(&comps)?)
+            } else {
+                return None;
+            };
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        draw::color,
+        math::matrix::InvertibleMatrix,
+        scene::{
+            camera::RenderOpts,
+            light::{AreaLight, LightShape, PointLight},
+            material::Material,
+            object::{plane::Plane, sphere::Sphere, transformed::Transformed},
+            transformation,
+        },
+    };
+
+    use super::*;
+
+    /// A glass ball hanging over a floor, lit from straight above
+    fn lens_over_floor() -> World {
+        let ball = Transformed::new(
+            Sphere::new(Material {
+                diffuse: 0.0,
+                specular: 0.0,
+                transparency: 1.0,
+                refractive_index: 1.5,
+                ..Default::default()
+            }),
+            InvertibleMatrix::try_from(transformation::translation(0.0, 2.0, 0.0)).unwrap(),
+        );
+        World {
+            objects: vec![Box::new(Plane { material: None }), Box::new(ball)],
+            lights: vec![PointLight {
+                position: Point3d::new(0.0, 10.0, 0.0),
+                intensity: color::white(),
+                falloff: Falloff::None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn caustics() -> Caustics {
+        Caustics {
+            photons: 20000,
+            radius: 0.1,
+        }
+    }
+
+    fn looking_down_at(w: &World, x: f64) -> Color {
+        let r = Ray::new(Point3d::new(x, 0.5, 0.0), Vec3d::new(0.0, -1.0, 0.0));
+        w.color_at(&r)
+    }
+
+    #[test]
+    fn only_photons_through_glass_or_off_mirrors_are_kept() {
+        let mut w = lens_over_floor();
+        w.build_caustics(&caustics());
+        let kept = w.photon_map.as_ref().unwrap().len();
+
+        // Half the photons head down, and only those aimed at the ball go through glass
+        assert!(kept > 0);
+        assert!(kept < 1000);
+    }
+
+    #[test]
+    fn a_world_without_glass_or_mirrors_has_no_caustics() {
+        let mut w = lens_over_floor();
+        w.objects.truncate(1);
+        w.build_caustics(&caustics());
+
+        assert!(w.photon_map.unwrap().is_empty());
+    }
+
+    #[test]
+    fn glass_focuses_light_onto_the_floor_below_it() {
+        let mut w = lens_over_floor();
+        let without = looking_down_at(&w, 0.0);
+        w.build_caustics(&caustics());
+        let focused = looking_down_at(&w, 0.0);
+
+        assert!(focused.r() > without.r() + 0.5);
+    }
+
+    #[test]
+    fn glass_still_lets_light_through_its_shadow_with_caustics() {
+        let mut w = lens_over_floor();
+        let under_ball = Point3d::new(0.9, 0.0, 0.0);
+        let without = looking_down_at(&w, 0.9);
+        w.build_caustics(&caustics());
+
+        assert_eq!(
+            w.shadow_attenuation(&under_ball, 0.0, &w.lights[0], &mut Intersections::new()),
+            color::white()
+        );
+        // Just outside the caustic, where no photons land, but still under the ball
+        assert_eq!(looking_down_at(&w, 0.9), without);
+        assert!(without.r() > 0.1);
+    }
+
+    #[test]
+    fn opaque_objects_still_cast_full_shadows_with_caustics() {
+        let mut w = lens_over_floor();
+        let blocker = Transformed::new(
+            Sphere::unit(),
+            InvertibleMatrix::try_from(transformation::translation(3.0, 2.0, 0.0)).unwrap(),
+        );
+        w.objects.push(Box::new(blocker));
+        w.build_caustics(&caustics());
+
+        assert_eq!(
+            w.shadow_attenuation(
+                &Point3d::new(3.0, 0.0, 0.0),
+                0.0,
+                &w.lights[0],
+                &mut Intersections::new()
+            ),
+            color::black()
+        );
+    }
+
+    #[test]
+    fn caustics_dim_with_the_light_they_come_from() {
+        let mut w = lens_over_floor();
+        w.build_caustics(&caustics());
+        let bright = w
+            .photon_map
+            .as_ref()
+            .unwrap()
+            .irradiance(&Point3d::new(0.0, 0.0, 0.0), &Vec3d::new(0.0, 1.0, 0.0));
+
+        w.lights[0].falloff = Falloff::InverseSquare { radius: 4.0 };
+        w.build_caustics(&caustics());
+        let dimmed = w
+            .photon_map
+            .as_ref()
+            .unwrap()
+            .irradiance(&Point3d::new(0.0, 0.0, 0.0), &Vec3d::new(0.0, 1.0, 0.0));
+
+        // Photons first hit the upper half of the ball, between 7 and 8 units from the light
+        assert!(dimmed.r() <= bright.r() * 16.0 / 49.0);
+        assert!(dimmed.r() >= bright.r() * 16.0 / 64.0);
+    }
+
+    #[test]
+    fn sampling_shows_caustics_too() {
+        let mut w = lens_over_floor();
+        w.build_caustics(&caustics());
+        let r = Ray::new(Point3d::new(0.0, 0.5, 0.0), Vec3d::new(0.0, -1.0, 0.0));
+        let opts = RenderOpts::default();
+
+        assert_eq!(w.sample_with(&r, &opts).color, w.color_at_with(&r, &opts));
+    }
+
+    #[test]
+    fn area_lights_give_off_as_much_power_as_point_lights() {
+        // Wide enough to gather every photon the ball focuses onto the floor
+        let gather_all = Caustics {
+            radius: 5.0,
+            ..caustics()
+        };
+        let total_power = |w: &World| {
+            w.photon_map
+                .as_ref()
+                .unwrap()
+                .irradiance(&Point3d::new(0.0, 0.0, 0.0), &Vec3d::new(0.0, 1.0, 0.0))
+        };
+
+        let mut w = lens_over_floor();
+        w.build_caustics(&gather_all);
+        let from_point = total_power(&w);
+
+        let light = AreaLight {
+            shape: LightShape::Sphere {
+                center: Point3d::new(0.0, 10.0, 0.0),
+                radius: 0.5,
+            },
+            intensity: color::white(),
+            samples: 1,
+            falloff: Falloff::None,
+        };
+        w.lights.clear();
+        w.objects.push(light.object().unwrap());
+        w.area_lights.push(light);
+        w.build_caustics(&gather_all);
+        let from_area = total_power(&w);
+
+        assert!(from_area.r() > from_point.r() * 0.9);
+        assert!(from_area.r() < from_point.r() * 1.1);
+    }
+
+    #[test]
+    fn caustics_are_deterministic() {
+        let mut w = lens_over_floor();
+        w.build_caustics(&caustics());
+        let a = looking_down_at(&w, 0.0);
+        w.build_caustics(&caustics());
+
+        assert_eq!(a, looking_down_at(&w, 0.0));
+    }
+}
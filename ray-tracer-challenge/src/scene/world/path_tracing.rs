@@ -0,0 +1,332 @@
+use rand::Rng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use crate::{
+    draw::color::{self, Color},
+    math::sampling,
+    scene::{camera::PathTracing, intersect::Intersections, ray::Ray},
+};
+
+use super::{glossy_direction, refraction_direction, World};
+
+/// Russian roulette never lets a path survive with certainty, so that bright paths still end
+const MAX_SURVIVAL: f64 = 0.95;
+
+impl World {
+    /// The average color of the paths traced out from the ray
+    pub(super) fn path_traced_color<'a>(
+        &'a self,
+        ray: &Ray,
+        settings: &PathTracing,
+        xs: &mut Intersections<'a>,
+    ) -> Color {
+        let mut rng = sampling::rng_for_point(&(&ray.origin + &ray.direction));
+        let samples = settings.samples_per_pixel.max(1);
+
+        let total = (0..samples)
+            .map(|_| self.trace_path(ray.clone(), settings, &mut rng, xs))
+            .reduce(|acc, c| &acc + &c)
+            .unwrap();
+        &total * (1.0 / samples as f64)
+    }
+
+    /// The light carried back along a single random path starting with the ray. At each hit the
+    /// path picks one of reflecting, refracting or bouncing off diffusely, with odds weighted by
+    /// how much the material does of each.
+    fn trace_path<'a>(
+        &'a self,
+        mut ray: Ray,
+        settings: &PathTracing,
+        rng: &mut Xoshiro256PlusPlus,
+        xs: &mut Intersections<'a>,
+    ) -> Color {
+        let mut radiance = color::black();
+        let mut throughput = color::white();
+        // Area lights are already sampled directly at diffuse hits, so a diffuse bounce landing on
+        // one mustn't count its glow a second time. Other glowing surfaces are only ever found by
+        // bouncing into them.
+        let mut after_diffuse = false;
+
+        for bounce in 0..=settings.max_bounces {
+            let Some(comps) = self.first_hit(&ray, xs) else {
+                let sky = self.environment.color_at(&ray.direction);
+                let seen = self.through_media(&ray, f64::INFINITY, sky, xs);
+                radiance = &radiance + &(&throughput * &seen);
+                break;
+            };
+
+            let in_front = self.through_media(&ray, comps.t, color::black(), xs);
+            radiance = &radiance + &(&throughput * &in_front);
+            throughput = &throughput * self.media_transmittance(&ray, comps.t, xs);
+            if let Some(absorption) = comps.material.absorption.as_ref().filter(|_| comps.inside) {
+                let traveled = comps.t * ray.direction.mag();
+                throughput = &throughput * &absorption.transmittance(traveled);
+            }
+
+            let m = comps.material;
+            let sampled_directly = after_diffuse
+                && self
+                    .area_lights
+                    .iter()
+                    .any(|l| l.shape.is_on_surface(&comps.point));
+            if !sampled_directly {
+                radiance = &radiance + &(&throughput * &m.emissive);
+            }
+
+            // Light bounced around the scene takes the place of the ambient term
+            let direct = self
+                .light_samples(&comps.over_point)
+                .map(|samples| self.direct_light(&comps, &samples, Some(0.0), xs))
+                .fold(color::black(), |acc, c| &acc + &c);
+            radiance = &radiance + &(&throughput * &direct);
+
+            if bounce == settings.max_bounces {
+                break;
+            }
+            if bounce >= settings.min_bounces {
+                let survival = throughput.r().max(throughput.g()).max(throughput.b());
+                let survival = survival.min(MAX_SURVIVAL);
+                if rng.gen::<f64>() >= survival {
+                    break;
+                }
+                throughput = &throughput * (1.0 / survival);
+            }
+
+            let (reflect, refract) = if m.reflectivity > 0.0 && m.transparency > 0.0 {
+                let reflectance = comps.schlick();
+                (
+                    m.reflectivity * reflectance,
+                    m.transparency * (1.0 - reflectance),
+                )
+            } else {
+                (m.reflectivity, m.transparency)
+            };
+            let total = reflect + refract + m.diffuse;
+            if total <= 0.0 {
+                break;
+            }
+
+            // Dividing each choice's weight by the odds of making it leaves `total` behind
+            let choice = rng.gen::<f64>() * total;
+            ray = if choice < reflect {
+                after_diffuse = false;
+                let d = glossy_direction(rng, &comps.reflect_v, &comps.normal_v, m.roughness);
+                throughput = &throughput * total;
+                Ray::new(comps.over_point.clone(), d).with_time(ray.time)
+            } else if choice < reflect + refract {
+                let Some(refracted) = refraction_direction(&comps) else {
+                    break;
+                };
+                after_diffuse = false;
+                let d = glossy_direction(rng, &refracted, &comps.normal_v, m.roughness);
+                throughput = &throughput * total;
+                Ray::new(comps.under_point.clone(), d).with_time(ray.time)
+            } else {
+                after_diffuse = true;
+                let d = sampling::cosine_weighted_hemisphere(rng, &comps.normal_v);
+                throughput = &(&throughput * &comps.object_color) * total;
+                Ray::new(comps.over_point.clone(), d).with_time(ray.time)
+            };
+        }
+
+        radiance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        math::{matrix::InvertibleMatrix, point::Point3d, vector::Vec3d},
+        scene::{
+            camera::{Integrator, RenderOpts},
+            environment::Environment,
+            light::{Falloff, PointLight},
+            material::{Material, Surface},
+            object::{plane::Plane, sphere::Sphere, transformed::Transformed, Object},
+            transformation,
+        },
+    };
+
+    use super::*;
+
+    fn path_traced(samples_per_pixel: usize) -> RenderOpts {
+        RenderOpts {
+            integrator: Integrator::PathTracing(PathTracing {
+                samples_per_pixel,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn matte(color: Color) -> Material {
+        Material {
+            surface: Surface::Color(color),
+            specular: 0.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_ray_that_misses_sees_the_environment() {
+        let w = World {
+            environment: Environment::constant(Color::new(0.2, 0.4, 0.6)),
+            ..Default::default()
+        };
+        let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+        assert_eq!(
+            w.color_at_with(&r, &path_traced(4)),
+            Color::new(0.2, 0.4, 0.6)
+        );
+    }
+
+    #[test]
+    fn a_glowing_surface_seen_directly() {
+        let w = World {
+            objects: vec![Box::new(Sphere::new(Material {
+                diffuse: 0.0,
+                emissive: Color::new(1.0, 0.5, 0.25),
+                ..Default::default()
+            }))],
+            ..Default::default()
+        };
+        let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+        assert_eq!(
+            w.color_at_with(&r, &path_traced(4)),
+            Color::new(1.0, 0.5, 0.25)
+        );
+    }
+
+    #[test]
+    fn a_diffuse_surface_under_a_white_sky_picks_up_light_from_all_around() {
+        // With nothing but the sky lighting it, the floor reflects its albedo of the sky's light
+        let w = World {
+            objects: vec![Box::new(Plane {
+                material: Some(Material {
+                    diffuse: 1.0,
+                    ..matte(Color::new(0.5, 0.5, 0.5))
+                }),
+            })],
+            environment: Environment::constant(color::white()),
+            ..Default::default()
+        };
+        let r = Ray::new(Point3d::new(0.0, 1.0, 0.0), Vec3d::new(0.0, -1.0, 0.0));
+
+        let c = w.color_at_with(&r, &path_traced(64));
+
+        assert_eq!(c, Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn light_bounces_off_a_nearby_wall_into_a_shadow() {
+        let floor = Plane {
+            material: Some(matte(color::white())),
+        };
+        let wall = Transformed::new(
+            Plane {
+                material: Some(matte(color::red())),
+            },
+            InvertibleMatrix::try_from(transformation::sequence(&[
+                transformation::rotation_z(std::f64::consts::FRAC_PI_2),
+                transformation::translation(1.0, 0.0, 0.0),
+            ]))
+            .unwrap(),
+        );
+        let blocker = Transformed::new(
+            Sphere::unit(),
+            InvertibleMatrix::try_from(transformation::translation(0.0, 3.0, 0.0)).unwrap(),
+        );
+        let objects: Vec<Box<dyn Object>> =
+            vec![Box::new(floor), Box::new(wall), Box::new(blocker)];
+        let w = World {
+            objects,
+            lights: vec![PointLight {
+                position: Point3d::new(-0.5, 10.0, 0.0),
+                intensity: color::white(),
+                falloff: Falloff::None,
+            }],
+            ..Default::default()
+        };
+        let r = Ray::new(Point3d::new(0.0, 1.0, 0.0), Vec3d::new(0.0, -1.0, 0.0));
+
+        let whitted = w.color_at(&r);
+        let bounced = w.color_at_with(&r, &path_traced(64));
+
+        // Only the path tracer sees red light bounced off the lit wall into the shadow
+        assert_eq!(whitted.r(), whitted.g());
+        assert!(bounced.r() > bounced.g());
+    }
+
+    #[test]
+    fn a_glowing_object_that_is_not_a_light_lights_a_wall_by_bouncing() {
+        let wall = Plane {
+            material: Some(Material {
+                diffuse: 1.0,
+                ..matte(color::white())
+            }),
+        };
+        let lamp = Transformed::new(
+            Sphere::new(Material {
+                diffuse: 0.0,
+                emissive: color::white(),
+                ..Default::default()
+            }),
+            InvertibleMatrix::try_from(transformation::translation(0.0, 2.0, 0.0)).unwrap(),
+        );
+        let objects: Vec<Box<dyn Object>> = vec![Box::new(wall), Box::new(lamp)];
+        let w = World {
+            objects,
+            ..Default::default()
+        };
+        let r = Ray::new(Point3d::new(3.0, 1.0, 0.0), Vec3d::new(-1.0, -1.0, 0.0));
+
+        let c = w.color_at_with(&r, &path_traced(64));
+
+        assert!(c.r() > 0.0);
+    }
+
+    #[test]
+    fn path_tracing_is_deterministic() {
+        let w = World::basic();
+        let r = Ray::new(Point3d::new(0.0, 0.0, -5.0), Vec3d::new(0.0, 0.0, 1.0));
+
+        assert_eq!(
+            w.color_at_with(&r, &path_traced(8)),
+            w.color_at_with(&r, &path_traced(8))
+        );
+    }
+
+    #[test]
+    fn paths_end_after_the_maximum_number_of_bounces() {
+        // Between two facing mirrors a path would otherwise bounce forever
+        let mirror = |y: f64| {
+            Transformed::new(
+                Plane {
+                    material: Some(Material {
+                        diffuse: 0.0,
+                        reflectivity: 1.0,
+                        ..Default::default()
+                    }),
+                },
+                InvertibleMatrix::try_from(transformation::translation(0.0, y, 0.0)).unwrap(),
+            )
+        };
+        let w = World {
+            objects: vec![Box::new(mirror(-1.0)), Box::new(mirror(1.0))],
+            ..Default::default()
+        };
+        let r = Ray::new(Point3d::new(0.0, 0.0, 0.0), Vec3d::new(0.0, 1.0, 0.0));
+        let opts = RenderOpts {
+            integrator: Integrator::PathTracing(PathTracing {
+                samples_per_pixel: 1,
+                min_bounces: 100,
+                max_bounces: 100,
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(w.color_at_with(&r, &opts), color::black());
+    }
+}
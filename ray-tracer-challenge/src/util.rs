@@ -3,14 +3,39 @@ use std::{fs, time::SystemTime};
 use crate::draw::canvas::Canvas;
 
 pub fn write_to_file(c: &Canvas, filename_prefix: &str) {
-    let ppm_data = c.ppm();
-    let filename = format!(
-        "{}-{}.ppm",
+    write_bytes(c.ppm().as_bytes(), filename_prefix, &timestamp(), "ppm")
+}
+
+/// Write the canvas as a floating-point Portable Float Map, for grading in external tools
+pub fn write_pfm_to_file(c: &Canvas, filename_prefix: &str) {
+    write_bytes(&c.pfm(), filename_prefix, &timestamp(), "pfm")
+}
+
+/// Write the canvas as a Radiance .hdr image, for grading in external tools
+pub fn write_hdr_to_file(c: &Canvas, filename_prefix: &str) {
+    write_bytes(&c.hdr(), filename_prefix, &timestamp(), "hdr")
+}
+
+/// Write the canvas as frame number `frame` of an animation, numbered so that the frames sort in
+/// order
+pub fn write_frame_to_file(c: &Canvas, filename_prefix: &str, frame: usize) {
+    write_bytes(
+        c.ppm().as_bytes(),
         filename_prefix,
-        SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("time went backwards")
-            .as_secs()
-    );
-    fs::write(filename, ppm_data).expect("unable to write file")
+        &format!("{:04}", frame),
+        "ppm",
+    )
+}
+
+fn timestamp() -> String {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("time went backwards")
+        .as_secs()
+        .to_string()
+}
+
+fn write_bytes(data: &[u8], filename_prefix: &str, suffix: &str, extension: &str) {
+    let filename = format!("{}-{}.{}", filename_prefix, suffix, extension);
+    fs::write(filename, data).expect("unable to write file")
 }
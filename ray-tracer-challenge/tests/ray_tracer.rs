@@ -8,7 +8,7 @@ use ray_tracer_challenge::{
     math::{matrix::InvertibleMatrix, point::Point3d, vector::Vec3d},
     scene::{
         camera::Camera,
-        light::PointLight,
+        light::{Falloff, PointLight},
         material::{Material, Surface},
         object::{
             bounded::Bounded,
@@ -18,7 +18,6 @@ use ray_tracer_challenge::{
             plane::Plane,
             sphere::Sphere,
             transformed::Transformed,
-            Object,
         },
         pattern::{checker3d::Checker3d, stripe::Stripe},
         transformation,
@@ -64,7 +63,7 @@ fn test_mirror_scene() -> Scene {
     );
     let sphere_group = Bounded::new(Transformed::new(
         Group::new(vec![Box::new(gs1), Box::new(gs2)]),
-        InvertibleMatrix::try_from(transformation::sequence(&vec![
+        InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::rotation_z(consts::FRAC_PI_2),
             transformation::translation(-2.0, 2.0, 0.0),
         ]))
@@ -72,31 +71,32 @@ fn test_mirror_scene() -> Scene {
     ));
 
     let floor = Plane {
-        material: Material {
+        material: Some(Material {
             surface: Surface::Pattern(Box::new(Checker3d {
                 a: color::white(),
                 b: color::black(),
                 transform: InvertibleMatrix::try_from(transformation::translation(0.0, -0.01, 0.0))
                     .unwrap(),
+                ..Default::default()
             })),
             specular: 0.0,
             reflectivity: 0.02,
             ..Default::default()
-        },
+        }),
     };
 
     let left_wall = Transformed::new(
         Plane {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(color::white()),
                 specular: 1.0,
                 reflectivity: 0.9,
                 shininess: 400.0,
                 diffuse: 0.0,
                 ..Default::default()
-            },
+            }),
         },
-        InvertibleMatrix::try_from(transformation::sequence(&vec![
+        InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::rotation_x(consts::FRAC_PI_2),
             transformation::rotation_y(-consts::FRAC_PI_3),
             transformation::translation(-8.0, 0.0, 0.0),
@@ -106,16 +106,16 @@ fn test_mirror_scene() -> Scene {
 
     let right_wall = Transformed::new(
         Plane {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(color::white()),
                 specular: 1.0,
                 reflectivity: 1.0,
                 shininess: 400.0,
                 diffuse: 0.0,
                 ..Default::default()
-            },
+            }),
         },
-        InvertibleMatrix::try_from(transformation::sequence(&vec![
+        InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::rotation_x(consts::FRAC_PI_2),
             transformation::rotation_y(consts::FRAC_PI_4),
             transformation::translation(10.0, 0.0, 0.0),
@@ -125,14 +125,14 @@ fn test_mirror_scene() -> Scene {
 
     let middle_wall = Transformed::new(
         Plane {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(Color::new(0.945, 0.788, 0.647)),
                 specular: 0.1,
                 shininess: 50.0,
                 ..Default::default()
-            },
+            }),
         },
-        InvertibleMatrix::try_from(transformation::sequence(&vec![
+        InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::rotation_x(consts::FRAC_PI_2),
             transformation::translation(0.0, 0.0, 7.0),
         ]))
@@ -141,7 +141,7 @@ fn test_mirror_scene() -> Scene {
 
     let ball = Transformed::new(
         Sphere {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(Color::new(0.059, 0.322, 0.729)),
                 diffuse: 0.3,
                 specular: 1.0,
@@ -149,14 +149,14 @@ fn test_mirror_scene() -> Scene {
                 transparency: 0.75,
                 refractive_index: 1.52,
                 ..Default::default()
-            },
+            }),
         },
         InvertibleMatrix::try_from(transformation::translation(0.0, 2.0, 0.0)).unwrap(),
     );
 
     let inner_air_pocket = Transformed::new(
         Sphere {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(color::white()),
                 ambient: 0.0,
                 diffuse: 0.0,
@@ -165,9 +165,9 @@ fn test_mirror_scene() -> Scene {
                 refractive_index: 1.0,
                 reflectivity: 1.0,
                 ..Default::default()
-            },
+            }),
         },
-        InvertibleMatrix::try_from(transformation::sequence(&vec![
+        InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::scaling(0.5, 0.5, 0.5),
             transformation::translation(0.0, 2.0, 0.0),
         ]))
@@ -176,29 +176,30 @@ fn test_mirror_scene() -> Scene {
 
     let behind_cube = Transformed::new(
         Cube {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Pattern(Box::new(Stripe {
                     a: Color::new(0.545, 0.0, 0.0),
                     b: Color::new(0.0, 0.392, 0.0),
                     transform: InvertibleMatrix::try_from(transformation::scaling(0.2, 1.0, 1.0))
                         .unwrap(),
+                    ..Default::default()
                 })),
                 ..Default::default()
-            },
+            }),
         },
         InvertibleMatrix::try_from(transformation::translation(3.0, 0.0, -10.0)).unwrap(),
     );
 
     let behind_wall = Transformed::new(
         Plane {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(Color::new(0.678, 0.847, 0.902)),
                 specular: 0.1,
                 shininess: 50.0,
                 ..Default::default()
-            },
+            }),
         },
-        InvertibleMatrix::try_from(transformation::sequence(&vec![
+        InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::rotation_x(consts::FRAC_PI_2),
             transformation::translation(0.0, 0.0, -100.0),
         ]))
@@ -208,6 +209,7 @@ fn test_mirror_scene() -> Scene {
     let light_source = PointLight {
         position: Point3d::new(-10.0, 10.0, -10.0),
         intensity: color::white(),
+        falloff: Falloff::None,
     };
 
     let world = World {
@@ -253,6 +255,7 @@ fn test_obj_scene() -> Scene {
     let light_source = PointLight {
         position: Point3d::new(-2.0, 20.0, -30.0),
         intensity: color::white(),
+        falloff: Falloff::None,
     };
 
     let world = World {
@@ -277,15 +280,16 @@ fn test_obj_scene() -> Scene {
 fn test_csg_scene() -> Scene {
     let room = Transformed::new(
         Cube {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Pattern(Box::new(Checker3d {
                     a: Color::new(0.6, 0.6, 0.6),
                     b: Color::new(0.7, 0.7, 0.7),
-                    transform: InvertibleMatrix::try_from(transformation::sequence(&vec![
+                    transform: InvertibleMatrix::try_from(transformation::sequence(&[
                         transformation::translation(0.01, 0.01, 0.01),
                         transformation::scaling(0.02, 0.02, 0.02),
                     ]))
                     .unwrap(),
+                    ..Default::default()
                 })),
                 reflectivity: 0.0,
                 ambient: 0.5,
@@ -293,45 +297,47 @@ fn test_csg_scene() -> Scene {
                 diffuse: 0.3,
                 specular: 0.3,
                 ..Default::default()
-            },
+            }),
         },
         InvertibleMatrix::try_from(transformation::scaling(50.0, 50.0, 50.0)).unwrap(),
     );
 
-    let hollow_circle = Csg::<Box<dyn Object>> {
+    let hollow_circle = Csg {
         left: Box::new(Sphere {
-            material: Material {
+            material: Some(Material {
                 surface: Surface::Color(color::green()),
                 ..Default::default()
-            },
+            }),
         }),
         right: Box::new(Transformed::new(
             Sphere {
-                material: Material {
+                material: Some(Material {
                     surface: Surface::Color(color::blue()),
                     ..Default::default()
-                },
+                }),
             },
             InvertibleMatrix::try_from(transformation::scaling(0.7, 0.7, 0.7)).unwrap(),
         )),
         operation: CsgOperation::Difference,
+        material: None,
     };
-    let object = Csg::<Box<dyn Object>> {
+    let object = Csg {
         left: Box::new(hollow_circle),
         right: Box::new(Transformed::new(
             Cube {
-                material: Material {
+                material: Some(Material {
                     surface: Surface::Color(color::red()),
                     ..Default::default()
-                },
+                }),
             },
             InvertibleMatrix::try_from(transformation::translation(1.0, 0.0, 0.0)).unwrap(),
         )),
         operation: CsgOperation::Difference,
+        material: None,
     };
     let object_transformed = Transformed::new(
         object,
-        InvertibleMatrix::try_from(transformation::sequence(&vec![
+        InvertibleMatrix::try_from(transformation::sequence(&[
             transformation::rotation_y(consts::FRAC_PI_6),
             transformation::scaling(7.0, 7.0, 7.0),
         ]))
@@ -341,11 +347,13 @@ fn test_csg_scene() -> Scene {
     let light_source_1 = PointLight {
         position: Point3d::new(-2.0, 20.0, -30.0),
         intensity: Color::new(0.5, 0.5, 0.5),
+        falloff: Falloff::None,
     };
 
     let light_source_2 = PointLight {
         position: Point3d::new(10.0, 20.0, -30.0),
         intensity: Color::new(0.5, 0.5, 0.5),
+        falloff: Falloff::None,
     };
 
     let world = World {
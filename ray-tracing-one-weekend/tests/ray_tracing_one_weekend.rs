@@ -82,9 +82,9 @@ fn test_render() {
         .unwrap();
 
     let out_str = String::from_utf8(out).unwrap();
-    fs::write("test-out.ppm", &out_str).unwrap();
+    fs::write(std::env::temp_dir().join("test-out.ppm"), &out_str).unwrap();
     assert!(
-        &out_str == expected_data,
+        out_str == expected_data,
         "output image did not equal expectation"
     );
 }